@@ -1,21 +1,33 @@
 /*!
-This crate provides a derive macro for the [FromRequest](actix_web::FromRequest) trait.
+This crate provides a derive macro for the [FromRequest](actix_web::FromRequest) trait
+and the `require_role` attribute macro for handlers.
 */
 
 use derive_elves::type_aware_impl;
 use proc_macro::TokenStream;
+use quote::format_ident;
 use quote::quote;
 use syn::parse_macro_input;
 use syn::DeriveInput;
-use syn::Ident;
+use syn::Expr;
+use syn::ExprClosure;
+use syn::FnArg;
+use syn::ItemFn;
+use syn::LitStr;
+use syn::Pat;
+use syn::ReturnType;
+use syn::Type;
 
 /**
 This macro implements the [FromRequest](actix_web::FromRequest) trait for the annotated type.
 
+The generated code refers to `actix-web` through the re-export of the `actix-jwt-auth-middleware` crate,
+so it keeps working if `actix-web` is renamed or only available transitively.
+
 ## Example
 
 ```rust
-# use actix_jwt_auth_middleware_derive::FromRequest;
+# use actix_jwt_auth_middleware::FromRequest;
 #
 #[derive(Clone, FromRequest)]
 struct UserClaims {
@@ -28,13 +40,42 @@ enum Role {
     User,
     Admin
 }
+```
+
+## Mapping the Claims
+
+With the `#[from_request(map = "...")]` attribute, the annotated type is not looked up itself,
+instead the claims type named by the argument of the closure are looked up and passed to the closure by reference.
+This allows extracting a part of the claims without writing an extractor for every part.
+
+```rust
+# use actix_jwt_auth_middleware::FromRequest;
+#
+#[derive(Clone)]
+struct FullClaims {
+    user: User,
+    scopes: Vec<String>,
+}
+
+#[derive(Clone, FromRequest)]
+#[from_request(map = "|claims: &FullClaims| claims.user.clone()")]
+struct User {
+    id: u32,
+}
+```
 */
-#[proc_macro_derive(FromRequest)]
+#[proc_macro_derive(FromRequest, attributes(from_request))]
 pub fn from_request(tokenstream: TokenStream) -> TokenStream {
     let input = parse_macro_input!(tokenstream as DeriveInput);
     let ident = &input.ident;
 
-    let lower_case_ident = Ident::new(&ident.to_string().to_lowercase(), ident.span());
+    let extraction = match map_attribute(&input) {
+        Ok(Some((claims_type, map))) => quote!(
+            ::actix_jwt_auth_middleware::map_claims::<#claims_type, _, _>(req, #map)
+        ),
+        Ok(None) => quote!(::actix_jwt_auth_middleware::extract_claims::<#ident>(req)),
+        Err(err) => return err.to_compile_error().into(),
+    };
 
     let error = format!(
         "could not extract type \"{}\" from HttpRequest extensions",
@@ -45,14 +86,17 @@ pub fn from_request(tokenstream: TokenStream) -> TokenStream {
         quote!(
             // stolen from https://stackoverflow.com/questions/63673447/how-can-i-pass-structs-from-an-actix-middleware-to-the-handler
 
-            impl actix_web::FromRequest for #ident { // works
-                type Error = ::actix_web::Error;
-                type Future = std::future::Ready<Result<Self, Self::Error>>;
-                fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
-                    std::future::ready(
-                        match <actix_web::HttpRequest as actix_web::HttpMessage>::extensions(req).get::<#ident>() {
-                            Some(#lower_case_ident) => Ok(#lower_case_ident.clone()),
-                            None => Err(actix_web::error::ErrorBadRequest(#error))
+            impl ::actix_jwt_auth_middleware::__private::actix_web::FromRequest for #ident { // works
+                type Error = ::actix_jwt_auth_middleware::__private::actix_web::Error;
+                type Future = ::std::future::Ready<::std::result::Result<Self, Self::Error>>;
+                fn from_request(
+                    req: &::actix_jwt_auth_middleware::__private::actix_web::HttpRequest,
+                    _: &mut ::actix_jwt_auth_middleware::__private::actix_web::dev::Payload,
+                ) -> Self::Future {
+                    ::std::future::ready(
+                        match #extraction {
+                            ::std::option::Option::Some(__claims) => ::std::result::Result::Ok(__claims),
+                            ::std::option::Option::None => ::std::result::Result::Err(::actix_jwt_auth_middleware::__private::actix_web::error::ErrorBadRequest(#error))
                         }
                     )
                 }
@@ -62,3 +106,158 @@ pub fn from_request(tokenstream: TokenStream) -> TokenStream {
     )
     .into()
 }
+
+/*
+    Parses the closure of the `#[from_request(map = "...")]` attribute,
+    alongside the claims type referenced by the type of its argument.
+*/
+fn map_attribute(input: &DeriveInput) -> syn::Result<Option<(Type, ExprClosure)>> {
+    let mut map = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("from_request"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("map") {
+                map = Some(meta.value()?.parse::<LitStr>()?.parse::<ExprClosure>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `from_request` attribute, expected `map`"))
+            }
+        })?;
+    }
+    let Some(map) = map else {
+        return Ok(None);
+    };
+
+    let claims_type = match map.inputs.first() {
+        Some(Pat::Type(pat_type)) if map.inputs.len() == 1 => match &*pat_type.ty {
+            Type::Reference(reference) => Some((*reference.elem).clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    match claims_type {
+        Some(claims_type) => Ok(Some((claims_type, map))),
+        None => Err(syn::Error::new_spanned(
+            &map.inputs,
+            "the closure has to take a single reference to the claims, like `|claims: &Claims| ...`",
+        )),
+    }
+}
+
+/**
+This attribute macro only runs the annotated handler if the claims passed as its **first** argument grant the given role,
+otherwise a `403 Forbidden` error is returned.
+
+The claims type has to implement the `HasRole` trait of the `actix-jwt-auth-middleware` crate for the type of the role.
+The return type of the handler is wrapped in a `Result`, so it can be any [Responder](actix_web::Responder).
+
+## Example
+
+```rust
+# use actix_jwt_auth_middleware::{require_role, FromRequest, HasRole};
+# use actix_web::{get, Responder};
+#
+#[derive(Clone, FromRequest)]
+struct UserClaims {
+    id: u32,
+    role: Role,
+}
+
+#[derive(Clone, PartialEq)]
+enum Role {
+    User,
+    Admin
+}
+
+impl HasRole<Role> for UserClaims {
+    fn has_role(&self, role: &Role) -> bool {
+        self.role == *role
+    }
+}
+
+#[get("/admin")]
+#[require_role(Role::Admin)]
+async fn admin(user_claims: UserClaims) -> impl Responder {
+    format!("Hello admin with id: {}", user_claims.id)
+}
+```
+*/
+#[proc_macro_attribute]
+pub fn require_role(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let role = parse_macro_input!(attr as Expr);
+    let handler = parse_macro_input!(item as ItemFn);
+
+    wrap_handler(role, handler).unwrap_or_else(|err| err.to_compile_error().into())
+}
+
+/*
+    Moves the handler into a nested function, called by a wrapper with the same signature
+    once the role of the claims in the first argument has been checked.
+*/
+fn wrap_handler(role: Expr, handler: ItemFn) -> syn::Result<TokenStream> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = handler;
+
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            sig.fn_token,
+            "`require_role` can only be applied to async handlers",
+        ));
+    }
+
+    let mut arg_idents = Vec::new();
+    let mut args = Vec::new();
+    for (index, input) in sig.inputs.iter().enumerate() {
+        match input {
+            FnArg::Typed(pat_type) => {
+                let ident = format_ident!("__arg{}", index);
+                let ty = &pat_type.ty;
+                args.push(quote!(#ident: #ty));
+                arg_idents.push(ident);
+            }
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "`require_role` can not be applied to methods",
+                ))
+            }
+        }
+    }
+    let Some(claims) = arg_idents.first() else {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            "the first argument of the handler has to be the claims",
+        ));
+    };
+
+    let output = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let ident = &sig.ident;
+    let (impl_generics, _, where_clause) = sig.generics.split_for_impl();
+    let error = format!("missing the role required by \"{}\"", ident);
+
+    let mut inner_sig = sig.clone();
+    inner_sig.ident = format_ident!("__handler");
+
+    Ok(quote!(
+        #(#attrs)*
+        #vis async fn #ident #impl_generics (#(#args),*) -> ::std::result::Result<#output, ::actix_jwt_auth_middleware::__private::actix_web::Error> #where_clause {
+            #inner_sig #block
+
+            if !::actix_jwt_auth_middleware::HasRole::has_role(&#claims, &#role) {
+                return ::std::result::Result::Err(::actix_jwt_auth_middleware::__private::actix_web::error::ErrorForbidden(#error));
+            }
+            ::std::result::Result::Ok(__handler(#(#arg_idents),*).await)
+        }
+    )
+    .into())
+}
@@ -0,0 +1,97 @@
+use crate::AuthError;
+use crate::AuthResult;
+use crate::TokenSigner;
+
+use std::future::ready;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use jwt_compact::Algorithm;
+use jwt_compact::CreationError;
+use serde::Serialize;
+
+/**
+    Future returned by [`AsyncTokenSigner::sign`].
+*/
+pub type SignFuture<'a> = Pin<Box<dyn Future<Output = AuthResult<String>> + 'a>>;
+
+pub(crate) type SharedAsyncTokenSigner<Claims> = Arc<dyn AsyncTokenSigner<Claims> + Send + Sync>;
+
+/**
+    Signs access tokens asynchronously.
+
+    Hardware security modules and key management services sign remotely, so the signing key never has to be present on the server.
+    Setting an implementation of this trait as the `async_token_signer` of the [`crate::Authority`] delegates the signing
+    of renewed access tokens to it.
+
+    The [`TokenSigner`] implements this trait by signing in memory.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{AsyncTokenSigner, SignFuture};
+    # use serde::Serialize;
+    #[derive(Serialize)]
+    struct User {
+        id: u32,
+    }
+
+    struct KmsSigner;
+
+    impl AsyncTokenSigner<User> for KmsSigner {
+        fn sign<'a>(&'a self, claims: &'a User) -> SignFuture<'a> {
+            Box::pin(async move {
+                // send the claims to the key management service and return the signed token
+                # let _ = claims;
+                # unimplemented!()
+            })
+        }
+    }
+    ```
+*/
+pub trait AsyncTokenSigner<Claims> {
+    /**
+        Signs the `claims` as an access token and returns the compact token.
+    */
+    fn sign<'a>(&'a self, claims: &'a Claims) -> SignFuture<'a>;
+
+    /**
+        Signs the `claims` as an access token bound to a device, storing the `fingerprint_hash` in its `fgp` claim.
+
+        The [`crate::Authority`] calls this instead of [`Self::sign`] if a `fingerprint_extractor` is set,
+        since tokens without the claim are rejected with [`AuthError::FingerprintMismatch`].
+        The default implementation fails, implementations have to override it to be used together with a `fingerprint_extractor`.
+    */
+    fn sign_fingerprinted<'a>(
+        &'a self,
+        claims: &'a Claims,
+        fingerprint_hash: &'a str,
+    ) -> SignFuture<'a> {
+        let _ = (claims, fingerprint_hash);
+        Box::pin(ready(Err(AuthError::TokenCreation(CreationError::Claims(
+            serde::ser::Error::custom(
+                "this AsyncTokenSigner does not support fingerprinted tokens",
+            ),
+        )))))
+    }
+}
+
+impl<Claims, Algo> AsyncTokenSigner<Claims> for TokenSigner<Claims, Algo>
+where
+    Claims: Serialize,
+    Algo: Algorithm + Clone,
+{
+    fn sign<'a>(&'a self, claims: &'a Claims) -> SignFuture<'a> {
+        Box::pin(ready(self.create_access_token(claims)))
+    }
+
+    fn sign_fingerprinted<'a>(
+        &'a self,
+        claims: &'a Claims,
+        fingerprint_hash: &'a str,
+    ) -> SignFuture<'a> {
+        Box::pin(ready(
+            self.fingerprinted_access_token(claims, fingerprint_hash),
+        ))
+    }
+}
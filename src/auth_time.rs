@@ -0,0 +1,33 @@
+use crate::time::SharedTimeOptions;
+use crate::validate::deserialize_claims_unchecked;
+use crate::AuthError;
+use crate::AuthResult;
+
+use std::time::Duration;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct AuthTimeClaim {
+    auth_time: Option<i64>,
+}
+
+/*
+    Checks that the subject of an already validated token authenticated at most `max_auth_age` ago,
+    based on the `auth_time` claim of the token, which holds the seconds since the unix epoch.
+*/
+pub(crate) fn verify_auth_time(
+    token_value: &str,
+    time_options: &SharedTimeOptions,
+    max_auth_age: Duration,
+) -> AuthResult<()> {
+    let auth_time = deserialize_claims_unchecked::<_, AuthTimeClaim>(token_value)?
+        .custom
+        .auth_time
+        .ok_or(AuthError::MissingClaim("auth_time"))?;
+    let auth_age = time_options.now().timestamp().saturating_sub(auth_time);
+    if u64::try_from(auth_age).unwrap_or_default() > max_auth_age.as_secs() {
+        return Err(AuthError::ReauthRequired);
+    }
+    Ok(())
+}
@@ -1,40 +1,264 @@
+use crate::async_token_signer::SharedAsyncTokenSigner;
+use crate::auth_time::verify_auth_time;
+use crate::body_tokens::buffer_form_body;
+use crate::body_tokens::form_body_field;
+use crate::claims_format::ClaimsFormat;
+use crate::client_id::verify_client_id;
+use crate::csrf::verify_csrf_token;
+#[cfg(feature = "detached-payload")]
+use crate::detached::attach_payload;
+#[cfg(feature = "detached-payload")]
+use crate::detached::verify_detached_jws;
+use crate::encryption::TokenEncryptionKey;
+use crate::extractors::insert_scoped_claims;
+use crate::extractors::RegisteredClaims;
+use crate::extractors::TokenSubject;
+use crate::fingerprint::hash_fingerprint;
+use crate::fingerprint::verify_fingerprint;
+use crate::fingerprint::FingerprintExtractor;
+use crate::guard::Authenticated;
 use crate::helper_macros::continue_if_matches_err_variant;
 use crate::helper_macros::make_token_update;
 use crate::helper_macros::pull_from_token_signer;
+use crate::profile::validate_access_token_profile;
+use crate::time::SharedTimeOptions;
+use crate::token_signer::default_cookie_builder;
+use crate::token_signer::prefixed_cookie_name;
+use crate::token_version::token_version_check;
+use crate::token_version::TokenVersionCheck;
+use crate::validate::validate_age;
 use crate::validate::validate_jwt;
+#[cfg(feature = "detached-payload")]
+use crate::validate::validate_lifetime;
+use crate::validate::verify_jwt_signature;
+use crate::validate::ValidationOptions;
+use crate::AsyncTokenSigner;
 use crate::AuthError;
 use crate::AuthResult;
+use crate::ClaimsSchema;
 use crate::TokenSigner;
+use crate::TokenVersion;
+use crate::WasExpired;
+use crate::WasRefreshed;
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
 
 use actix_web::cookie::Cookie;
+use actix_web::cookie::SameSite;
+use actix_web::dev::Extensions;
 use actix_web::dev::ServiceRequest;
-use actix_web::http::header::HeaderMap;
+use actix_web::error::InternalError;
 use actix_web::http::header::HeaderValue;
+use actix_web::http::header::ACCEPT;
 use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::header::LOCATION;
+use actix_web::http::uri::PathAndQuery;
+use actix_web::http::Method;
+use actix_web::http::StatusCode;
+use actix_web::http::Uri;
 use actix_web::Error as ActixWebError;
 use actix_web::FromRequest;
 use actix_web::Handler;
 use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use derive_builder::Builder;
 use jwt_compact::Algorithm;
-use jwt_compact::TimeOptions;
-use jwt_compact::Token;
+use jwt_compact::Claims as TokenClaims;
+use jwt_compact::ParseError;
 use jwt_compact::UntrustedToken;
 use jwt_compact::ValidationError::Expired as TokenExpired;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-/*
-    Struct used to signal to the middleware that a cookie needs to be updated
-    after the wrapped service has returned a response.
+type RequestMutator<Claims> = Arc<dyn Fn(&mut ServiceRequest, &Claims) + Send + Sync>;
+
+/**
+    The cookies of tokens renewed by the [`Authority`],
+    which the middleware sets on the response after the wrapped service has returned it.
+
+    Returned by [`Authority::verify_service_request`], the accessors allow inspecting the renewed tokens,
+    for example in tests.
 */
-#[doc(hidden)]
 #[derive(Debug)]
 pub struct TokenUpdate {
+    pub(crate) access_token: Option<String>,
     pub(crate) access_cookie: Option<Cookie<'static>>,
+    pub(crate) refresh_token: Option<String>,
     pub(crate) refresh_cookie: Option<Cookie<'static>>,
+    pub(crate) navigation_cookie: Option<Cookie<'static>>,
+    pub(crate) csrf_cookie: Option<Cookie<'static>>,
+}
+
+impl TokenUpdate {
+    /**
+        Returns the renewed access token, if it was renewed.
+
+        Unlike the value of the [`Self::access_cookie`], the token is neither encrypted nor encoded.
+    */
+    pub fn access_token(&self) -> Option<&str> {
+        self.access_token.as_deref()
+    }
+
+    /**
+        Returns the renewed refresh token, if it was renewed.
+
+        Unlike the value of the [`Self::refresh_cookie`], the token is neither encrypted nor encoded.
+    */
+    pub fn refresh_token(&self) -> Option<&str> {
+        self.refresh_token.as_deref()
+    }
+
+    /**
+        Returns the cookie of the renewed access token, if it was renewed.
+    */
+    pub fn access_cookie(&self) -> Option<&Cookie<'static>> {
+        self.access_cookie.as_ref()
+    }
+
+    /**
+        Returns the cookie of the renewed refresh token, if it was renewed.
+    */
+    pub fn refresh_cookie(&self) -> Option<&Cookie<'static>> {
+        self.refresh_cookie.as_ref()
+    }
+
+    /**
+        Returns the navigation cookie mirroring the renewed access token, if a `navigation_cookie_name` is set.
+    */
+    pub fn navigation_cookie(&self) -> Option<&Cookie<'static>> {
+        self.navigation_cookie.as_ref()
+    }
+
+    /**
+        Returns the CSRF cookie set again alongside the renewed refresh token, if a `csrf_cookie_name` is set.
+    */
+    pub fn csrf_cookie(&self) -> Option<&Cookie<'static>> {
+        self.csrf_cookie.as_ref()
+    }
+}
+
+/**
+    Controls how tokens renewed by the [`Authority`] are delivered to the client.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TokenDelivery {
+    /**
+        Renewed tokens are set as cookies on the response.
+    */
+    #[default]
+    Cookie,
+    /**
+        Renewed tokens are set as response headers named after the `access_token_header_name` and `refresh_token_header_name`
+        of the [`Authority`], which default to the `access_token_name` and `refresh_token_name`.
+        The headers carry the plain tokens, even if token cookies are encrypted or encoded.
+
+        This suits clients which do not use cookies at all, like mobile apps or single page applications
+        which read tokens via `enable_header_tokens`.
+    */
+    Header,
+    /**
+        Renewed tokens are set as cookies as well as response headers.
+    */
+    Both,
+}
+
+impl TokenDelivery {
+    pub(crate) fn includes_cookie(self) -> bool {
+        matches!(self, Self::Cookie | Self::Both)
+    }
+
+    pub(crate) fn includes_header(self) -> bool {
+        matches!(self, Self::Header | Self::Both)
+    }
+}
+
+/**
+    A place in a request the [`Authority`] looks for tokens.
+
+    The order in which the sources are checked is controlled by the `token_source_order` field of the [`Authority`],
+    every source still has to be enabled through its respective `enable_*` field.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TokenSource {
+    /**
+        The query string of the request, see `enable_query_tokens`.
+    */
+    Query,
+    /**
+        A header named after the token, see `enable_header_tokens`.
+    */
+    Header,
+    /**
+        The `Authorization` header of the request, see `enable_authorization_header`.
+    */
+    AuthorizationHeader,
+    /**
+        The cookies of the request, see `enable_cookie_tokens`.
+    */
+    Cookie,
+    /**
+        A field of a form body, see `enable_body_tokens`.
+    */
+    Body,
+}
+
+impl TokenSource {
+    /*
+        The order in which the sources were checked before it became configurable,
+        the body was added afterwards and is checked last.
+    */
+    pub(crate) fn default_order() -> Vec<Self> {
+        vec![
+            Self::Query,
+            Self::Header,
+            Self::AuthorizationHeader,
+            Self::Cookie,
+            Self::Body,
+        ]
+    }
+}
+
+/**
+    The non-secret configuration of an [`Authority`], as returned by [`Authority::public_config`].
+
+    This allows operators to verify that a deployment matches their intent, for example through a diagnostics endpoint.
+    It never contains any keys, only the names of the algorithms and the `kid`s of the `verifying_keys`.
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AuthorityConfig {
+    pub algorithm: String,
+    pub refresh_algorithm: Option<String>,
+    pub verifying_key_ids: Vec<String>,
+    pub access_token_name: String,
+    pub refresh_token_name: String,
+    pub query_access_token_name: String,
+    pub query_refresh_token_name: String,
+    pub enable_query_tokens: bool,
+    pub require_tls_for_query_tokens: bool,
+    pub enable_header_tokens: bool,
+    pub enable_authorization_header: bool,
+    pub enable_cookie_tokens: bool,
+    pub enable_body_tokens: bool,
+    pub token_source_order: Vec<TokenSource>,
+    pub renew_access_token_automatically: bool,
+    pub renew_refresh_token_automatically: bool,
+    pub allow_expired: bool,
+    pub require_expiration: bool,
+    pub expected_token_type: Option<String>,
+    pub enforce_access_token_profile: bool,
+    pub binds_fingerprint: bool,
+    pub decrypts_tokens: bool,
+    pub has_token_signer: bool,
+    pub has_async_token_signer: bool,
 }
 
 /**
@@ -63,7 +287,7 @@ pub struct TokenUpdate {
     Please refer to the [`AuthorityBuilder`] for a detailed description of options available on this struct.
 */
 #[derive(Builder, Clone)]
-#[builder(pattern = "owned")]
+#[builder(pattern = "owned", build_fn(validate = "Self::validate"))]
 pub struct Authority<Claims, Algo, ReAuth, Args>
 where
     Algo: Algorithm + Clone,
@@ -80,11 +304,42 @@ where
         will be passed along as a wrapped [`AuthError::RefreshAuthorizerDenied`] back to the client
         (There are options to remap this, for example this crate: [`actix-error-mapper-middleware`](https://github.com/michaelvanstraten/actix-error-mapper-middleware)).
 
+        The response of the returned error is sent to the client as is, including its status code and headers.
+        This allows the `refresh_authorizer` to redirect the client, for example to a login page:
+        ```rust
+        # use actix_web::error::InternalError;
+        # use actix_web::http::header::LOCATION;
+        # use actix_web::HttpResponse;
+        async fn refresh_authorizer() -> Result<(), actix_web::Error> {
+            Err(InternalError::from_response(
+                "please log in again",
+                HttpResponse::Found()
+                    .insert_header((LOCATION, "/login"))
+                    .finish(),
+            )
+            .into())
+        }
+        ```
+
         Since `refresh_authorizer` has to implement the [`Handler`](actix_web::dev::Handler) trait,
         you are able to access your regular application an request state from within
         the function. This allows you to perform Database Check etc...
+
+        The `refresh_authorizer` is called before the request reaches its handler,
+        so its arguments must not extract the request payload, like [`actix_web::web::Json`] or [`actix_web::web::Bytes`] do,
+        as the payload would then be consumed before the handler could read it.
     */
     refresh_authorizer: ReAuth,
+    /**
+        If set, a denial by the `refresh_authorizer` is answered with this status code,
+        for example `StatusCode::FORBIDDEN` to distinguish "you can't do that" from "please log in".
+        Redirects returned by the `refresh_authorizer` are passed along unchanged.
+
+        Defaults to `None`, in which case the status code of the error returned by the `refresh_authorizer` is used.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    refresh_denied_status: Option<StatusCode>,
     /**
         Depending on whether a [`TokenSigner`] is set, setting this field will have no affect.
 
@@ -96,6 +351,29 @@ where
     )]
     #[builder(setter(into))]
     pub(crate) access_token_name: String,
+    /**
+        The name of a secondary access token cookie with the `SameSite=Lax` attribute,
+        which is accepted if the access token cookie was not sent, for example on a cross-site navigation.
+
+        Please refer to [`crate::TokenSignerBuilder::navigation_cookie_name`] for more details.
+
+        Defaults to the value of the `navigation_cookie_name` field set on the `token_signer`, if the `token_signer` is not set,
+        this defaults to `None`.
+    */
+    #[builder(default = "pull_from_token_signer!(self, navigation_cookie_name, None)")]
+    #[builder(setter(into, strip_option))]
+    navigation_cookie_name: Option<String>,
+    /**
+        A prefix prepended to the names of the token cookies, for example `"t1_"` to look for the `t1_access_token` cookie.
+
+        Please refer to [`crate::TokenSignerBuilder::cookie_name_prefix`] for more details.
+
+        Defaults to the value of the `cookie_name_prefix` field set on the `token_signer`, if the `token_signer` is not set,
+        this defaults to `None`.
+    */
+    #[builder(default = "pull_from_token_signer!(self, cookie_name_prefix, None)")]
+    #[builder(setter(into, strip_option))]
+    cookie_name_prefix: Option<String>,
     /**
         If set to false the clients access token will not be automatically refreshed.
 
@@ -103,6 +381,101 @@ where
     */
     #[builder(default = "true")]
     renew_access_token_automatically: bool,
+    /**
+        If set to true, expired access tokens with a valid signature are accepted by the middleware instead of being renewed or rejected,
+        the claims are inserted as usual and [`WasExpired(true)`](crate::WasExpired) is inserted into the request extensions.
+        [`Authority::verify_http_request`] still rejects expired access tokens, since it has no way of marking them.
+
+        This allows handlers to render a "your session expired" page themselves instead of the client receiving a bare `401`.
+        Tokens older than the `max_access_token_age` are still rejected.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    allow_expired: bool,
+    /**
+        Tokens without an `exp` claim are rejected with [`AuthError::TokenValidation`] wrapping
+        [`jwt_compact::ValidationError::NoClaim`] by default, since jwt-compact requires every token to expire.
+
+        Setting this to false deliberately weakens the validation, tokens without an `exp` claim are then accepted without ever expiring.
+        Only do so if tokens are issued by a party which relies on revoking them by other means.
+
+        Defaults to `true`
+    */
+    #[builder(default = "true")]
+    require_expiration: bool,
+    /**
+        Controls whether renewed tokens are delivered as cookies, response headers or both.
+
+        Please refer to [`TokenDelivery`] for more details.
+
+        Defaults to `TokenDelivery::Cookie`
+    */
+    #[builder(default = "TokenDelivery::Cookie")]
+    pub(crate) token_delivery: TokenDelivery,
+    /**
+        Name of the response header renewed access tokens are delivered in, if the `token_delivery` includes headers.
+
+        Defaults to `None`, in which case the `access_token_name` is used.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    access_token_header_name: Option<String>,
+    /**
+        Name of the response header renewed refresh tokens are delivered in, if the `token_delivery` includes headers.
+
+        Defaults to `None`, in which case the `refresh_token_name` is used.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    refresh_token_header_name: Option<String>,
+    /**
+        If set to true, responses delivering renewed tokens carry the `Cache-Control: no-store` and `Pragma: no-cache` headers,
+        as recommended by the OAuth 2.0 Security Best Current Practice.
+
+        This prevents intermediary caches from storing responses carrying tokens.
+
+        Defaults to `true`
+    */
+    #[builder(default = "true")]
+    pub(crate) no_store_token_responses: bool,
+    /**
+        Requests using one of these methods are passed to the wrapped service without being authenticated.
+
+        CORS preflight requests are sent by browsers without any cookies or tokens,
+        rejecting them would break every cross origin request to the wrapped scope.
+        Please note that no claims are available to handlers of these requests.
+
+        Defaults to `[Method::OPTIONS]`
+    */
+    #[builder(default = "vec![Method::OPTIONS]")]
+    skip_auth_for_methods: Vec<Method>,
+    /**
+        Requests to one of these paths are passed to the wrapped service without being authenticated,
+        for example health checks or a `/login` endpoint inside of an otherwise protected scope.
+
+        Patterns are matched against the full path of the request, including the prefix of enclosing scopes.
+        A pattern ending in `*` matches every path starting with the part before the `*`,
+        so `"/health*"` matches `"/health"`, `"/health/live"` and `"/healthz"`.
+        Every other pattern has to match the path exactly.
+
+        Defaults to `[]`
+    */
+    #[builder(default = "Vec::new()")]
+    skip_auth_for_paths: Vec<String>,
+    /**
+        URL browser requests are redirected to with a `302 Found` if they are not authenticated, instead of being rejected with a `401 Unauthorized`.
+
+        Only requests accepting `text/html` are redirected, and only if they carry no token or their token expired without being renewed,
+        API requests are still rejected with a `401 Unauthorized`.
+        The path and query of the original request are passed along as the `next` query parameter,
+        so `"/login"` redirects a request to `/account?tab=1` to `/login?next=%2Faccount%3Ftab%3D1`.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    unauthenticated_redirect: Option<String>,
     /**
         Depending on whether a [`TokenSigner`] is set, setting this field will have no affect.
 
@@ -122,6 +495,102 @@ where
     */
     #[builder(default = "false")]
     renew_refresh_token_automatically: bool,
+    /**
+        Leeway used instead of the one set on the `time_options` when validating the refresh token.
+
+        A slightly larger leeway allows a client whose refresh token has just barely expired to still get a new access token.
+        The validation of the access token is unaffected by this.
+        A leeway exceeding the range of [`chrono::TimeDelta`] is saturated.
+
+        Defaults to `None`, in which case the leeway of the `time_options` is used.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    refresh_leeway: Option<Duration>,
+    /**
+        Fraction of the refresh token lifetime, between `0.0` and `1.0`, below which the remaining lifetime of a still valid refresh token
+        has to drop before it is renewed alongside the access token.
+
+        A threshold of `0.2` only rotates the refresh token once less than 20% of its lifetime is left,
+        instead of re-minting it on every request. This has no effect unless `renew_refresh_token_automatically` is set to true.
+
+        Defaults to `None`, in which case refresh tokens are only renewed after they have expired.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    refresh_renewal_threshold: Option<f32>,
+    /**
+        Remaining lifetime below which a still valid access token is renewed through the refresh token,
+        so clients rarely ever present an expired access token.
+
+        If the renewal fails, for example because no refresh token was sent, the still valid access token is accepted as usual.
+        This calls the `refresh_authorizer` for requests which carry a valid access token, so it must not extract the request payload.
+        This has no effect unless `renew_access_token_automatically` is set to true.
+
+        Defaults to `None`, in which case access tokens are only renewed after they have expired.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    access_renewal_threshold: Option<Duration>,
+    /**
+        Maximum age of an access token, measured from its `iat` claim, independent of its expiration.
+
+        Older access tokens are rejected with [`AuthError::SessionExpired`], just like tokens without an `iat` claim.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    max_access_token_age: Option<Duration>,
+    /**
+        Maximum age of a refresh token, measured from its `iat` claim, independent of its expiration.
+
+        Access tokens are only renewed from refresh tokens younger than this, which caps how long a session can be kept alive through refreshes.
+        Refresh tokens renewed through `renew_refresh_token_automatically` keep the `iat` of the refresh token they renew,
+        so the age is measured from the login and the cap holds for expired refresh tokens as well.
+        Older refresh tokens are rejected with [`AuthError::SessionExpired`].
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    max_refresh_token_age: Option<Duration>,
+    /**
+        Maximum time since the subject of a token authenticated, measured from the `auth_time` claim of the token
+        as defined by OpenID Connect, in seconds since the unix epoch.
+
+        Unlike the `max_refresh_token_age`, this forces the subject to authenticate again after a period,
+        no matter how often its tokens were renewed. For this to work the `auth_time` has to be part of the `Claims`,
+        set once when the subject logs in, so renewed tokens keep the original time.
+        Tokens whose subject authenticated longer ago are rejected with [`AuthError::ReauthRequired`],
+        tokens without an `auth_time` claim with [`AuthError::MissingClaim`].
+        This applies to refresh tokens as well, including expired ones renewed through `renew_refresh_token_automatically`.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    max_auth_age: Option<Duration>,
+    /**
+        Overrides the name of the query parameter the access token is read from,
+        if `enable_query_tokens` is set to true.
+
+        This is useful for clients like `EventSource`, which can only pass tokens via a specific query parameter.
+
+        Defaults to `None`, in which case the `access_token_name` is used.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    query_access_token_name: Option<String>,
+    /**
+        Overrides the name of the query parameter the refresh token is read from,
+        if `enable_query_tokens` is set to true.
+
+        Defaults to `None`, in which case the `refresh_token_name` is used.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    query_refresh_token_name: Option<String>,
     /**
         If set to true, the service will look for `access_token_name` and `refresh_token_name` in
         http headers.
@@ -140,16 +609,73 @@ where
     */
     #[builder(default = "false")]
     enable_query_tokens: bool,
+    /**
+        If set to true, the access and refresh token query parameters are removed from the query string
+        of the processed request, after the tokens have been extracted.
+
+        This prevents downstream services and logging middleware from recording the tokens.
+        Please note that middleware wrapping this one will still see the original query string.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    redact_query_tokens: bool,
+    /**
+        If set to true, tokens passed as query parameters are rejected with [`AuthError::InsecureTransport`]
+        unless the request was made over `https`, as reported by [`ConnectionInfo::scheme`](actix_web::dev::ConnectionInfo::scheme).
+        Behind a reverse proxy, the scheme is taken from the `Forwarded` or `X-Forwarded-Proto` header.
+
+        A token in the URL of a plain `http` request is exposed to every intermediary,
+        so this keeps a development setup without TLS from leaking tokens once it is deployed.
+        This has no effect unless `enable_query_tokens` is set to true.
+
+        Defaults to `true`
+    */
+    #[builder(default = "true")]
+    require_tls_for_query_tokens: bool,
     /**
         If set to true, the service will look for `access_token_name` and `refresh_token_name` in
         in the cookies of the processed request.
     */
     #[builder(default = "true")]
     enable_cookie_tokens: bool,
+    /**
+        If set to true, the service will look for `access_token_name` and `refresh_token_name` in
+        the fields of `application/x-www-form-urlencoded` request bodies.
+
+        This is meant for form posts continuing a login flow. Since the token has to be known before the handler runs,
+        the whole body of every form request is buffered in memory, limited by the [`PayloadConfig`](actix_web::web::PayloadConfig)
+        of the application, and handed to the handler afterwards. Bodies of other content types are still streamed.
+        Failing to read the body is reported as [`AuthError::BodyRead`].
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    enable_body_tokens: bool,
+    /**
+        The order in which the enabled token sources are checked, the first source carrying a token is used.
+
+        Sources missing from this list are never checked, even if they are enabled.
+
+        Defaults to `[Query, Header, AuthorizationHeader, Cookie, Body]`
+    */
+    #[builder(default = "TokenSource::default_order()")]
+    token_source_order: Vec<TokenSource>,
     /**
         Key used to verify integrity of access and refresh token.
     */
     verifying_key: Algo::VerifyingKey,
+    /**
+        Keys used to verify tokens carrying a `kid` header, keyed by their `kid`.
+
+        Tokens whose `kid` is not part of this map are rejected with [`AuthError::UnknownKeyId`],
+        tokens without a `kid` header are still verified with the `verifying_key` or `refresh_verifying_key`.
+
+        Defaults to `None`, in which case the `kid` header is ignored.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    verifying_keys: Option<HashMap<String, Algo::VerifyingKey>>,
     /**
         The Cryptographic signing algorithm used in the process of creation of access and refresh tokens.
 
@@ -160,103 +686,895 @@ where
     */
     #[builder(default = "pull_from_token_signer!(self, algorithm)")]
     algorithm: Algo,
+    /**
+        Key used to verify the integrity of refresh tokens,
+        if they are signed with the `refresh_signing_key` of the `token_signer`.
+
+        Defaults to `None`, in which case refresh tokens are verified with the `verifying_key`.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    refresh_verifying_key: Option<Algo::VerifyingKey>,
+    /**
+        The Cryptographic signing algorithm used to verify refresh tokens.
+
+        Defaults to the value of the `refresh_algorithm` field set on the `token_signer`,
+        if neither is set, refresh tokens are verified with the `algorithm`.
+    */
+    #[builder(default = "pull_from_token_signer!(self, refresh_algorithm, None)")]
+    #[builder(setter(strip_option))]
+    refresh_algorithm: Option<Algo>,
     /**
         Used in the creating of the `token`, the current time stamp is taken from this, but please referee to the Structs documentation.
+        The [`crate::time`] module provides helpers for constructing them.
 
         Defaults to the value of the `time_options` field set on the `token_signer`, if the `token_signer` is not set,
         this field needs to be set.
     */
     #[builder(default = "pull_from_token_signer!(self, time_options)")]
-    time_options: TimeOptions,
+    #[builder(setter(into))]
+    time_options: SharedTimeOptions,
     /**
-        Not Passing a [`TokenSigner`] struct will make your middleware unable to refresh the access token automatically.
+        The format the claims of accepted tokens have to be serialized in.
 
-        You will have to provide a algorithm manually in this case because the Authority can not pull it from the `token_signer` field.
+        Defaults to the value of the `claims_format` field set on the `token_signer`, if the `token_signer` is not set,
+        this defaults to `ClaimsFormat::Json`.
+    */
+    #[builder(default = "pull_from_token_signer!(self, claims_format, ClaimsFormat::Json)")]
+    claims_format: ClaimsFormat,
+    /**
+        If set, only tokens whose `typ` header matches this value are accepted,
+        every other token is rejected with [`AuthError::TokenTypeMismatch`].
 
-        Please referee to the structs own documentation for more details.
+        Defaults to `None`, in which case the `typ` header is not checked.
     */
     #[builder(default = "None")]
-    token_signer: Option<TokenSigner<Claims, Algo>>,
-    #[doc(hidden)]
-    #[builder(setter(skip), default = "PhantomData")]
-    claims_marker: PhantomData<Claims>,
-    #[doc(hidden)]
-    #[builder(setter(skip), default = "PhantomData")]
-    args_marker: PhantomData<Args>,
-}
+    #[builder(setter(into, strip_option))]
+    expected_token_type: Option<String>,
+    /**
+        If set to true, access tokens have to follow [RFC 9068](https://www.rfc-editor.org/rfc/rfc9068),
+        they have to carry the `typ` header `"at+jwt"` as well as the `iss`, `exp`, `aud`, `sub`, `client_id`, `iat` and `jti` claims.
 
-impl<Claims, Algo, ReAuth, Args> Authority<Claims, Algo, ReAuth, Args>
-where
-    Claims: Serialize + DeserializeOwned + 'static,
-    Algo: Algorithm + Clone,
-    Algo::SigningKey: Clone,
-    ReAuth: Handler<Args, Output = Result<(), ActixWebError>>,
-    Args: FromRequest,
-{
+        Please refer to [`crate::AccessTokenProfile`] for issuing such tokens.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    enforce_access_token_profile: bool,
     /**
-        Returns a new [`AuthorityBuilder`].
+        Function used to compute a fingerprint of the device a request originates from,
+        tokens not carrying the hash of this fingerprint are rejected with [`AuthError::FingerprintMismatch`].
+
+        Please refer to [`crate::TokenSignerBuilder::fingerprint_extractor`] for more details.
+
+        Defaults to the value of the `fingerprint_extractor` field set on the `token_signer`, if the `token_signer` is not set,
+        this defaults to `None`.
     */
-    #[allow(clippy::new_ret_no_self)]
-    pub fn new() -> AuthorityBuilder<Claims, Algo, ReAuth, Args> {
-        AuthorityBuilder::default()
-    }
+    #[builder(
+        setter(custom),
+        default = "pull_from_token_signer!(self, fingerprint_extractor, None)"
+    )]
+    fingerprint_extractor: Option<FingerprintExtractor>,
+    /**
+        The name of the header, for example `"X-CSRF-Token"`, which has to echo the `csrf` claim of tokens sent as cookies.
+
+        This implements the double-submit pattern together with [`crate::TokenSignerBuilder::csrf_cookie_name`].
+        Requests using one of the `csrf_checked_methods` are rejected with [`AuthError::CsrfMismatch`]
+        if they were authenticated through a cookie and the header does not match the claim.
+        Tokens sent through any other source are not affected, since browsers never attach them automatically.
 
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    csrf_header_name: Option<String>,
     /**
-        Returns a Clone of the `token_signer` field on the Authority.
+        Requests using one of these methods have to pass the CSRF check if a `csrf_header_name` is set.
+
+        Only methods changing state should be listed here, so links and plain `GET` APIs keep working
+        without the client having to send the CSRF header.
+
+        Defaults to `[Method::POST, Method::PUT, Method::PATCH, Method::DELETE]`
     */
-    pub fn token_signer(&self) -> Option<TokenSigner<Claims, Algo>>
-    where
-        TokenSigner<Claims, Algo>: Clone,
-    {
-        self.token_signer.clone()
-    }
+    #[builder(default = "vec![Method::POST, Method::PUT, Method::PATCH, Method::DELETE]")]
+    csrf_checked_methods: Vec<Method>,
+    /**
+        Key used to decrypt token cookies, which were encrypted by the [`TokenSigner`].
+
+        Decryption only takes effect with the `jwe` feature enabled.
+
+        Defaults to the value of the `encryption_key` field set on the `token_signer`, if the `token_signer` is not set,
+        this defaults to `None`.
+    */
+    #[builder(default = "pull_from_token_signer!(self, encryption_key, None)")]
+    #[builder(setter(strip_option))]
+    encryption_key: Option<TokenEncryptionKey>,
+    /**
+        If set to true, errors are responded to with [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) `application/problem+json` bodies,
+        please refer to `AuthError::to_problem_details` for more details.
+
+        Responses of errors returned by the `refresh_authorizer` or the `claims_enricher` are kept as is.
+        This only takes effect with the `problem-details` feature enabled.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    pub(crate) problem_details_responses: bool,
+    /**
+        If set, only tokens whose `azp` claim, or `client_id` claim if no `azp` claim is present,
+        is part of this set are accepted, every other token is rejected with [`AuthError::ClientNotAllowed`].
+
+        This allows a resource server to serve multiple OAuth clients, while only trusting a subset of them.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    allowed_client_ids: Option<HashSet<String>>,
+    /**
+        A JSON Schema the claims of every token have to match, tokens violating it are rejected with [`AuthError::ClaimsSchemaViolation`].
+
+        Please refer to [`crate::ClaimsSchema`] for more details, this setter is only available with the `jsonschema` feature.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[cfg_attr(feature = "jsonschema", builder(setter(strip_option)))]
+    #[cfg_attr(not(feature = "jsonschema"), builder(setter(custom)))]
+    claims_schema: Option<ClaimsSchema>,
+    /**
+        If set to true, token cookies are base64url decoded before they are validated.
+
+        Defaults to the value of the `encode_cookie_value` field set on the `token_signer`, if the `token_signer` is not set,
+        this defaults to `false`.
+    */
+    #[builder(default = "pull_from_token_signer!(self, encode_cookie_value, false)")]
+    decode_cookie_value: bool,
+    /**
+        Not Passing a [`TokenSigner`] struct will make your middleware unable to refresh the access token automatically.
+
+        You will have to provide a algorithm manually in this case because the Authority can not pull it from the `token_signer` field.
+
+        Please referee to the structs own documentation for more details.
+    */
+    #[builder(default = "None")]
+    token_signer: Option<TokenSigner<Claims, Algo>>,
+    /**
+        Signer used to sign renewed access tokens asynchronously, instead of the `token_signer`.
+
+        Please refer to [`AuthorityBuilder::async_token_signer`] for more details.
+
+        Defaults to `None`
+    */
+    #[builder(setter(custom), default = "None")]
+    async_token_signer: Option<SharedAsyncTokenSigner<Claims>>,
+    /**
+        If set to true, the validated claims are inserted into the request extensions as `Arc<Claims>` instead of `Claims`.
+
+        Handlers only reading the claims can then use the [`SharedClaims`](crate::SharedClaims) extractor,
+        which does not clone the claims themselves.
+        Types deriving [`crate::FromRequest`] and the [`AuthenticatedClaims`](crate::AuthenticatedClaims) extractor keep working,
+        but clone the claims out of the `Arc`. No [`ScopedClaims`](crate::ScopedClaims) are inserted in this case.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    share_claims: bool,
+    /**
+        Function used to insert the validated claims into the request extensions.
+
+        Please refer to [`AuthorityBuilder::claims_marker`] for more details.
+
+        Defaults to `None`, which inserts the plain `Claims`.
+    */
+    #[builder(setter(custom), default = "None")]
+    claims_inserter: Option<fn(&mut Extensions, Claims)>,
+    /**
+        If set to true, the `sub` claim of the validated claims is inserted into the request extensions,
+        so handlers can read it with the [`Subject`](crate::Subject) extractor without knowing the `Claims` type.
+
+        The `sub` claim is looked up by serializing the claims, which is only done if this is enabled.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    insert_subject: bool,
+    /**
+        Check rejecting tokens whose version is below the minimal token version of their subject.
+
+        Please refer to [`AuthorityBuilder::min_token_version`] for more details.
+
+        Defaults to `None`
+    */
+    #[builder(setter(custom), default = "None")]
+    token_version_check: Option<TokenVersionCheck<Claims>>,
+    /**
+        Function called with the request and its validated claims once the request is authenticated.
+
+        Please refer to [`AuthorityBuilder::request_mutator`] for more details.
+
+        Defaults to `None`
+    */
+    #[builder(setter(custom), default = "None")]
+    request_mutator: Option<RequestMutator<Claims>>,
+    #[doc(hidden)]
+    #[builder(setter(skip), default = "PhantomData")]
+    claims_marker: PhantomData<Claims>,
+    #[doc(hidden)]
+    #[builder(setter(skip), default = "PhantomData")]
+    args_marker: PhantomData<Args>,
+}
+
+impl<Claims, Algo, ReAuth, Args> Authority<Claims, Algo, ReAuth, Args>
+where
+    Claims: Serialize + DeserializeOwned + 'static,
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+    ReAuth: Handler<Args, Output = Result<(), ActixWebError>>,
+    Args: FromRequest,
+{
+    /**
+        Returns a new [`AuthorityBuilder`].
+    */
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> AuthorityBuilder<Claims, Algo, ReAuth, Args> {
+        AuthorityBuilder::default()
+    }
+
+    /**
+        Returns a new [`Authority`] for the claims type `Other`, sharing the keys and configuration of this one.
+
+        This avoids duplicating the configuration of authorities protecting different areas of an application,
+        for example an admin area using `AdminClaims` and a user area using `UserClaims` signed with the same key.
+        Settings tied to the claims type are not carried over: the `token_signer` drops its `claims_enricher`,
+        and the `async_token_signer`, `claims_marker`, `min_token_version` and `request_mutator` are unset.
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::Authority;
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        # use jwt_compact::TimeOptions;
+        # use serde::{Deserialize, Serialize};
+        #[derive(Serialize, Deserialize, Clone)]
+        struct UserClaims {
+            id: u32,
+        }
+
+        #[derive(Serialize, Deserialize, Clone)]
+        struct AdminClaims {
+            id: u32,
+            permissions: Vec<String>,
+        }
+
+        let user_authority = Authority::<UserClaims, _, _, _>::new()
+            .refresh_authorizer(|| async move { Ok(()) })
+            .algorithm(Ed25519)
+            .verifying_key(KeyPair::generate().pk)
+            .time_options(TimeOptions::default())
+            .build()
+            .unwrap();
+        let admin_authority = user_authority.with_claims::<AdminClaims>();
+        ```
+    */
+    pub fn with_claims<Other>(&self) -> Authority<Other, Algo, ReAuth, Args>
+    where
+        ReAuth: Clone,
+        Algo::VerifyingKey: Clone,
+    {
+        Authority {
+            refresh_authorizer: Clone::clone(&self.refresh_authorizer),
+            refresh_denied_status: Clone::clone(&self.refresh_denied_status),
+            access_token_name: Clone::clone(&self.access_token_name),
+            navigation_cookie_name: Clone::clone(&self.navigation_cookie_name),
+            cookie_name_prefix: Clone::clone(&self.cookie_name_prefix),
+            renew_access_token_automatically: Clone::clone(&self.renew_access_token_automatically),
+            allow_expired: Clone::clone(&self.allow_expired),
+            require_expiration: Clone::clone(&self.require_expiration),
+            token_delivery: Clone::clone(&self.token_delivery),
+            access_token_header_name: Clone::clone(&self.access_token_header_name),
+            refresh_token_header_name: Clone::clone(&self.refresh_token_header_name),
+            no_store_token_responses: Clone::clone(&self.no_store_token_responses),
+            skip_auth_for_methods: Clone::clone(&self.skip_auth_for_methods),
+            skip_auth_for_paths: Clone::clone(&self.skip_auth_for_paths),
+            unauthenticated_redirect: Clone::clone(&self.unauthenticated_redirect),
+            refresh_token_name: Clone::clone(&self.refresh_token_name),
+            renew_refresh_token_automatically: Clone::clone(
+                &self.renew_refresh_token_automatically,
+            ),
+            refresh_leeway: Clone::clone(&self.refresh_leeway),
+            refresh_renewal_threshold: Clone::clone(&self.refresh_renewal_threshold),
+            access_renewal_threshold: Clone::clone(&self.access_renewal_threshold),
+            max_access_token_age: Clone::clone(&self.max_access_token_age),
+            max_refresh_token_age: Clone::clone(&self.max_refresh_token_age),
+            max_auth_age: Clone::clone(&self.max_auth_age),
+            query_access_token_name: Clone::clone(&self.query_access_token_name),
+            query_refresh_token_name: Clone::clone(&self.query_refresh_token_name),
+            enable_header_tokens: Clone::clone(&self.enable_header_tokens),
+            enable_authorization_header: Clone::clone(&self.enable_authorization_header),
+            enable_query_tokens: Clone::clone(&self.enable_query_tokens),
+            redact_query_tokens: Clone::clone(&self.redact_query_tokens),
+            require_tls_for_query_tokens: Clone::clone(&self.require_tls_for_query_tokens),
+            enable_cookie_tokens: Clone::clone(&self.enable_cookie_tokens),
+            enable_body_tokens: Clone::clone(&self.enable_body_tokens),
+            token_source_order: Clone::clone(&self.token_source_order),
+            verifying_key: Clone::clone(&self.verifying_key),
+            verifying_keys: Clone::clone(&self.verifying_keys),
+            algorithm: Clone::clone(&self.algorithm),
+            refresh_verifying_key: Clone::clone(&self.refresh_verifying_key),
+            refresh_algorithm: Clone::clone(&self.refresh_algorithm),
+            time_options: Clone::clone(&self.time_options),
+            claims_format: Clone::clone(&self.claims_format),
+            expected_token_type: Clone::clone(&self.expected_token_type),
+            enforce_access_token_profile: Clone::clone(&self.enforce_access_token_profile),
+            fingerprint_extractor: Clone::clone(&self.fingerprint_extractor),
+            csrf_header_name: Clone::clone(&self.csrf_header_name),
+            csrf_checked_methods: Clone::clone(&self.csrf_checked_methods),
+            encryption_key: Clone::clone(&self.encryption_key),
+            problem_details_responses: Clone::clone(&self.problem_details_responses),
+            allowed_client_ids: Clone::clone(&self.allowed_client_ids),
+            claims_schema: Clone::clone(&self.claims_schema),
+            decode_cookie_value: Clone::clone(&self.decode_cookie_value),
+            token_signer: self.token_signer.as_ref().map(TokenSigner::with_claims),
+            async_token_signer: None,
+            share_claims: Clone::clone(&self.share_claims),
+            claims_inserter: None,
+            insert_subject: Clone::clone(&self.insert_subject),
+            token_version_check: None,
+            request_mutator: None,
+            claims_marker: PhantomData,
+            args_marker: PhantomData,
+        }
+    }
+
+    /**
+        Returns a Clone of the `token_signer` field on the Authority.
+    */
+    pub fn token_signer(&self) -> Option<TokenSigner<Claims, Algo>>
+    where
+        TokenSigner<Claims, Algo>: Clone,
+    {
+        self.token_signer.clone()
+    }
+
+    /**
+        Returns the configuration of this [`Authority`] without any keys or other secrets.
+    */
+    pub fn public_config(&self) -> AuthorityConfig {
+        let mut verifying_key_ids: Vec<String> = self
+            .verifying_keys
+            .iter()
+            .flat_map(HashMap::keys)
+            .cloned()
+            .collect();
+        verifying_key_ids.sort();
+
+        AuthorityConfig {
+            algorithm: self.algorithm.name().into_owned(),
+            refresh_algorithm: self
+                .refresh_algorithm
+                .as_ref()
+                .map(|refresh_algorithm| refresh_algorithm.name().into_owned()),
+            verifying_key_ids,
+            access_token_name: self.access_token_name.clone(),
+            refresh_token_name: self.refresh_token_name.clone(),
+            query_access_token_name: self
+                .query_access_token_name
+                .as_ref()
+                .unwrap_or(&self.access_token_name)
+                .clone(),
+            query_refresh_token_name: self
+                .query_refresh_token_name
+                .as_ref()
+                .unwrap_or(&self.refresh_token_name)
+                .clone(),
+            enable_query_tokens: self.enable_query_tokens,
+            require_tls_for_query_tokens: self.require_tls_for_query_tokens,
+            enable_header_tokens: self.enable_header_tokens,
+            enable_authorization_header: self.enable_authorization_header,
+            enable_cookie_tokens: self.enable_cookie_tokens,
+            enable_body_tokens: self.enable_body_tokens,
+            token_source_order: self.token_source_order.clone(),
+            renew_access_token_automatically: self.renew_access_token_automatically,
+            renew_refresh_token_automatically: self.renew_refresh_token_automatically,
+            allow_expired: self.allow_expired,
+            require_expiration: self.require_expiration,
+            expected_token_type: self.expected_token_type.clone(),
+            enforce_access_token_profile: self.enforce_access_token_profile,
+            binds_fingerprint: self.fingerprint_extractor.is_some(),
+            decrypts_tokens: self.encryption_key.is_some(),
+            has_token_signer: self.token_signer.is_some(),
+            has_async_token_signer: self.async_token_signer.is_some(),
+        }
+    }
+
+    /**
+        Validates the signature of the `token` and returns its claims, without checking whether it expired.
+
+        This is useful for tooling which needs to display the claims of an expired session,
+        like the time it expired at. Tokens with an invalid signature are still rejected.
+        Unlike the middleware, this neither decrypts the token nor checks the access token profile.
+    */
+    pub fn verify_token_ignoring_expiry(&self, token: &str) -> AuthResult<TokenClaims<Claims>> {
+        let (algorithm, verifying_key) = self.verification_pair(token, TokenKind::Access)?;
+        verify_jwt_signature(
+            &token,
+            algorithm,
+            verifying_key,
+            self.claims_format,
+            self.expected_token_type.as_deref(),
+        )
+    }
+
+    /**
+        Verifies a token with a detached payload and returns its claims.
+
+        The `detached_token` is a compact JWS without its payload, `header..signature`, usually passed in a header,
+        while the `payload` holding the claims as JSON is transmitted out-of-band, for example as the request body.
+        Headers setting `"b64": false` sign the raw `payload` following [RFC 7797](https://www.rfc-editor.org/rfc/rfc7797),
+        otherwise the base64url encoded `payload` is signed.
+
+        The token is verified like an access token, including its expiration, the `max_access_token_age`, the `expected_token_type`,
+        the `allowed_client_ids`, the `max_auth_age`, the `claims_schema` and the `min_token_version`.
+        It is neither decrypted nor checked against the access token profile or the device fingerprint, since there is no request to take it from.
+        Requires the `detached-payload` feature.
+    */
+    #[cfg(feature = "detached-payload")]
+    pub async fn verify_detached_token(
+        &self,
+        detached_token: &str,
+        payload: &[u8],
+    ) -> AuthResult<TokenClaims<Claims>> {
+        let (algorithm, verifying_key) =
+            self.verification_pair(detached_token, TokenKind::Access)?;
+        let claims = verify_detached_jws(
+            detached_token,
+            payload,
+            algorithm,
+            verifying_key,
+            self.expected_token_type.as_deref(),
+        )?;
+        validate_lifetime(
+            &claims,
+            &self.time_options,
+            self.max_access_token_age,
+            self.require_expiration,
+        )?;
+        self.verify_token_claims(&attach_payload(detached_token, payload))?;
+        self.check_token_version(&claims.custom).await?;
+        Ok(claims)
+    }
+
+    /**
+        Inserts the `claims` into the extensions of the `req` using the configured `claims_inserter`,
+        alongside the `registered_claims` of the access token and, if `insert_subject` is set, its `sub` claim.
+        The `request_mutator` is called beforehand, once the `registered_claims` are inserted.
+    */
+    fn insert_claims(
+        &self,
+        req: &mut ServiceRequest,
+        registered_claims: RegisteredClaims,
+        claims: Claims,
+    ) {
+        if self.insert_subject {
+            req.extensions_mut()
+                .insert(TokenSubject(registered_claims.subject.clone()));
+        }
+        req.extensions_mut().insert(registered_claims);
+        if let Some(ref request_mutator) = self.request_mutator {
+            request_mutator(req, &claims);
+        }
+        match self.claims_inserter {
+            _ if self.share_claims => {
+                req.extensions_mut().insert(Arc::new(claims));
+            }
+            Some(claims_inserter) => claims_inserter(&mut req.extensions_mut(), claims),
+            None => {
+                req.extensions_mut().insert(claims);
+            }
+        }
+    }
 
     /**
         Use by the actual middleware, which is hidden from the docs,
         in order to verify an incoming request and ether hand it of to protected services
         or deny the request by return a wrapped [`AuthError`].
     */
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn verify_service_request(
         &self,
         req: &mut ServiceRequest,
     ) -> AuthResult<Option<TokenUpdate>> {
+        let result = self
+            .authorize_service_request(req)
+            .await
+            .map(|token_update| {
+                token_update.map(|token_update| self.add_navigation_cookie(token_update))
+            });
+        if let Ok(ref token_update) = result {
+            req.extensions_mut().insert(Authenticated);
+            if token_update.is_some() {
+                req.extensions_mut().insert(WasRefreshed(true));
+            }
+        }
+        if self.enable_query_tokens && self.redact_query_tokens {
+            self.strip_query_tokens(req);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            outcome = outcome_name(&result),
+            "authenticated service request"
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!(counter_name(&result)).increment(1);
+        result
+    }
+
+    /**
+        Verifies the access token of a plain [`HttpRequest`] and returns its claims.
+
+        This authenticates requests which do not pass through the middleware, like the handshake of a WebSocket connection
+        or messages handled inside of a WebSocket actor. The token is looked up in the same sources as by the middleware,
+        but since no response is involved, expired access tokens are never renewed through the refresh token.
+    */
+    pub fn verify_http_request(&self, req: &HttpRequest) -> AuthResult<Claims> {
+        self.validate_token(req, TokenKind::Access, ExpiredTokens::Reject)
+            .map(|access_token| access_token.custom)
+    }
+
+    async fn authorize_service_request(
+        &self,
+        req: &mut ServiceRequest,
+    ) -> AuthResult<Option<TokenUpdate>> {
+        if self.enable_body_tokens {
+            buffer_form_body(req).await?;
+        }
         match self.validate_access_token(req) {
             Ok(access_token) => {
-                let (_, claims) = access_token.into_parts();
-                req.extensions_mut().insert(claims.custom);
+                self.check_token_version(&access_token.custom).await?;
+                if self.access_token_needs_renewal(&access_token) {
+                    match self.renew_tokens(req).await {
+                        Ok(token_update) => return Ok(token_update),
+                        Err(_err) => {
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(
+                                error = %_err,
+                                "proactive renewal of the access token failed, accepting the still valid access token"
+                            );
+                        }
+                    }
+                }
+                self.insert_claims(
+                    req,
+                    RegisteredClaims::from_validated_claims(&access_token),
+                    access_token.custom,
+                );
                 Ok(None)
             }
-            Err(AuthError::TokenValidation(TokenExpired) | AuthError::NoToken)
+            Err(AuthError::TokenValidation(TokenExpired, _)) if self.allow_expired => {
+                let access_token =
+                    self.validate_token(req.request(), TokenKind::Access, ExpiredTokens::Accept)?;
+                self.check_token_version(&access_token.custom).await?;
+                req.extensions_mut().insert(WasExpired(true));
+                self.insert_claims(
+                    req,
+                    RegisteredClaims::from_validated_claims(&access_token),
+                    access_token.custom,
+                );
+                Ok(None)
+            }
+            Err(AuthError::TokenValidation(TokenExpired, _) | AuthError::NoToken(_))
                 if self.renew_access_token_automatically =>
             {
-                self.call_refresh_authorizer(req).await?;
-                match (self.validate_refresh_token(req), &self.token_signer) {
-                    (Ok(refresh_token), Some(token_signer)) => {
-                        let (_, claims) = refresh_token.into_parts();
-                        let access_cookie = token_signer.create_access_cookie(&claims.custom)?;
-                        req.extensions_mut().insert(claims.custom);
-                        make_token_update!(access_cookie)
-                    }
-                    (Err(AuthError::TokenValidation(TokenExpired)), Some(token_signer))
-                        if self.renew_refresh_token_automatically =>
-                    {
-                        let claims = extract_claims_unsafe(
-                            req.cookie(&self.refresh_token_name)
-                                .expect("Cookie has to be set in oder to get to this point")
-                                .value(),
-                        );
-                        let access_cookie = token_signer.create_access_cookie(&claims)?;
-                        let refresh_cookie = token_signer.create_refresh_cookie(&claims)?;
-                        req.extensions_mut().insert(claims);
-                        make_token_update!(access_cookie, refresh_cookie)
-                    }
-                    (Ok(_), None) => Err(AuthError::NoTokenSigner),
-                    (Err(err), _) => Err(err),
-                }
+                self.renew_tokens(req).await
             }
             Err(err) => Err(err),
         }
     }
+
+    /*
+        Adds a navigation cookie mirroring the renewed access cookie, if a `navigation_cookie_name` is set.
+    */
+    fn add_navigation_cookie(&self, mut token_update: TokenUpdate) -> TokenUpdate {
+        if let (Some(navigation_cookie_name), Some(access_cookie)) =
+            (&self.navigation_cookie_name, &token_update.access_cookie)
+        {
+            let mut navigation_cookie = access_cookie.clone();
+            navigation_cookie.set_name(self.cookie_name(navigation_cookie_name));
+            navigation_cookie.set_same_site(SameSite::Lax);
+            token_update.navigation_cookie = Some(navigation_cookie);
+        }
+        token_update
+    }
+
+    /*
+        Renews the access token, and the refresh token if necessary, through the refresh token of the `req`.
+    */
+    async fn renew_tokens(&self, req: &mut ServiceRequest) -> AuthResult<Option<TokenUpdate>> {
+        self.call_refresh_authorizer(req).await?;
+        if let Some(ref async_token_signer) = self.async_token_signer {
+            return self.renew_access_token_async(req, async_token_signer).await;
+        }
+        match (self.validate_refresh_token(req), &self.token_signer) {
+            (Ok(refresh_token), Some(token_signer)) => {
+                self.check_token_version(&refresh_token.custom).await?;
+                let access_token =
+                    token_signer.access_token_for_request(&refresh_token.custom, req.request())?;
+                let access_cookie = token_signer.access_cookie_from_token(access_token.clone());
+                if self.refresh_token_needs_renewal(&refresh_token, token_signer) {
+                    let renewed_refresh_token = token_signer.renewed_refresh_token_for_request(
+                        &refresh_token.custom,
+                        req.request(),
+                        refresh_token.issued_at,
+                    )?;
+                    let refresh_cookie =
+                        token_signer.refresh_cookie_from_token(renewed_refresh_token.clone());
+                    let csrf_cookie = token_signer.csrf_cookie_for_request(req.request());
+                    self.insert_claims(
+                        req,
+                        RegisteredClaims::from_issued_token(&access_token)?,
+                        refresh_token.custom,
+                    );
+                    return make_token_update!(
+                        access_token,
+                        access_cookie,
+                        renewed_refresh_token,
+                        refresh_cookie,
+                        csrf_cookie
+                    );
+                }
+                self.insert_claims(
+                    req,
+                    RegisteredClaims::from_issued_token(&access_token)?,
+                    refresh_token.custom,
+                );
+                make_token_update!(access_token, access_cookie)
+            }
+            (Err(AuthError::TokenValidation(TokenExpired, _)), Some(token_signer))
+                if self.renew_refresh_token_automatically =>
+            {
+                let refresh_token =
+                    self.validate_token(req.request(), TokenKind::Refresh, ExpiredTokens::Accept)?;
+                let claims = refresh_token.custom;
+                self.check_token_version(&claims).await?;
+                let access_token = token_signer.access_token_for_request(&claims, req.request())?;
+                let access_cookie = token_signer.access_cookie_from_token(access_token.clone());
+                let renewed_refresh_token = token_signer.renewed_refresh_token_for_request(
+                    &claims,
+                    req.request(),
+                    refresh_token.issued_at,
+                )?;
+                let refresh_cookie =
+                    token_signer.refresh_cookie_from_token(renewed_refresh_token.clone());
+                let csrf_cookie = token_signer.csrf_cookie_for_request(req.request());
+                self.insert_claims(
+                    req,
+                    RegisteredClaims::from_issued_token(&access_token)?,
+                    claims,
+                );
+                make_token_update!(
+                    access_token,
+                    access_cookie,
+                    renewed_refresh_token,
+                    refresh_cookie,
+                    csrf_cookie
+                )
+            }
+            (Ok(_), None) => Err(AuthError::NoTokenSigner),
+            (Err(err), _) => Err(map_expired_refresh_token(err)),
+        }
+    }
+}
+
+impl<Claims, Algo, ReAuth, Args> AuthorityBuilder<Claims, Algo, ReAuth, Args>
+where
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+{
+    /*
+        Catches misconfigurations at startup instead of failing requests at runtime, it rejects
+        - a `refresh_renewal_threshold` outside of `0.0..=1.0`,
+        - a `verifying_key` which can not verify the access tokens signed by the `token_signer`,
+        - a `refresh_verifying_key`, or the `verifying_key` if none is set,
+          which can not verify the refresh tokens signed by the `token_signer`.
+    */
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(threshold)) = self.refresh_renewal_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err("The refresh_renewal_threshold has to be between 0.0 and 1.0.".into());
+            }
+        }
+        if let (Some(Some(ref token_signer)), Some(ref verifying_key)) =
+            (&self.token_signer, &self.verifying_key)
+        {
+            let algorithm = self.algorithm.as_ref().unwrap_or(&token_signer.algorithm);
+            if !token_signer.is_verified_by(algorithm, verifying_key, TokenKind::Access) {
+                return Err(
+                    "The verifying_key does not match the signing_key of the token_signer, every token signed by the token_signer would be rejected."
+                        .into(),
+                );
+            }
+
+            let refresh_algorithm = match self.refresh_algorithm {
+                Some(Some(ref refresh_algorithm)) => refresh_algorithm,
+                _ => token_signer.refresh_algorithm.as_ref().unwrap_or(algorithm),
+            };
+            let refresh_verifying_key = match self.refresh_verifying_key {
+                Some(Some(ref refresh_verifying_key)) => refresh_verifying_key,
+                _ => verifying_key,
+            };
+            if !token_signer.is_verified_by(
+                refresh_algorithm,
+                refresh_verifying_key,
+                TokenKind::Refresh,
+            ) {
+                return Err(
+                    "The refresh_verifying_key does not match the refresh_signing_key of the token_signer, every refresh token signed by the token_signer would be rejected."
+                        .into(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /**
+        Sets a function computing a fingerprint of the device a request originates from,
+        overriding the `fingerprint_extractor` of the `token_signer`.
+
+        Please refer to [`crate::TokenSignerBuilder::fingerprint_extractor`] for more details.
+    */
+    pub fn fingerprint_extractor<F>(mut self, fingerprint_extractor: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> String + Send + Sync + 'static,
+    {
+        self.fingerprint_extractor = Some(Some(Arc::new(fingerprint_extractor)));
+        self
+    }
+
+    /**
+        Sets a signer which signs renewed access tokens asynchronously,
+        for example by delegating to a hardware security module or a key management service.
+
+        Only the access token is renewed through the `async_token_signer`, even if `renew_refresh_token_automatically` is set to true.
+        The cookie carrying the renewed access token is configured by the `token_signer` if one is set,
+        otherwise a `Secure` cookie with `SameSite=Strict` named after the `access_token_name` is used,
+        which allows deployments without any signing key on the server.
+    */
+    pub fn async_token_signer<S>(mut self, async_token_signer: S) -> Self
+    where
+        S: AsyncTokenSigner<Claims> + Send + Sync + 'static,
+    {
+        self.async_token_signer = Some(Some(Arc::new(async_token_signer)));
+        self
+    }
+
+    /**
+        Sets a function which is called with the request and its validated claims once the request is authenticated,
+        right before the claims are inserted into the request extensions. It is never called for rejected requests.
+
+        This allows forwarding the identity to services which do not parse JWTs themselves,
+        for example by setting a request header derived from the claims.
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::Authority;
+        # use actix_web::http::header::{HeaderName, HeaderValue};
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        # use jwt_compact::TimeOptions;
+        # use serde::{Deserialize, Serialize};
+        #[derive(Serialize, Deserialize, Clone)]
+        struct User {
+            id: u32,
+        }
+
+        let authority = Authority::<User, Ed25519, _, _>::new()
+            .refresh_authorizer(|| async move { Ok(()) })
+            .algorithm(Ed25519)
+            .verifying_key(KeyPair::generate().pk)
+            .time_options(TimeOptions::default())
+            .request_mutator(|req, user: &User| {
+                req.headers_mut().insert(
+                    HeaderName::from_static("x-user-id"),
+                    HeaderValue::from(user.id),
+                );
+            })
+            .build()
+            .unwrap();
+        ```
+    */
+    pub fn request_mutator<F>(mut self, request_mutator: F) -> Self
+    where
+        F: Fn(&mut ServiceRequest, &Claims) + Send + Sync + 'static,
+    {
+        self.request_mutator = Some(Some(Arc::new(request_mutator)));
+        self
+    }
+}
+
+impl<Claims, Algo, ReAuth, Args> AuthorityBuilder<Claims, Algo, ReAuth, Args>
+where
+    Claims: Clone + 'static,
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+{
+    /**
+        Additionally inserts the validated claims wrapped in a [`ScopedClaims<Claims, Marker>`](crate::ScopedClaims)
+        into the request extensions.
+
+        Request extensions are keyed by type, so when two authorities with the same `Claims` type are nested
+        (for example an outer and an inner scope verified by different keys), the inner authority silently overwrites the claims
+        inserted by the outer one. Giving every authority a distinct `Marker` type keeps the claims of each authority
+        extractable through [`ScopedClaims`](crate::ScopedClaims).
+
+        The plain `Claims` are still inserted, the innermost authority wins in this case.
+    */
+    pub fn claims_marker<Marker: 'static>(mut self) -> Self {
+        self.claims_inserter = Some(Some(insert_scoped_claims::<Claims, Marker>));
+        self
+    }
+}
+
+impl<Claims, Algo, ReAuth, Args> AuthorityBuilder<Claims, Algo, ReAuth, Args>
+where
+    Claims: TokenVersion,
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+{
+    /**
+        Sets a function looking up the minimal token version of a subject,
+        tokens whose [`TokenVersion::token_version`] is below it are rejected with [`AuthError::TokenSuperseded`].
+
+        This allows logging a subject out everywhere by bumping its version, without keeping track of every issued token.
+        The version is checked for access tokens as well as for refresh tokens before they are used to renew the access token,
+        but only by the middleware, [`Authority::verify_http_request`] does not check it since it is synchronous.
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::{Authority, TokenVersion};
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        # use jwt_compact::TimeOptions;
+        # use serde::{Deserialize, Serialize};
+        #[derive(Serialize, Deserialize, Clone)]
+        struct User {
+            sub: String,
+            token_version: u64,
+        }
+
+        impl TokenVersion for User {
+            fn subject(&self) -> String {
+                self.sub.clone()
+            }
+
+            fn token_version(&self) -> u64 {
+                self.token_version
+            }
+        }
+
+        async fn current_token_version(_subject: String) -> u64 {
+            // look up the token version of the subject in your database
+            0
+        }
+
+        let authority = Authority::<User, Ed25519, _, _>::new()
+            .refresh_authorizer(|| async move { Ok(()) })
+            .algorithm(Ed25519)
+            .verifying_key(KeyPair::generate().pk)
+            .time_options(TimeOptions::default())
+            .min_token_version(current_token_version)
+            .build()
+            .unwrap();
+        ```
+    */
+    pub fn min_token_version<F, Fut>(mut self, min_token_version: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = u64> + 'static,
+    {
+        self.token_version_check = Some(Some(token_version_check(min_token_version)));
+        self
+    }
 }
 
 impl<Claims, Algo, ReAuth, Args> Authority<Claims, Algo, ReAuth, Args>
@@ -268,142 +1586,820 @@ where
     Args: FromRequest,
 {
     #[inline]
-    fn validate_access_token(&self, req: &ServiceRequest) -> AuthResult<Token<Claims>> {
-        self.validate_token(req, &self.access_token_name)
+    pub(crate) fn access_token_header_name(&self) -> &str {
+        self.access_token_header_name
+            .as_deref()
+            .unwrap_or(&self.access_token_name)
     }
 
     #[inline]
-    fn validate_refresh_token(&self, req: &ServiceRequest) -> AuthResult<Token<Claims>> {
-        self.validate_token(req, &self.refresh_token_name)
+    pub(crate) fn refresh_token_header_name(&self) -> &str {
+        self.refresh_token_header_name
+            .as_deref()
+            .unwrap_or(&self.refresh_token_name)
     }
 
-    fn validate_token(&self, req: &ServiceRequest, token_name: &str) -> AuthResult<Token<Claims>> {
-        if self.enable_query_tokens {
-            continue_if_matches_err_variant!(
-                self.get_token_from_query(req, token_name),
-                AuthError::NoToken
-            )
+    #[inline]
+    fn query_access_token_name(&self) -> &str {
+        self.query_access_token_name
+            .as_deref()
+            .unwrap_or(&self.access_token_name)
+    }
+
+    #[inline]
+    fn query_refresh_token_name(&self) -> &str {
+        self.query_refresh_token_name
+            .as_deref()
+            .unwrap_or(&self.refresh_token_name)
+    }
+
+    #[inline]
+    fn validate_access_token(&self, req: &ServiceRequest) -> AuthResult<TokenClaims<Claims>> {
+        self.validate_token(req.request(), TokenKind::Access, ExpiredTokens::Reject)
+    }
+
+    #[inline]
+    fn validate_refresh_token(&self, req: &ServiceRequest) -> AuthResult<TokenClaims<Claims>> {
+        self.validate_token(req.request(), TokenKind::Refresh, ExpiredTokens::Reject)
+    }
+
+    /*
+        Renews the access token using the `async_token_signer`, refresh tokens are never renewed this way.
+    */
+    async fn renew_access_token_async(
+        &self,
+        req: &mut ServiceRequest,
+        async_token_signer: &SharedAsyncTokenSigner<Claims>,
+    ) -> AuthResult<Option<TokenUpdate>> {
+        let refresh_token = self
+            .validate_refresh_token(req)
+            .map_err(map_expired_refresh_token)?;
+        self.check_token_version(&refresh_token.custom).await?;
+        let access_token = match self.fingerprint_extractor {
+            Some(ref fingerprint_extractor) => {
+                let fingerprint_hash = hash_fingerprint(&fingerprint_extractor(req.request()));
+                async_token_signer
+                    .sign_fingerprinted(&refresh_token.custom, &fingerprint_hash)
+                    .await?
+            }
+            None => async_token_signer.sign(&refresh_token.custom).await?,
+        };
+        let registered_claims = RegisteredClaims::from_issued_token(&access_token)?;
+        let access_cookie = match self.token_signer {
+            Some(ref token_signer) => token_signer.access_cookie_from_token(access_token.clone()),
+            None => {
+                let mut access_cookie = default_cookie_builder().finish();
+                access_cookie.set_name(self.cookie_name(&self.access_token_name));
+                access_cookie.set_value(self.seal_cookie_token(access_token.clone()));
+                access_cookie
+            }
+        };
+        self.insert_claims(req, registered_claims, refresh_token.custom);
+        make_token_update!(access_token, access_cookie)
+    }
+
+    /*
+        Rejects the `claims` if a `min_token_version` is set and their version is outdated.
+    */
+    async fn check_token_version(&self, claims: &Claims) -> AuthResult<()> {
+        match self.token_version_check {
+            Some(ref token_version_check) => token_version_check(claims).await,
+            None => Ok(()),
         }
-        if self.enable_header_tokens {
-            continue_if_matches_err_variant!(
-                self.get_token_from_header_value(req.headers(), token_name),
-                AuthError::NoToken
-            )
+    }
+
+    /*
+        Checks whether less than `refresh_renewal_threshold` of the lifetime of a still valid refresh token is left.
+    */
+    fn refresh_token_needs_renewal(
+        &self,
+        refresh_token: &TokenClaims<Claims>,
+        token_signer: &TokenSigner<Claims, Algo>,
+    ) -> bool {
+        let (Some(threshold), Some(expiration), Some(issued_at)) = (
+            self.refresh_renewal_threshold,
+            refresh_token.expiration,
+            refresh_token.issued_at,
+        ) else {
+            return false;
+        };
+        if !self.renew_refresh_token_automatically {
+            return false;
         }
-        if self.enable_authorization_header {
-            continue_if_matches_err_variant!(
-                self.get_token_from_authorization_header(req.headers()),
-                AuthError::NoToken
-            )
+
+        // renewed refresh tokens keep the `iat` of the login, so their lifetime is capped by the `refresh_token_lifetime`
+        let lifetime = (expiration - issued_at)
+            .num_milliseconds()
+            .min(token_signer.refresh_token_lifetime.as_millis() as i64)
+            as f64;
+        let remaining = (expiration - self.time_options.now()).num_milliseconds() as f64;
+        remaining <= lifetime * f64::from(threshold)
+    }
+
+    /*
+        Checks whether less than `access_renewal_threshold` of the lifetime of a still valid access token is left.
+    */
+    fn access_token_needs_renewal(&self, access_token: &TokenClaims<Claims>) -> bool {
+        let (Some(threshold), Some(expiration)) =
+            (self.access_renewal_threshold, access_token.expiration)
+        else {
+            return false;
+        };
+        if !self.renew_access_token_automatically {
+            return false;
         }
-        if self.enable_cookie_tokens {
-            continue_if_matches_err_variant!(
-                self.get_token_from_cookie(req, token_name),
-                AuthError::NoToken
-            )
+
+        let remaining = (expiration - self.time_options.now())
+            .to_std()
+            .unwrap_or_default();
+        remaining < threshold
+    }
+
+    fn validate_token(
+        &self,
+        req: &HttpRequest,
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
+        let (token_name, query_param_name) = match kind {
+            TokenKind::Access => (&*self.access_token_name, self.query_access_token_name()),
+            TokenKind::Refresh => (&*self.refresh_token_name, self.query_refresh_token_name()),
+        };
+
+        let mut checked_sources = Vec::new();
+        for source in &self.token_source_order {
+            match source {
+                TokenSource::Query if self.enable_query_tokens => {
+                    continue_if_matches_err_variant!(
+                        self.get_token_from_query(req, query_param_name, kind, expired_tokens),
+                        AuthError::NoToken(_)
+                    )
+                }
+                TokenSource::Header if self.enable_header_tokens => {
+                    continue_if_matches_err_variant!(
+                        self.get_token_from_header_value(req, token_name, kind, expired_tokens),
+                        AuthError::NoToken(_)
+                    )
+                }
+                TokenSource::AuthorizationHeader if self.enable_authorization_header => {
+                    continue_if_matches_err_variant!(
+                        self.get_token_from_authorization_header(req, kind, expired_tokens),
+                        AuthError::NoToken(_)
+                    )
+                }
+                TokenSource::Cookie if self.enable_cookie_tokens => {
+                    continue_if_matches_err_variant!(
+                        self.get_token_from_cookie(req, token_name, kind, expired_tokens),
+                        AuthError::NoToken(_)
+                    );
+                    if let (TokenKind::Access, Some(ref navigation_cookie_name)) =
+                        (kind, &self.navigation_cookie_name)
+                    {
+                        continue_if_matches_err_variant!(
+                            self.get_token_from_cookie(
+                                req,
+                                navigation_cookie_name,
+                                kind,
+                                expired_tokens
+                            ),
+                            AuthError::NoToken(_)
+                        )
+                    }
+                }
+                TokenSource::Body if self.enable_body_tokens => {
+                    continue_if_matches_err_variant!(
+                        self.get_token_from_body(req, token_name, kind, expired_tokens),
+                        AuthError::NoToken(_)
+                    )
+                }
+                _ => continue,
+            }
+            checked_sources.push(*source);
         }
 
-        Err(AuthError::NoToken)
+        Err(AuthError::NoToken(checked_sources))
     }
 
-    fn get_token_from_cookie(
+    /*
+        Serializes a renewed token cookie, including the attributes only known to the `token_signer`.
+    */
+    pub(crate) fn set_cookie_header_value(&self, cookie: &Cookie<'_>) -> HeaderValue {
+        match self.token_signer {
+            Some(ref token_signer) => token_signer.set_cookie_header_value(cookie),
+            None => HeaderValue::from_str(&cookie.to_string())
+                .expect("Cookie should not contain ASCII characters (33-127)"),
+        }
+    }
+
+    /*
+        Returns whether the `req` is passed to the wrapped service without being authenticated.
+    */
+    pub(crate) fn skips_authentication(&self, req: &ServiceRequest) -> bool {
+        self.skip_auth_for_methods.contains(req.method())
+            || self
+                .skip_auth_for_paths
+                .iter()
+                .any(|pattern| path_matches(pattern, req.path()))
+    }
+
+    /*
+        Returns a redirect to the `unauthenticated_redirect` if the `req` comes from a browser and was rejected with the `err`
+        because it is not authenticated.
+    */
+    pub(crate) fn unauthenticated_redirect(
         &self,
         req: &ServiceRequest,
-        cookie_name: &str,
-    ) -> AuthResult<Token<Claims>> {
-        match req.cookie(cookie_name) {
-            Some(token_value) => validate_jwt(
-                &token_value.value(),
-                &self.algorithm,
-                &self.verifying_key,
-                &self.time_options,
-            ),
-            None => Err(AuthError::NoToken),
+        err: &AuthError,
+    ) -> Option<HttpResponse> {
+        let unauthenticated_redirect = self.unauthenticated_redirect.as_deref()?;
+        let is_unauthenticated = matches!(
+            err,
+            AuthError::NoToken(_)
+                | AuthError::RefreshTokenExpired
+                | AuthError::TokenValidation(TokenExpired, _)
+        );
+        let accepts_html = req
+            .headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"));
+        if !is_unauthenticated || !accepts_html {
+            return None;
+        }
+
+        let next = req
+            .uri()
+            .path_and_query()
+            .map_or(req.path(), PathAndQuery::as_str);
+        let separator = if unauthenticated_redirect.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("next", next)
+            .finish();
+        Some(
+            HttpResponse::Found()
+                .insert_header((
+                    LOCATION,
+                    format!("{unauthenticated_redirect}{separator}{query}"),
+                ))
+                .finish(),
+        )
+    }
+
+    /*
+        Selects the key the token has to be verified with,
+        the `verifying_keys` take precedence for tokens carrying a `kid` header.
+    */
+    fn verification_pair(
+        &self,
+        token_value: &str,
+        kind: TokenKind,
+    ) -> AuthResult<(&Algo, &Algo::VerifyingKey)> {
+        let algorithm = match kind {
+            TokenKind::Access => &self.algorithm,
+            TokenKind::Refresh => self.refresh_algorithm.as_ref().unwrap_or(&self.algorithm),
+        };
+        if let Some(ref verifying_keys) = self.verifying_keys {
+            if let Some(key_id) = UntrustedToken::new(token_value)?.header().key_id.as_ref() {
+                return verifying_keys
+                    .get(key_id)
+                    .map(|verifying_key| (algorithm, verifying_key))
+                    .ok_or(AuthError::UnknownKeyId);
+            }
+        }
+        let verifying_key = match kind {
+            TokenKind::Access => &self.verifying_key,
+            TokenKind::Refresh => self
+                .refresh_verifying_key
+                .as_ref()
+                .unwrap_or(&self.verifying_key),
+        };
+        Ok((algorithm, verifying_key))
+    }
+
+    fn validate_token_value(
+        &self,
+        req: &HttpRequest,
+        token_value: &str,
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
+        let time_options = match (kind, self.refresh_leeway) {
+            (TokenKind::Refresh, Some(refresh_leeway)) => {
+                self.time_options.with_leeway(refresh_leeway)
+            }
+            _ => Clone::clone(&self.time_options),
+        };
+        let (algorithm, verifying_key) = self.verification_pair(token_value, kind)?;
+        let max_age = match kind {
+            TokenKind::Access => self.max_access_token_age,
+            TokenKind::Refresh => self.max_refresh_token_age,
+        };
+        let options = ValidationOptions {
+            time_options: &time_options,
+            claims_format: self.claims_format,
+            expected_token_type: self.expected_token_type.as_deref(),
+            max_age,
+            require_expiration: self.require_expiration,
+        };
+        let claims = match validate_jwt(&token_value, algorithm, verifying_key, &options) {
+            Err(AuthError::TokenValidation(TokenExpired, _))
+                if expired_tokens == ExpiredTokens::Accept =>
+            {
+                let claims = verify_jwt_signature(
+                    &token_value,
+                    algorithm,
+                    verifying_key,
+                    self.claims_format,
+                    self.expected_token_type.as_deref(),
+                )?;
+                if let Some(max_age) = max_age {
+                    validate_age(&claims, &time_options, max_age)?;
+                }
+                claims
+            }
+            result => result?,
+        };
+        if kind == TokenKind::Access && self.enforce_access_token_profile {
+            validate_access_token_profile(token_value)?;
+        }
+        if let Some(ref fingerprint_extractor) = self.fingerprint_extractor {
+            verify_fingerprint(token_value, &fingerprint_extractor(req))?;
+        }
+        self.verify_token_claims(token_value)?;
+        Ok(claims)
+    }
+
+    /*
+        Checks the claims of a token whose signature and lifetime were already validated
+        against the `allowed_client_ids`, the `max_auth_age` and the `claims_schema`.
+    */
+    fn verify_token_claims(&self, token_value: &str) -> AuthResult<()> {
+        if let Some(ref allowed_client_ids) = self.allowed_client_ids {
+            verify_client_id(token_value, allowed_client_ids)?;
+        }
+        if let Some(max_auth_age) = self.max_auth_age {
+            verify_auth_time(token_value, &self.time_options, max_auth_age)?;
+        }
+        #[cfg(feature = "jsonschema")]
+        if let Some(ref claims_schema) = self.claims_schema {
+            claims_schema.validate(token_value)?;
+        }
+        Ok(())
+    }
+
+    fn get_token_from_cookie(
+        &self,
+        req: &HttpRequest,
+        token_name: &str,
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
+        let cookie_name = self.cookie_name(token_name);
+        let cookie_values: Vec<String> = req
+            .cookies()
+            .map(|cookies| {
+                cookies
+                    .iter()
+                    .filter(|cookie| cookie.name() == cookie_name)
+                    .map(|cookie| cookie.value().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if cookie_values.is_empty() {
+            let token_value = self.get_cookie_token_value(req, &cookie_name)?;
+            return self.validate_cookie_token_value(req, &token_value, kind, expired_tokens);
         }
+
+        // multiple cookies with the same name but different paths or domains might be sent,
+        // for example a stale one left over after the cookie path changed
+        let mut result = Err(AuthError::NoToken(Vec::new()));
+        for cookie_value in cookie_values {
+            let cookie_result = self
+                .unseal_cookie_value(cookie_value)
+                .and_then(|token_value| {
+                    self.validate_cookie_token_value(req, &token_value, kind, expired_tokens)
+                });
+            match (&result, cookie_result) {
+                (_, Ok(claims)) => return Ok(claims),
+                // an expired token can still be renewed, so it is preferred over any other error
+                (Err(AuthError::TokenValidation(TokenExpired, _)), Err(_)) => (),
+                (_, cookie_result) => result = cookie_result,
+            }
+        }
+        result
+    }
+
+    /*
+        Validates a token taken from a cookie, which additionally has to pass the CSRF check if a `csrf_header_name` is set.
+    */
+    fn validate_cookie_token_value(
+        &self,
+        req: &HttpRequest,
+        token_value: &str,
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
+        let claims = self.validate_token_value(req, token_value, kind, expired_tokens)?;
+        if let Some(ref csrf_header_name) = self.csrf_header_name {
+            verify_csrf_token(
+                req,
+                token_value,
+                csrf_header_name,
+                &self.csrf_checked_methods,
+            )?;
+        }
+        Ok(claims)
+    }
+
+    /*
+        Returns the name of the cookie holding the token called `token_name`, including the `cookie_name_prefix`.
+    */
+    fn cookie_name(&self, token_name: &str) -> String {
+        prefixed_cookie_name(self.cookie_name_prefix.as_deref(), token_name)
+    }
+
+    /*
+        Returns the token stored in the cookie named `cookie_name`.
+    */
+    fn get_cookie_token_value(&self, req: &HttpRequest, cookie_name: &str) -> AuthResult<String> {
+        let cookie_value =
+            get_cookie_value(req, cookie_name).ok_or(AuthError::NoToken(Vec::new()))?;
+        self.unseal_cookie_value(cookie_value)
+    }
+
+    /*
+        Encrypts the token if an `encryption_key` is set and encodes it if `decode_cookie_value` is set,
+        the counterpart of [`Self::unseal_cookie_value`] for cookies created without a `token_signer`.
+    */
+    fn seal_cookie_token(&self, token: String) -> String {
+        #[cfg(feature = "jwe")]
+        let token = match self.encryption_key {
+            Some(ref encryption_key) => encryption_key.encrypt(&token),
+            None => token,
+        };
+        if self.decode_cookie_value {
+            URL_SAFE_NO_PAD.encode(token)
+        } else {
+            token
+        }
+    }
+
+    /*
+        Decodes the cookie value if `decode_cookie_value` is set and decrypts it if an `encryption_key` is set.
+    */
+    fn unseal_cookie_value(&self, cookie_value: String) -> AuthResult<String> {
+        let cookie_value = if self.decode_cookie_value {
+            URL_SAFE_NO_PAD
+                .decode(cookie_value)
+                .ok()
+                .and_then(|cookie_value| String::from_utf8(cookie_value).ok())
+                .ok_or(AuthError::TokenParse(ParseError::InvalidTokenStructure))?
+        } else {
+            cookie_value
+        };
+        #[cfg(feature = "jwe")]
+        if let Some(ref encryption_key) = self.encryption_key {
+            return encryption_key.decrypt(&cookie_value);
+        }
+        Ok(cookie_value)
     }
 
     fn get_token_from_header_value(
         &self,
-        header_map: &HeaderMap,
+        req: &HttpRequest,
         header_key: &str,
-    ) -> AuthResult<Token<Claims>> {
-        match header_map.get(header_key).map(HeaderValue::to_str) {
-            Some(Ok(token_value)) => validate_jwt(
-                &token_value,
-                &self.algorithm,
-                &self.verifying_key,
-                &self.time_options,
-            ),
-            Some(_) | None => Err(AuthError::NoToken),
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
+        match req.headers().get(header_key).map(HeaderValue::to_str) {
+            Some(Ok(token_value)) => {
+                self.validate_token_value(req, token_value, kind, expired_tokens)
+            }
+            Some(_) | None => Err(AuthError::NoToken(Vec::new())),
         }
     }
 
     fn get_token_from_authorization_header(
         &self,
-        header_map: &HeaderMap,
-    ) -> AuthResult<Token<Claims>> {
-        match header_map.get(AUTHORIZATION).map(HeaderValue::to_str) {
+        req: &HttpRequest,
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
+        match req.headers().get(AUTHORIZATION).map(HeaderValue::to_str) {
             Some(Ok(header_value)) => {
                 let token_value = if let Some(token_value) = header_value.strip_prefix("Bearer") {
                     token_value.trim()
                 } else {
                     // to-do: better error handling
-                    return Err(AuthError::NoToken);
+                    return Err(AuthError::NoToken(Vec::new()));
                 };
 
-                validate_jwt(
-                    &token_value,
-                    &self.algorithm,
-                    &self.verifying_key,
-                    &self.time_options,
-                )
+                self.validate_token_value(req, token_value, kind, expired_tokens)
             }
-            Some(_) | None => Err(AuthError::NoToken),
+            Some(_) | None => Err(AuthError::NoToken(Vec::new())),
+        }
+    }
+
+    fn get_token_from_body(
+        &self,
+        req: &HttpRequest,
+        field_name: &str,
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
+        match form_body_field(req, field_name) {
+            Some(token_value) => self.validate_token_value(req, &token_value, kind, expired_tokens),
+            None => Err(AuthError::NoToken(Vec::new())),
         }
     }
 
     fn get_token_from_query(
         &self,
-        req: &ServiceRequest,
+        req: &HttpRequest,
         param_name: &str,
-    ) -> AuthResult<Token<Claims>> {
+        kind: TokenKind,
+        expired_tokens: ExpiredTokens,
+    ) -> AuthResult<TokenClaims<Claims>> {
         match form_urlencoded::parse(req.query_string().as_bytes())
             .find(|(query_param_name, _)| param_name.eq(query_param_name))
         {
-            Some((_, token_value)) => validate_jwt(
-                &token_value,
-                &self.algorithm,
-                &self.verifying_key,
-                &self.time_options,
-            ),
-            None => Err(AuthError::NoToken),
+            Some(_)
+                if self.require_tls_for_query_tokens
+                    && req.connection_info().scheme() != "https" =>
+            {
+                Err(AuthError::InsecureTransport)
+            }
+            Some((_, token_value)) => {
+                self.validate_token_value(req, &token_value, kind, expired_tokens)
+            }
+            None => Err(AuthError::NoToken(Vec::new())),
+        }
+    }
+
+    fn strip_query_tokens(&self, req: &mut ServiceRequest) {
+        let uri = req.uri();
+        let query = match uri.query() {
+            Some(query) => query,
+            None => return,
+        };
+        let is_token_param = |param_name: &str| {
+            param_name == self.query_access_token_name()
+                || param_name == self.query_refresh_token_name()
+        };
+        // the remaining pairs are kept exactly as sent, re-encoding them could change their meaning
+        let is_token_pair = |pair: &&str| {
+            form_urlencoded::parse(pair.as_bytes())
+                .next()
+                .is_some_and(|(param_name, _)| is_token_param(&param_name))
+        };
+        if !query.split('&').any(|pair| is_token_pair(&pair)) {
+            return;
+        }
+
+        let redacted_query = query
+            .split('&')
+            .filter(|pair| !is_token_pair(pair))
+            .collect::<Vec<_>>()
+            .join("&");
+        let path_and_query = if redacted_query.is_empty() {
+            uri.path().to_owned()
+        } else {
+            format!("{}?{}", uri.path(), redacted_query)
+        };
+
+        let mut uri_parts = uri.clone().into_parts();
+        if let Ok(path_and_query) = PathAndQuery::try_from(path_and_query) {
+            uri_parts.path_and_query = Some(path_and_query);
+            if let Ok(redacted_uri) = Uri::from_parts(uri_parts) {
+                req.head_mut().uri = redacted_uri;
+            }
         }
     }
 
     async fn call_refresh_authorizer(&self, req: &mut ServiceRequest) -> AuthResult<()> {
         let (mut_req, payload) = req.parts_mut();
         match Args::from_request(mut_req, payload).await {
-            Ok(args) => self
-                .refresh_authorizer
-                .call(args)
-                .await
-                .map_err(AuthError::RefreshAuthorizerDenied),
+            Ok(args) => self.refresh_authorizer.call(args).await.map_err(|err| {
+                match self.refresh_denied_status {
+                    Some(status) if !err.as_response_error().status_code().is_redirection() => {
+                        AuthError::RefreshAuthorizerDenied(
+                            InternalError::new(err.to_string(), status).into(),
+                        )
+                    }
+                    _ => AuthError::RefreshAuthorizerDenied(err),
+                }
+            }),
             Err(err) => Err(AuthError::RefreshAuthorizerCall(err.into())),
         }
     }
 }
 
-#[inline]
-fn extract_claims_unsafe<S, Claims>(token_value: &S) -> Claims
+impl<Claims, Algo, ReAuth, Args> fmt::Debug for Authority<Claims, Algo, ReAuth, Args>
 where
-    S: AsRef<str> + ?Sized,
-    Claims: DeserializeOwned,
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
 {
-    UntrustedToken::new(token_value)
-        .expect(
-            "UntrustedToken token has to be parseable fro, cookie value in order to get to here",
-        )
-        .deserialize_claims_unchecked::<Claims>()
-        .expect("Claims has to be desirializeable to get to this point")
-        .custom
+    /**
+        Formats the [`Authority`] while redacting the `verifying_key` as well as the `signing_key` of the `token_signer`.
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authority")
+            .field("refresh_denied_status", &self.refresh_denied_status)
+            .field("access_token_name", &self.access_token_name)
+            .field(
+                "renew_access_token_automatically",
+                &self.renew_access_token_automatically,
+            )
+            .field("allow_expired", &self.allow_expired)
+            .field("require_expiration", &self.require_expiration)
+            .field("share_claims", &self.share_claims)
+            .field("insert_subject", &self.insert_subject)
+            .field("checks_token_version", &self.token_version_check.is_some())
+            .field("mutates_requests", &self.request_mutator.is_some())
+            .field("token_delivery", &self.token_delivery)
+            .field("access_token_header_name", &self.access_token_header_name)
+            .field("refresh_token_header_name", &self.refresh_token_header_name)
+            .field("no_store_token_responses", &self.no_store_token_responses)
+            .field("skip_auth_for_methods", &self.skip_auth_for_methods)
+            .field("skip_auth_for_paths", &self.skip_auth_for_paths)
+            .field("unauthenticated_redirect", &self.unauthenticated_redirect)
+            .field("navigation_cookie_name", &self.navigation_cookie_name)
+            .field("cookie_name_prefix", &self.cookie_name_prefix)
+            .field("refresh_token_name", &self.refresh_token_name)
+            .field(
+                "renew_refresh_token_automatically",
+                &self.renew_refresh_token_automatically,
+            )
+            .field("refresh_leeway", &self.refresh_leeway)
+            .field("refresh_renewal_threshold", &self.refresh_renewal_threshold)
+            .field("access_renewal_threshold", &self.access_renewal_threshold)
+            .field("max_access_token_age", &self.max_access_token_age)
+            .field("max_refresh_token_age", &self.max_refresh_token_age)
+            .field("max_auth_age", &self.max_auth_age)
+            .field("query_access_token_name", &self.query_access_token_name)
+            .field("query_refresh_token_name", &self.query_refresh_token_name)
+            .field("enable_header_tokens", &self.enable_header_tokens)
+            .field(
+                "enable_authorization_header",
+                &self.enable_authorization_header,
+            )
+            .field("enable_query_tokens", &self.enable_query_tokens)
+            .field("redact_query_tokens", &self.redact_query_tokens)
+            .field(
+                "require_tls_for_query_tokens",
+                &self.require_tls_for_query_tokens,
+            )
+            .field("enable_cookie_tokens", &self.enable_cookie_tokens)
+            .field("enable_body_tokens", &self.enable_body_tokens)
+            .field("token_source_order", &self.token_source_order)
+            .field("verifying_key", &format_args!("<redacted>"))
+            .field(
+                "verifying_keys",
+                &self
+                    .verifying_keys
+                    .as_ref()
+                    .map(|verifying_keys| verifying_keys.keys().collect::<Vec<_>>()),
+            )
+            .field("algorithm", &self.algorithm.name())
+            .field(
+                "refresh_verifying_key",
+                &self.refresh_verifying_key.as_ref().map(|_| "<redacted>"),
+            )
+            .field(
+                "refresh_algorithm",
+                &self.refresh_algorithm.as_ref().map(Algorithm::name),
+            )
+            .field("time_options", &self.time_options)
+            .field("claims_format", &self.claims_format)
+            .field("expected_token_type", &self.expected_token_type)
+            .field(
+                "enforce_access_token_profile",
+                &self.enforce_access_token_profile,
+            )
+            .field("csrf_header_name", &self.csrf_header_name)
+            .field("csrf_checked_methods", &self.csrf_checked_methods)
+            .field(
+                "fingerprint_extractor",
+                &self.fingerprint_extractor.is_some(),
+            )
+            .field("encryption_key", &self.encryption_key)
+            .field("claims_schema", &self.claims_schema)
+            .field("problem_details_responses", &self.problem_details_responses)
+            .field("allowed_client_ids", &self.allowed_client_ids)
+            .field("decode_cookie_value", &self.decode_cookie_value)
+            .field("token_signer", &self.token_signer)
+            .field("async_token_signer", &self.async_token_signer.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/*
+    Maps the result of a verified service request to a short name describing its outcome.
+    Never includes any token contents.
+*/
+#[cfg(feature = "tracing")]
+fn outcome_name(result: &AuthResult<Option<TokenUpdate>>) -> &'static str {
+    match result {
+        Ok(None) => "authenticated",
+        Ok(Some(_)) => "refreshed",
+        Err(AuthError::NoToken(_)) => "no_token",
+        Err(
+            AuthError::TokenValidation(TokenExpired, _)
+            | AuthError::RefreshTokenExpired
+            | AuthError::ReauthRequired
+            | AuthError::SessionExpired,
+        ) => "expired",
+        Err(AuthError::RefreshAuthorizerDenied(_)) => "denied",
+        Err(_) => "invalid",
+    }
+}
+
+/*
+    Maps the result of a verified service request to the name of the counter tracking its outcome.
+    Counters carry no labels, so they never include any token contents or user identifiers.
+*/
+#[cfg(feature = "metrics")]
+fn counter_name(result: &AuthResult<Option<TokenUpdate>>) -> &'static str {
+    match result {
+        Ok(None) => "jwt_auth_success_total",
+        Ok(Some(_)) => "jwt_auth_refreshed_total",
+        Err(AuthError::NoToken(_)) => "jwt_auth_no_token_total",
+        Err(
+            AuthError::TokenValidation(TokenExpired, _)
+            | AuthError::RefreshTokenExpired
+            | AuthError::ReauthRequired
+            | AuthError::SessionExpired,
+        ) => "jwt_auth_expired_total",
+        Err(AuthError::RefreshAuthorizerDenied(_)) => "jwt_auth_denied_total",
+        Err(_) => "jwt_auth_invalid_total",
+    }
+}
+
+/*
+    Distinguishes an expired refresh token from an expired access token,
+    so clients know that they have to authenticate again instead of retrying the refresh.
+*/
+fn map_expired_refresh_token(err: AuthError) -> AuthError {
+    match err {
+        AuthError::TokenValidation(TokenExpired, _) => AuthError::RefreshTokenExpired,
+        err => err,
+    }
+}
+
+/*
+    The kind of token which is currently validated.
+*/
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum TokenKind {
+    Access,
+    Refresh,
+}
+
+/*
+    Whether a token is accepted after it expired while it is looked up,
+    every other check including its signature still has to pass.
+*/
+#[derive(Clone, Copy, PartialEq)]
+enum ExpiredTokens {
+    Reject,
+    Accept,
+}
+
+/*
+    Returns the value of the cookie named `cookie_name`,
+    or the concatenated values of the cookies `{cookie_name}.0`, `{cookie_name}.1`, ...
+    if the value was split across multiple cookies by the `TokenSigner`.
+*/
+fn get_cookie_value(req: &HttpRequest, cookie_name: &str) -> Option<String> {
+    if let Some(cookie) = req.cookie(cookie_name) {
+        return Some(cookie.value().to_string());
+    }
+
+    let mut cookie_value = String::new();
+    let mut index = 0;
+    while let Some(chunk) = req.cookie(&format!("{cookie_name}.{index}")) {
+        cookie_value.push_str(chunk.value());
+        index += 1;
+    }
+
+    if cookie_value.is_empty() {
+        None
+    } else {
+        Some(cookie_value)
+    }
+}
+
+/*
+    Matches the `path` against the `pattern`, a trailing `*` matches any suffix.
+*/
+fn path_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => path.starts_with(prefix),
+        None => pattern == path,
+    }
 }
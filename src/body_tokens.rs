@@ -0,0 +1,50 @@
+use crate::AuthError;
+use crate::AuthResult;
+
+use actix_web::dev::ServiceRequest;
+use actix_web::web::Bytes;
+use actix_web::FromRequest;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+
+/*
+    The only content type tokens are read from, other bodies are never buffered.
+*/
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+/*
+    The buffered body of a form request, inserted into the request extensions by `buffer_form_body`.
+*/
+struct BufferedForm(Bytes);
+
+/*
+    Buffers the body of a form request and restores it afterwards, so the handler can still read it.
+    The size of the buffered body is limited by the `PayloadConfig` of the application.
+*/
+pub(crate) async fn buffer_form_body(req: &mut ServiceRequest) -> AuthResult<()> {
+    if req.content_type() != FORM_CONTENT_TYPE {
+        return Ok(());
+    }
+
+    let (http_req, payload) = req.parts_mut();
+    let body = Bytes::from_request(http_req, payload)
+        .await
+        .map_err(AuthError::BodyRead)?;
+
+    let (_, mut restored_payload) = actix_http::h1::Payload::create(true);
+    restored_payload.unread_data(body.clone());
+    req.set_payload(restored_payload.into());
+    req.extensions_mut().insert(BufferedForm(body));
+    Ok(())
+}
+
+/*
+    Returns the value of the field called `field_name` of a form body buffered by `buffer_form_body`.
+*/
+pub(crate) fn form_body_field(req: &HttpRequest, field_name: &str) -> Option<String> {
+    let extensions = req.extensions();
+    let BufferedForm(body) = extensions.get::<BufferedForm>()?;
+    form_urlencoded::parse(body)
+        .find(|(name, _)| name == field_name)
+        .map(|(_, value)| value.into_owned())
+}
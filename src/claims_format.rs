@@ -0,0 +1,75 @@
+use crate::AuthError;
+use crate::AuthResult;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jwt_compact::ParseError;
+use serde::Deserialize;
+
+/**
+    The format the claims of a token are serialized in.
+
+    The [`crate::TokenSigner`] and the [`crate::Authority`] have to agree on the format,
+    tokens in a different format are rejected by the [`crate::Authority`].
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClaimsFormat {
+    /**
+        Claims are serialized as JSON, as specified by RFC 7519.
+    */
+    #[default]
+    Json,
+    /**
+        Claims are serialized as [CBOR](https://cbor.io/), this results in smaller tokens,
+        but is not supported by every JWT library.
+
+        This format is only available with the `cbor` feature enabled.
+    */
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+/*
+    The `cty` header set by jwt-compact on tokens with CBOR claims.
+*/
+#[cfg(feature = "cbor")]
+const CBOR_CONTENT_TYPE: &str = "CBOR";
+
+#[derive(Deserialize)]
+struct ContentTypeHeader {
+    cty: Option<String>,
+}
+
+/*
+    Checks that the claims of the token are serialized in the `expected_format`,
+    based on the `cty` header of the token.
+*/
+pub(crate) fn check_claims_format(
+    token_value: &str,
+    expected_format: ClaimsFormat,
+) -> AuthResult<()> {
+    let encoded_header = token_value.split('.').next().unwrap_or_default();
+    let header = URL_SAFE_NO_PAD
+        .decode(encoded_header)
+        .map_err(|_| AuthError::TokenParse(ParseError::InvalidBase64Encoding))?;
+    let content_type = serde_json::from_slice::<ContentTypeHeader>(&header)
+        .map_err(|err| AuthError::TokenParse(ParseError::MalformedHeader(err)))?
+        .cty;
+
+    let format = match content_type.as_deref() {
+        None => ClaimsFormat::Json,
+        #[cfg(feature = "cbor")]
+        Some(CBOR_CONTENT_TYPE) => ClaimsFormat::Cbor,
+        Some(content_type) => {
+            return Err(AuthError::TokenParse(ParseError::UnsupportedContentType(
+                content_type.to_string(),
+            )))
+        }
+    };
+    if format != expected_format {
+        return Err(AuthError::TokenParse(ParseError::UnsupportedContentType(
+            content_type.unwrap_or_else(|| "JSON".into()),
+        )));
+    }
+    Ok(())
+}
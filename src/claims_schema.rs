@@ -0,0 +1,80 @@
+#[cfg(feature = "jsonschema")]
+use crate::validate::deserialize_claims_unchecked;
+#[cfg(feature = "jsonschema")]
+use crate::AuthError;
+#[cfg(feature = "jsonschema")]
+use crate::AuthResult;
+
+use std::fmt;
+#[cfg(feature = "jsonschema")]
+use std::sync::Arc;
+
+#[cfg(feature = "jsonschema")]
+use jsonschema::ValidationError;
+#[cfg(feature = "jsonschema")]
+use jsonschema::Validator;
+#[cfg(feature = "jsonschema")]
+use serde_json::Value;
+
+/**
+    A compiled [JSON Schema](https://json-schema.org/) the claims of every token have to match.
+
+    The schema is applied to the raw claims of a token after its signature was verified,
+    including the registered claims like `exp` or `iss`.
+    This allows expressing constraints the `Claims` type can not, like value ranges, enumerations or conditionally required fields,
+    which is useful for gateways accepting tokens issued by third parties.
+
+    Constructing a schema is only possible with the `jsonschema` feature enabled.
+
+    # Example
+    ```rust
+    # #[cfg(feature = "jsonschema")]
+    # {
+    # use actix_jwt_auth_middleware::ClaimsSchema;
+    # use serde_json::json;
+    let claims_schema = ClaimsSchema::new(&json!({
+        "type": "object",
+        "properties": {
+            "role": { "enum": ["admin", "user"] }
+        },
+        "required": ["role"]
+    }))
+    .unwrap();
+    # }
+    ```
+*/
+#[derive(Clone)]
+pub struct ClaimsSchema {
+    #[cfg(feature = "jsonschema")]
+    validator: Arc<Validator>,
+}
+
+#[cfg(feature = "jsonschema")]
+impl ClaimsSchema {
+    /**
+        Compiles the `schema`, returning an error if it is not a valid JSON Schema.
+    */
+    pub fn new(schema: &Value) -> Result<Self, Box<ValidationError<'static>>> {
+        Ok(Self {
+            validator: Arc::new(jsonschema::validator_for(schema).map_err(Box::new)?),
+        })
+    }
+
+    /*
+        Checks the claims of an already validated token against the schema.
+    */
+    pub(crate) fn validate(&self, token_value: &str) -> AuthResult<()> {
+        let claims = deserialize_claims_unchecked::<_, Value>(token_value)?;
+        let claims = serde_json::to_value(claims)
+            .expect("Claims deserialized from JSON can be serialized again");
+        self.validator
+            .validate(&claims)
+            .map_err(|err| AuthError::ClaimsSchemaViolation(err.to_string()))
+    }
+}
+
+impl fmt::Debug for ClaimsSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClaimsSchema")
+    }
+}
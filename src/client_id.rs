@@ -0,0 +1,28 @@
+use crate::validate::deserialize_claims_unchecked;
+use crate::AuthError;
+use crate::AuthResult;
+
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct ClientIdClaims {
+    azp: Option<String>,
+    client_id: Option<String>,
+}
+
+/*
+    Checks that an already validated token was issued to one of the `allowed_client_ids`,
+    based on its `azp` claim, falling back to its `client_id` claim.
+*/
+pub(crate) fn verify_client_id(
+    token_value: &str,
+    allowed_client_ids: &HashSet<String>,
+) -> AuthResult<()> {
+    let claims = deserialize_claims_unchecked::<_, ClientIdClaims>(token_value)?.custom;
+    match claims.azp.or(claims.client_id) {
+        Some(ref client_id) if allowed_client_ids.contains(client_id) => Ok(()),
+        _ => Err(AuthError::ClientNotAllowed),
+    }
+}
@@ -0,0 +1,126 @@
+use crate::AuthError;
+use crate::AuthResult;
+
+use std::io::Read;
+use std::io::Write;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use jwt_compact::Claims as TokenClaims;
+use jwt_compact::CreationError;
+use jwt_compact::Header;
+use jwt_compact::ValidationError;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+/*
+    Value of the `zip` header field signaling that the claims of a token are DEFLATE compressed,
+    borrowed from the JWE specification (RFC 7516).
+*/
+const DEFLATE: &str = "DEF";
+
+/*
+    Maximum size in bytes the claims of a token may inflate to.
+    DEFLATE compresses repetitive input by a factor of up to ~1000, so without a limit
+    a crafted token of a few kilobytes inflates to megabytes of memory before its signature is even verified.
+*/
+const MAX_INFLATED_CLAIMS_SIZE: u64 = 64 * 1024;
+
+/*
+    Custom header fields used to signal whether the claims of a token are compressed.
+*/
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub(crate) struct CompressionHeader {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zip: Option<String>,
+}
+
+impl CompressionHeader {
+    pub(crate) fn is_deflated(&self) -> bool {
+        self.zip.as_deref() == Some(DEFLATE)
+    }
+}
+
+/*
+    Custom claims of a token holding the DEFLATE compressed and base64url encoded custom claims.
+*/
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CompressedClaims {
+    deflated: String,
+}
+
+/*
+    Returns a copy of the `header` which signals that the claims of the token are DEFLATE compressed.
+*/
+pub(crate) fn deflate_header(header: &Header) -> Header<CompressionHeader> {
+    let mut deflate_header = Header::new(CompressionHeader {
+        zip: Some(DEFLATE.into()),
+    });
+    deflate_header.key_set_url = header.key_set_url.clone();
+    deflate_header.key_id = header.key_id.clone();
+    deflate_header.certificate_url = header.certificate_url.clone();
+    deflate_header.certificate_sha1_thumbprint = header.certificate_sha1_thumbprint.clone();
+    deflate_header.certificate_thumbprint = header.certificate_thumbprint.clone();
+    deflate_header.token_type = header.token_type.clone();
+    deflate_header
+}
+
+pub(crate) fn compress_claims<Claims>(claims: &Claims) -> AuthResult<CompressedClaims>
+where
+    Claims: Serialize,
+{
+    let serialized_claims = serde_json::to_vec(claims)
+        .map_err(|err| AuthError::TokenCreation(CreationError::Claims(err)))?;
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&serialized_claims)
+        .expect("Writing to a Vec should not fail");
+    let compressed_claims = encoder.finish().expect("Writing to a Vec should not fail");
+
+    Ok(CompressedClaims {
+        deflated: URL_SAFE_NO_PAD.encode(compressed_claims),
+    })
+}
+
+pub(crate) fn decompress_claims<Claims>(
+    compressed_claims: TokenClaims<CompressedClaims>,
+) -> AuthResult<TokenClaims<Claims>>
+where
+    Claims: DeserializeOwned,
+{
+    let malformed_claims = |message: &str| {
+        AuthError::TokenValidation(
+            ValidationError::MalformedClaims(<serde_json::Error as serde::de::Error>::custom(
+                message,
+            )),
+            None,
+        )
+    };
+
+    let deflated_claims = URL_SAFE_NO_PAD
+        .decode(&compressed_claims.custom.deflated)
+        .map_err(|_| malformed_claims("compressed claims are not base64url encoded"))?;
+    let mut serialized_claims = Vec::new();
+    DeflateDecoder::new(deflated_claims.as_slice())
+        .take(MAX_INFLATED_CLAIMS_SIZE + 1)
+        .read_to_end(&mut serialized_claims)
+        .map_err(|_| malformed_claims("compressed claims could not be inflated"))?;
+    if serialized_claims.len() as u64 > MAX_INFLATED_CLAIMS_SIZE {
+        return Err(malformed_claims(
+            "compressed claims exceed the maximum inflated size",
+        ));
+    }
+    let custom = serde_json::from_slice(&serialized_claims)
+        .map_err(|err| AuthError::ClaimsDeserialization(ValidationError::MalformedClaims(err)))?;
+
+    let mut claims = TokenClaims::new(custom);
+    claims.expiration = compressed_claims.expiration;
+    claims.not_before = compressed_claims.not_before;
+    claims.issued_at = compressed_claims.issued_at;
+    Ok(claims)
+}
@@ -0,0 +1,77 @@
+use crate::rng::random_token;
+use crate::validate::deserialize_claims_unchecked;
+use crate::AuthError;
+use crate::AuthResult;
+use crate::RandomSource;
+
+use actix_web::http::Method;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+use serde::Deserialize;
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+
+/*
+    Custom claims of a token extended by the CSRF token which has to be echoed by the client.
+*/
+#[derive(Serialize)]
+pub(crate) struct CsrfClaims<'a, Claims> {
+    csrf: &'a str,
+    #[serde(flatten)]
+    custom: &'a Claims,
+}
+
+impl<'a, Claims> CsrfClaims<'a, Claims> {
+    pub(crate) fn new(csrf_token: &'a str, custom: &'a Claims) -> Self {
+        Self {
+            csrf: csrf_token,
+            custom,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CsrfClaim {
+    csrf: Option<String>,
+}
+
+/*
+    The CSRF token of the token a request was authenticated with, inserted into the request extensions
+    so tokens renewed for the request keep the CSRF token the client already knows.
+*/
+#[derive(Clone)]
+pub(crate) struct CsrfToken(pub(crate) String);
+
+pub(crate) fn generate_csrf_token(random_source: &dyn RandomSource) -> String {
+    random_token(random_source, 32)
+}
+
+/*
+    Checks that the `csrf_header_name` header of the `req` matches the `csrf` claim of an already validated token
+    if the `req` uses one of the `checked_methods`, and remembers the CSRF token for renewed tokens.
+*/
+pub(crate) fn verify_csrf_token(
+    req: &HttpRequest,
+    token_value: &str,
+    csrf_header_name: &str,
+    checked_methods: &[Method],
+) -> AuthResult<()> {
+    let csrf_claim = deserialize_claims_unchecked::<_, CsrfClaim>(token_value)?
+        .custom
+        .csrf;
+    if checked_methods.contains(req.method()) {
+        let csrf_header = req
+            .headers()
+            .get(csrf_header_name)
+            .map(|csrf_header| csrf_header.as_bytes());
+        match (csrf_claim.as_deref(), csrf_header) {
+            (Some(csrf_claim), Some(csrf_header))
+                if bool::from(csrf_claim.as_bytes().ct_eq(csrf_header)) => {}
+            _ => return Err(AuthError::CsrfMismatch),
+        }
+    }
+    if let Some(csrf_claim) = csrf_claim {
+        req.extensions_mut().insert(CsrfToken(csrf_claim));
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use crate::AuthError;
+use crate::AuthResult;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jwt_compact::Algorithm;
+use jwt_compact::AlgorithmSignature;
+use jwt_compact::Claims as TokenClaims;
+use jwt_compact::ParseError;
+use jwt_compact::ValidationError;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/*
+    The members of the header of a token with a detached payload deciding how its signature is verified.
+*/
+#[derive(Deserialize)]
+struct DetachedHeader {
+    alg: String,
+    #[serde(default = "encodes_payload")]
+    b64: bool,
+    #[serde(default)]
+    crit: Vec<String>,
+    typ: Option<String>,
+}
+
+fn encodes_payload() -> bool {
+    true
+}
+
+/*
+    Verifies the signature of a token with a detached payload, `header..signature`, over the `payload` transmitted out-of-band
+    and returns its claims without checking whether it expired.
+
+    If the header sets `"b64": false`, the `payload` is signed as is following RFC 7797,
+    otherwise it is base64url encoded like the payload of a regular compact JWS (RFC 7515, Appendix F).
+    Following RFC 7515, headers marking any parameter other than `b64` as critical are rejected, since it is not understood.
+*/
+pub(crate) fn verify_detached_jws<Algo, Claims>(
+    detached_token: &str,
+    payload: &[u8],
+    algorithm: &Algo,
+    verifying_key: &Algo::VerifyingKey,
+    expected_token_type: Option<&str>,
+) -> AuthResult<TokenClaims<Claims>>
+where
+    Algo: Algorithm,
+    Claims: DeserializeOwned,
+{
+    let (encoded_header, encoded_signature) = match detached_token.split('.').collect::<Vec<_>>()[..]
+    {
+        [encoded_header, "", encoded_signature] => (encoded_header, encoded_signature),
+        _ => return Err(ParseError::InvalidTokenStructure.into()),
+    };
+    let header = URL_SAFE_NO_PAD
+        .decode(encoded_header)
+        .map_err(|_| ParseError::InvalidBase64Encoding)?;
+    let header: DetachedHeader =
+        serde_json::from_slice(&header).map_err(ParseError::MalformedHeader)?;
+    if header.alg != algorithm.name() {
+        return Err(AuthError::AlgorithmMismatch);
+    }
+    // RFC 7797 requires the b64 header parameter to be understood by every recipient
+    if !header.b64 && !header.crit.iter().any(|parameter| parameter == "b64") {
+        return Err(ParseError::InvalidTokenStructure.into());
+    }
+    if header.crit.iter().any(|parameter| parameter != "b64") {
+        return Err(ParseError::InvalidTokenStructure.into());
+    }
+
+    let mut signing_input = format!("{encoded_header}.").into_bytes();
+    if header.b64 {
+        signing_input.extend_from_slice(URL_SAFE_NO_PAD.encode(payload).as_bytes());
+    } else {
+        signing_input.extend_from_slice(payload);
+    }
+    let signature = URL_SAFE_NO_PAD
+        .decode(encoded_signature)
+        .map_err(|_| ParseError::InvalidBase64Encoding)?;
+    let signature = Algo::Signature::try_from_slice(&signature)
+        .map_err(|_| AuthError::TokenValidation(ValidationError::InvalidSignature, None))?;
+    if !algorithm.verify_signature(&signature, verifying_key, &signing_input) {
+        return Err(AuthError::TokenValidation(
+            ValidationError::InvalidSignature,
+            None,
+        ));
+    }
+
+    if let Some(expected_token_type) = expected_token_type {
+        if header.typ.as_deref() != Some(expected_token_type) {
+            return Err(AuthError::TokenTypeMismatch);
+        }
+    }
+
+    serde_json::from_slice(payload)
+        .map_err(|err| AuthError::ClaimsDeserialization(ValidationError::MalformedClaims(err)))
+}
+
+/*
+    Reattaches the `payload` to a token with a detached payload, which was already verified,
+    so the checks applied to the claims of a regular compact JWS can read them.
+*/
+pub(crate) fn attach_payload(detached_token: &str, payload: &[u8]) -> String {
+    let (encoded_header, encoded_signature) = detached_token
+        .split_once("..")
+        .expect("The detached token was verified to have the form header..signature");
+    format!(
+        "{encoded_header}.{}.{encoded_signature}",
+        URL_SAFE_NO_PAD.encode(payload)
+    )
+}
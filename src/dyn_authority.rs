@@ -0,0 +1,349 @@
+use crate::middleware::authenticate_request;
+use crate::AuthError;
+use crate::AuthResult;
+use crate::Authority;
+use crate::AuthorityConfig;
+use crate::TokenDelivery;
+use crate::TokenUpdate;
+
+use std::future;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::forward_ready;
+use actix_web::dev::Service;
+use actix_web::dev::ServiceRequest;
+use actix_web::dev::ServiceResponse;
+use actix_web::dev::Transform;
+use actix_web::http::header::HeaderValue;
+use actix_web::Error as ActixWebError;
+use actix_web::FromRequest;
+use actix_web::Handler;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use jwt_compact::Algorithm;
+use jwt_compact::Claims as TokenClaims;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub(crate) type VerifyFuture<'a> =
+    Pin<Box<dyn Future<Output = AuthResult<Option<TokenUpdate>>> + 'a>>;
+
+/**
+    The non-secret configuration of the authority wrapped by a [`DynAuthority`], as returned by [`DynAuthority::public_config`].
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub enum DynAuthorityConfig {
+    Authority(AuthorityConfig),
+    #[cfg(feature = "reqwest")]
+    Introspection(crate::IntrospectionConfig),
+}
+
+/*
+    The part of the [`Authority`] used by the middleware and the [`DynAuthority`],
+    independent of the algorithm and the `refresh_authorizer`.
+*/
+pub(crate) trait ErasedAuthority<Claims> {
+    fn verify_service_request<'a>(&'a self, req: &'a mut ServiceRequest) -> VerifyFuture<'a>;
+
+    fn verify_http_request(&self, req: &HttpRequest) -> AuthResult<Claims>;
+
+    fn verify_token_ignoring_expiry(&self, token: &str) -> AuthResult<TokenClaims<Claims>>;
+
+    fn public_config(&self) -> DynAuthorityConfig;
+
+    fn skips_authentication(&self, req: &ServiceRequest) -> bool;
+
+    fn unauthenticated_redirect(
+        &self,
+        req: &ServiceRequest,
+        err: &AuthError,
+    ) -> Option<HttpResponse>;
+
+    fn set_cookie_header_value(&self, cookie: &Cookie<'_>) -> HeaderValue;
+
+    fn token_delivery(&self) -> TokenDelivery;
+
+    fn access_token_header_name(&self) -> &str;
+
+    fn refresh_token_header_name(&self) -> &str;
+
+    fn no_store_token_responses(&self) -> bool;
+
+    #[cfg(feature = "problem-details")]
+    fn problem_details_responses(&self) -> bool;
+}
+
+impl<Claims, Algo, ReAuth, Args> ErasedAuthority<Claims> for Authority<Claims, Algo, ReAuth, Args>
+where
+    Claims: Serialize + DeserializeOwned + 'static,
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+    ReAuth: Handler<Args, Output = Result<(), ActixWebError>>,
+    Args: FromRequest,
+{
+    fn verify_service_request<'a>(&'a self, req: &'a mut ServiceRequest) -> VerifyFuture<'a> {
+        Box::pin(Authority::verify_service_request(self, req))
+    }
+
+    fn verify_http_request(&self, req: &HttpRequest) -> AuthResult<Claims> {
+        Authority::verify_http_request(self, req)
+    }
+
+    fn verify_token_ignoring_expiry(&self, token: &str) -> AuthResult<TokenClaims<Claims>> {
+        Authority::verify_token_ignoring_expiry(self, token)
+    }
+
+    fn public_config(&self) -> DynAuthorityConfig {
+        DynAuthorityConfig::Authority(Authority::public_config(self))
+    }
+
+    fn skips_authentication(&self, req: &ServiceRequest) -> bool {
+        Authority::skips_authentication(self, req)
+    }
+
+    fn unauthenticated_redirect(
+        &self,
+        req: &ServiceRequest,
+        err: &AuthError,
+    ) -> Option<HttpResponse> {
+        Authority::unauthenticated_redirect(self, req, err)
+    }
+
+    fn set_cookie_header_value(&self, cookie: &Cookie<'_>) -> HeaderValue {
+        Authority::set_cookie_header_value(self, cookie)
+    }
+
+    fn token_delivery(&self) -> TokenDelivery {
+        self.token_delivery
+    }
+
+    fn access_token_header_name(&self) -> &str {
+        Authority::access_token_header_name(self)
+    }
+
+    fn refresh_token_header_name(&self) -> &str {
+        Authority::refresh_token_header_name(self)
+    }
+
+    fn no_store_token_responses(&self) -> bool {
+        self.no_store_token_responses
+    }
+
+    #[cfg(feature = "problem-details")]
+    fn problem_details_responses(&self) -> bool {
+        self.problem_details_responses
+    }
+}
+
+/**
+    A type-erased [`Authority`], which only depends on the `Claims` type.
+
+    This allows choosing the algorithm at runtime, for example from a configuration file,
+    or storing authorities for different algorithms in the same collection.
+    Like the [`crate::AuthenticationService`], it can be passed to the `wrap` function of a [`App`](actix_web::App)/[`Scope`](actix_web::Scope).
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{Authority, DynAuthority, TokenSigner};
+    # use actix_web::{web, App};
+    # use ed25519_compact::KeyPair;
+    # use jwt_compact::alg::{Ed25519, Hs256, Hs256Key};
+    # use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Clone)]
+    struct User {
+        id: u32,
+    }
+
+    fn authority(algorithm: &str) -> DynAuthority<User> {
+        match algorithm {
+            "HS256" => {
+                let key = Hs256Key::new(b"a-secret-that-is-long-enough-for-hs256");
+                DynAuthority::new(
+                    Authority::<User, _, _, _>::new()
+                        .refresh_authorizer(|| async move { Ok(()) })
+                        .token_signer(Some(
+                            TokenSigner::new()
+                                .signing_key(key.clone())
+                                .algorithm(Hs256)
+                                .build()
+                                .unwrap(),
+                        ))
+                        .verifying_key(key)
+                        .build()
+                        .unwrap(),
+                )
+            }
+            _ => {
+                let KeyPair { pk, sk } = KeyPair::generate();
+                DynAuthority::new(
+                    Authority::<User, _, _, _>::new()
+                        .refresh_authorizer(|| async move { Ok(()) })
+                        .token_signer(Some(
+                            TokenSigner::new()
+                                .signing_key(sk)
+                                .algorithm(Ed25519)
+                                .build()
+                                .unwrap(),
+                        ))
+                        .verifying_key(pk)
+                        .build()
+                        .unwrap(),
+                )
+            }
+        }
+    }
+
+    let app = App::new().service(web::scope("/auth-only").wrap(authority("HS256")));
+    ```
+*/
+pub struct DynAuthority<Claims> {
+    inner: Arc<dyn ErasedAuthority<Claims> + Send + Sync>,
+}
+
+impl<Claims> DynAuthority<Claims>
+where
+    Claims: Serialize + DeserializeOwned + 'static,
+{
+    /**
+        Returns a new [`DynAuthority`] erasing the algorithm and `refresh_authorizer` of the `authority`.
+    */
+    pub fn new<Algo, ReAuth, Args>(authority: Authority<Claims, Algo, ReAuth, Args>) -> Self
+    where
+        Algo: Algorithm + Clone + 'static,
+        Algo::SigningKey: Clone,
+        ReAuth: Handler<Args, Output = Result<(), ActixWebError>> + 'static,
+        Args: FromRequest + 'static,
+        Authority<Claims, Algo, ReAuth, Args>: Send + Sync,
+    {
+        Self::from_shared(Arc::new(authority))
+    }
+
+    /*
+        Returns a new [`DynAuthority`] sharing the `authority` with an [`crate::AuthenticationService`].
+    */
+    pub(crate) fn from_shared<Algo, ReAuth, Args>(
+        authority: Arc<Authority<Claims, Algo, ReAuth, Args>>,
+    ) -> Self
+    where
+        Algo: Algorithm + Clone + 'static,
+        Algo::SigningKey: Clone,
+        ReAuth: Handler<Args, Output = Result<(), ActixWebError>> + 'static,
+        Args: FromRequest + 'static,
+        Authority<Claims, Algo, ReAuth, Args>: Send + Sync,
+    {
+        Self { inner: authority }
+    }
+
+    /**
+        Verifies the `req` like the middleware does, please refer to [`Authority::verify_service_request`].
+    */
+    pub async fn verify_service_request(
+        &self,
+        req: &mut ServiceRequest,
+    ) -> AuthResult<Option<TokenUpdate>> {
+        self.inner.verify_service_request(req).await
+    }
+
+    /**
+        Verifies the access token of a plain [`HttpRequest`], please refer to [`Authority::verify_http_request`].
+
+        An `IntrospectionAuthority` can not call its introspection endpoint here,
+        so it only accepts tokens whose cached introspection result did not expire yet.
+    */
+    pub fn verify_http_request(&self, req: &HttpRequest) -> AuthResult<Claims> {
+        self.inner.verify_http_request(req)
+    }
+
+    /**
+        Validates the signature of the `token` without checking whether it expired,
+        please refer to [`Authority::verify_token_ignoring_expiry`].
+
+        An `IntrospectionAuthority` always returns [`AuthError::Unsupported`],
+        as its introspection endpoint only reports active tokens.
+    */
+    pub fn verify_token_ignoring_expiry(&self, token: &str) -> AuthResult<TokenClaims<Claims>> {
+        self.inner.verify_token_ignoring_expiry(token)
+    }
+
+    /**
+        Returns the configuration of the wrapped authority without any keys or other secrets.
+    */
+    pub fn public_config(&self) -> DynAuthorityConfig {
+        self.inner.public_config()
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl<Claims> From<crate::IntrospectionAuthority<Claims>> for DynAuthority<Claims>
+where
+    Claims: DeserializeOwned + Send + Sync + 'static,
+{
+    /**
+        Wraps the `introspection_authority`, so it can be used as middleware like an [`Authority`].
+    */
+    fn from(introspection_authority: crate::IntrospectionAuthority<Claims>) -> Self {
+        Self {
+            inner: Arc::new(introspection_authority),
+        }
+    }
+}
+
+impl<Claims> Clone for DynAuthority<Claims> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S, Body, Claims> Transform<S, ServiceRequest> for DynAuthority<Claims>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<Body>, Error = ActixWebError> + 'static,
+    Claims: 'static,
+    Body: MessageBody,
+{
+    type Response = ServiceResponse<Body>;
+    type Error = ActixWebError;
+    type Transform = DynAuthenticationServiceInner<S, Claims>;
+    type InitError = ();
+    type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ready(Ok(DynAuthenticationServiceInner {
+            service: Rc::new(service),
+            inner: Arc::clone(&self.inner),
+        }))
+    }
+}
+
+#[doc(hidden)]
+pub struct DynAuthenticationServiceInner<S, Claims> {
+    service: Rc<S>,
+    inner: Arc<dyn ErasedAuthority<Claims> + Send + Sync>,
+}
+
+impl<S, Body, Claims> Service<ServiceRequest> for DynAuthenticationServiceInner<S, Claims>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<Body>, Error = ActixWebError> + 'static,
+    Claims: 'static,
+    Body: MessageBody,
+{
+    type Response = ServiceResponse<Body>;
+    type Error = ActixWebError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let inner = Arc::clone(&self.inner);
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move { authenticate_request(&*inner, service, req).await })
+    }
+}
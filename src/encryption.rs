@@ -0,0 +1,95 @@
+use std::fmt;
+
+#[cfg(feature = "jwe")]
+use crate::AuthError;
+#[cfg(feature = "jwe")]
+use crate::AuthResult;
+
+#[cfg(feature = "jwe")]
+use aes_gcm::aead::Aead;
+#[cfg(feature = "jwe")]
+use aes_gcm::aead::AeadCore;
+#[cfg(feature = "jwe")]
+use aes_gcm::aead::KeyInit;
+#[cfg(feature = "jwe")]
+use aes_gcm::aead::OsRng;
+#[cfg(feature = "jwe")]
+use aes_gcm::Aes256Gcm;
+#[cfg(feature = "jwe")]
+use aes_gcm::Nonce;
+#[cfg(feature = "jwe")]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+#[cfg(feature = "jwe")]
+use base64::Engine;
+#[cfg(feature = "jwe")]
+use jwt_compact::ParseError;
+
+#[cfg(feature = "jwe")]
+const NONCE_LENGTH: usize = 12;
+
+/**
+    Symmetric key used to additionally encrypt signed tokens before they are placed in a cookie.
+
+    Tokens are encrypted with `AES-256-GCM`, this keeps confidential claims unreadable for the client
+    while their integrity is still ensured by the signature of the token.
+    The same key has to be set on the [`crate::TokenSigner`] and the [`crate::Authority`].
+
+    Constructing this key is only possible with the `jwe` feature enabled.
+*/
+#[derive(Clone)]
+pub struct TokenEncryptionKey {
+    #[cfg(feature = "jwe")]
+    cipher: Aes256Gcm,
+}
+
+#[cfg(feature = "jwe")]
+impl TokenEncryptionKey {
+    /**
+        Returns a new [`TokenEncryptionKey`] from 32 bytes of key material.
+    */
+    pub fn new(key: [u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new_from_slice(&key)
+                .expect("AES-256 keys have a length of 32 bytes"),
+        }
+    }
+
+    /*
+        Encrypts the token and returns the base64url encoded nonce followed by the ciphertext.
+    */
+    pub(crate) fn encrypt(&self, token: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, token.as_bytes())
+            .expect("Tokens should not exceed the maximum plaintext length of AES-GCM");
+
+        let mut encrypted_token = nonce.to_vec();
+        encrypted_token.extend_from_slice(&ciphertext);
+        URL_SAFE_NO_PAD.encode(encrypted_token)
+    }
+
+    pub(crate) fn decrypt(&self, encrypted_token: &str) -> AuthResult<String> {
+        let invalid_token = || AuthError::TokenParse(ParseError::InvalidTokenStructure);
+
+        let encrypted_token = URL_SAFE_NO_PAD
+            .decode(encrypted_token)
+            .map_err(|_| invalid_token())?;
+        if encrypted_token.len() < NONCE_LENGTH {
+            return Err(invalid_token());
+        }
+        let (nonce, ciphertext) = encrypted_token.split_at(NONCE_LENGTH);
+
+        let token = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid_token())?;
+        String::from_utf8(token).map_err(|_| invalid_token())
+    }
+}
+
+impl fmt::Debug for TokenEncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TokenEncryptionKey(<redacted>)")
+    }
+}
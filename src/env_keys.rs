@@ -0,0 +1,149 @@
+use crate::AuthError;
+use crate::AuthResult;
+use crate::AuthorityBuilder;
+use crate::TokenSignerBuilder;
+
+use std::env;
+
+use base64::alphabet;
+use base64::engine::DecodePaddingMode;
+use base64::engine::GeneralPurpose;
+use base64::engine::GeneralPurposeConfig;
+use base64::Engine;
+use jwt_compact::alg::SigningKey;
+use jwt_compact::alg::VerifyingKey;
+use jwt_compact::Algorithm;
+
+/*
+    Base64 engines accepting keys with as well as without padding.
+*/
+const BASE64_STANDARD: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+const BASE64_URL_SAFE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/**
+    The encoding of a key stored in an environment variable.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEncoding {
+    /**
+        The standard or URL safe base64 alphabet, with or without padding.
+    */
+    Base64,
+    /**
+        Hexadecimal digits, in lower or upper case.
+    */
+    Hex,
+}
+
+impl KeyEncoding {
+    fn decode(self, encoded_key: &str) -> Option<Vec<u8>> {
+        match self {
+            Self::Base64 => BASE64_STANDARD
+                .decode(encoded_key)
+                .or_else(|_| BASE64_URL_SAFE.decode(encoded_key))
+                .ok(),
+            Self::Hex => decode_hex(encoded_key),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Base64 => "base64",
+            Self::Hex => "hex",
+        }
+    }
+}
+
+fn decode_hex(encoded_key: &str) -> Option<Vec<u8>> {
+    if encoded_key.len() % 2 != 0 || !encoded_key.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..encoded_key.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&encoded_key[index..index + 2], 16).ok())
+        .collect()
+}
+
+/*
+    Reads and decodes the raw bytes of a key from the environment variable called `name`.
+    The errors never contain the value of the variable, so they can be logged safely.
+*/
+fn key_from_env(name: &str, encoding: KeyEncoding) -> AuthResult<Vec<u8>> {
+    let encoded_key = env::var(name).map_err(|err| {
+        AuthError::KeyLoad(format!(
+            "the environment variable {name} could not be read: {err}"
+        ))
+    })?;
+    encoding.decode(encoded_key.trim()).ok_or_else(|| {
+        AuthError::KeyLoad(format!(
+            "the environment variable {name} does not contain a {} encoded key",
+            encoding.as_str()
+        ))
+    })
+}
+
+impl<Claims, Algo> TokenSignerBuilder<Claims, Algo>
+where
+    Algo: Algorithm,
+    Algo::SigningKey: SigningKey<Algo>,
+{
+    /**
+        Sets the `signing_key` to the key stored in the environment variable called `name`.
+
+        The key is decoded from the `encoding` and then parsed from its raw bytes by the algorithm,
+        for example `Ed25519` expects the 64 bytes of the secret key and `HS256` accepts a secret of any length.
+        Returns [`AuthError::KeyLoad`] if the variable is not set, not properly encoded or does not contain a valid key.
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::{AuthError, KeyEncoding, TokenSigner};
+        # use jwt_compact::alg::Hs256;
+        # use serde::Serialize;
+        #[derive(Serialize)]
+        struct User {
+            id: u32,
+        }
+        # std::env::set_var("JWT_SIGNING_KEY", "c2VjcmV0LWtleS1vZi10aGUtYXV0aC1zZXJ2aWNl");
+
+        let token_signer = TokenSigner::<User, _>::new()
+            .signing_key_from_env("JWT_SIGNING_KEY", KeyEncoding::Base64)?
+            .algorithm(Hs256)
+            .build()
+            .unwrap();
+        # Ok::<(), AuthError>(())
+        ```
+    */
+    pub fn signing_key_from_env(self, name: &str, encoding: KeyEncoding) -> AuthResult<Self> {
+        let signing_key = Algo::SigningKey::from_slice(&key_from_env(name, encoding)?)
+            .map_err(|err| AuthError::KeyLoad(format!("{name}: {err}")))?;
+        Ok(self.signing_key(signing_key))
+    }
+}
+
+impl<Claims, Algo, ReAuth, Args> AuthorityBuilder<Claims, Algo, ReAuth, Args>
+where
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+    Algo::VerifyingKey: VerifyingKey<Algo>,
+{
+    /**
+        Sets the `verifying_key` to the key stored in the environment variable called `name`.
+
+        The key is decoded from the `encoding` and then parsed from its raw bytes by the algorithm,
+        for example `Ed25519` expects the 32 bytes of the public key.
+        Returns [`AuthError::KeyLoad`] if the variable is not set, not properly encoded or does not contain a valid key.
+
+        Please refer to [`TokenSignerBuilder::signing_key_from_env`] for loading the matching signing key.
+    */
+    pub fn verifying_key_from_env(self, name: &str, encoding: KeyEncoding) -> AuthResult<Self> {
+        let verifying_key = Algo::VerifyingKey::from_slice(&key_from_env(name, encoding)?)
+            .map_err(|err| AuthError::KeyLoad(format!("{name}: {err}")))?;
+        Ok(self.verifying_key(verifying_key))
+    }
+}
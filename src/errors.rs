@@ -1,8 +1,12 @@
+use crate::TokenSource;
+
 use actix_web::body::BoxBody;
 use actix_web::http::StatusCode;
 use actix_web::Error as ActixWebError;
 use actix_web::HttpResponse;
 use actix_web::ResponseError;
+use chrono::DateTime;
+use chrono::Utc;
 use jwt_compact::CreationError;
 use jwt_compact::ParseError;
 use jwt_compact::ValidationError;
@@ -13,32 +17,158 @@ pub type AuthResult<T> = Result<T, AuthError>;
     Crate wide error type
 
     if `#[cfg(debug_assertions)]` is true
-    the wrapped errors in (Internal, RefreshAuthorizerDenied, TokenCreation, TokenParse, TokenValidation)
-    are in included in the error message.
+    the wrapped errors in (Internal, RefreshAuthorizerDenied, TokenCreation, TokenParse, TokenValidation, ClaimsDeserialization)
+    are in included in the error message, as are the [`TokenTiming`] of tokens rejected because they expired
+    and the [`TokenSource`]s checked for a token before a request was rejected with [`AuthError::NoToken`].
 */
 #[derive(Debug)]
 pub enum AuthError {
-    NoToken,
+    AlgorithmMismatch,
+    BodyRead(ActixWebError),
+    ClaimsDeserialization(ValidationError),
+    ClaimsEnricher(ActixWebError),
+    ClaimsSchemaViolation(String),
+    ClientNotAllowed,
+    CsrfMismatch,
+    FingerprintMismatch,
+    InactiveToken,
+    InsecureTransport,
+    Introspection(String),
+    KeyLoad(String),
+    MissingClaim(&'static str),
+    NoToken(Vec<TokenSource>),
     NoTokenSigner,
     RefreshAuthorizerCall(ActixWebError),
     RefreshAuthorizerDenied(ActixWebError),
+    RefreshTokenExpired,
+    ReauthRequired,
+    SessionExpired,
     TokenCreation(CreationError),
     TokenParse(ParseError),
-    TokenValidation(ValidationError),
+    TokenSuperseded,
+    TokenTypeMismatch,
+    TokenValidation(ValidationError, Option<TokenTiming>),
+    UnknownKeyId,
+    Unsupported(&'static str),
 }
 
 impl PartialEq for AuthError {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::TokenCreation(_), Self::TokenCreation(_))
-            | (Self::TokenValidation(_), Self::TokenValidation(_))
+            | (Self::TokenValidation(..), Self::TokenValidation(..))
+            | (Self::ClaimsDeserialization(_), Self::ClaimsDeserialization(_))
             | (Self::TokenParse(_), Self::TokenParse(_))
-            | (Self::RefreshAuthorizerCall(_), Self::RefreshAuthorizerCall(_)) => true,
+            | (Self::RefreshAuthorizerCall(_), Self::RefreshAuthorizerCall(_))
+            | (Self::ClaimsEnricher(_), Self::ClaimsEnricher(_))
+            | (Self::BodyRead(_), Self::BodyRead(_)) => true,
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
 }
 
+/**
+    The timing claims of a token rejected by [`AuthError::TokenValidation`] because it expired,
+    next to the current time of the server according to the `time_options`.
+
+    Comparing them tells a drifting server clock apart from an actually expired token,
+    which is why debug builds include them in the error message.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenTiming {
+    /**
+        The `exp` claim of the token.
+    */
+    pub expiration: Option<DateTime<Utc>>,
+    /**
+        The `nbf` claim of the token.
+    */
+    pub not_before: Option<DateTime<Utc>>,
+    /**
+        The current time of the server the token was validated at.
+    */
+    pub now: DateTime<Utc>,
+}
+
+impl std::fmt::Display for TokenTiming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format_claim = |claim: Option<DateTime<Utc>>| {
+            claim.map_or_else(|| "unset".to_string(), |claim| claim.to_rfc3339())
+        };
+        f.write_fmt(format_args!(
+            "exp {}, nbf {}, server time {}",
+            format_claim(self.expiration),
+            format_claim(self.not_before),
+            self.now.to_rfc3339()
+        ))
+    }
+}
+
+#[cfg(debug_assertions)]
+fn token_source_name(source: &TokenSource) -> &'static str {
+    match source {
+        TokenSource::Query => "query",
+        TokenSource::Header => "header",
+        TokenSource::AuthorizationHeader => "authorization header",
+        TokenSource::Cookie => "cookie",
+        TokenSource::Body => "body",
+    }
+}
+
+impl AuthError {
+    /**
+        Returns a [`Clone`]-able snapshot of this error, holding its status code and message.
+
+        The wrapped errors of jwt-compact and actix-web can not be cloned, so the snapshot only keeps their message.
+        Its response carries the same status code and message as the response of this error,
+        but not the headers of responses built by wrapped actix-web errors, like the `Location` header of a redirect.
+    */
+    pub fn to_static(&self) -> AuthErrorSnapshot {
+        AuthErrorSnapshot {
+            status_code: self.status_code(),
+            message: self.to_string(),
+        }
+    }
+}
+
+/**
+    A [`Clone`]-able snapshot of an [`AuthError`], created by [`AuthError::to_static`].
+
+    This allows storing an error in shared state or returning it from multiple branches.
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthErrorSnapshot {
+    status_code: StatusCode,
+    message: String,
+}
+
+impl AuthErrorSnapshot {
+    /**
+        Returns the message of the original error.
+    */
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for AuthErrorSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ResponseError for AuthErrorSnapshot {
+    fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+}
+
+impl From<&AuthError> for AuthErrorSnapshot {
+    fn from(val: &AuthError) -> Self {
+        val.to_static()
+    }
+}
+
 impl From<CreationError> for AuthError {
     fn from(val: CreationError) -> Self {
         AuthError::TokenCreation(val)
@@ -53,45 +183,134 @@ impl From<ParseError> for AuthError {
 
 impl From<ValidationError> for AuthError {
     fn from(val: ValidationError) -> Self {
-        AuthError::TokenValidation(val)
+        match val {
+            ValidationError::AlgorithmMismatch { .. } => AuthError::AlgorithmMismatch,
+            val => AuthError::TokenValidation(val, None),
+        }
     }
 }
 
 impl std::fmt::Display for AuthError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         const NO_TOKEN_MESSAGE: &str = "An error occurred, no cookie containing a jwt was found in the request. Please first authenticate with this application.";
+        const REFRESH_TOKEN_EXPIRED_MESSAGE: &str =
+            "An error occurred, the refresh token expired. Please authenticate with this application again.";
 
         #[cfg(not(debug_assertions))]
         match self {
-            AuthError::NoToken => f.write_str(NO_TOKEN_MESSAGE),
-            AuthError::RefreshAuthorizerDenied(err) => f.write_str(&err.to_string()),
-            AuthError::TokenParse(_) | AuthError::TokenValidation(_) => {
+            AuthError::NoToken(_) => f.write_str(NO_TOKEN_MESSAGE),
+            AuthError::RefreshTokenExpired => f.write_str(REFRESH_TOKEN_EXPIRED_MESSAGE),
+            AuthError::RefreshAuthorizerDenied(err)
+            | AuthError::ClaimsEnricher(err)
+            | AuthError::BodyRead(err) => f.write_str(&err.to_string()),
+            AuthError::AlgorithmMismatch
+            | AuthError::ClaimsDeserialization(_)
+            | AuthError::ClaimsSchemaViolation(_)
+            | AuthError::ClientNotAllowed
+            | AuthError::CsrfMismatch
+            | AuthError::FingerprintMismatch
+            | AuthError::InactiveToken
+            | AuthError::InsecureTransport
+            | AuthError::MissingClaim(_)
+            | AuthError::ReauthRequired
+            | AuthError::SessionExpired
+            | AuthError::TokenParse(_)
+            | AuthError::TokenSuperseded
+            | AuthError::TokenTypeMismatch
+            | AuthError::TokenValidation(..)
+            | AuthError::UnknownKeyId => {
                 f.write_str("An error occurred, the provided jwt could not be processed.")
             }
             AuthError::RefreshAuthorizerCall(_)
+            | AuthError::Introspection(_)
+            | AuthError::KeyLoad(_)
             | AuthError::NoTokenSigner
-            | AuthError::TokenCreation(_) => {
+            | AuthError::TokenCreation(_)
+            | AuthError::Unsupported(_) => {
                 f.write_str("An internal error occurred. Please try again later.")
             }
         }
         #[cfg(debug_assertions)]
         match self {
-            AuthError::NoToken => f.write_str(NO_TOKEN_MESSAGE),
+            AuthError::NoToken(checked_sources) => f.write_fmt(format_args!(
+                "{NO_TOKEN_MESSAGE}\n\t Checked: {}",
+                checked_sources
+                    .iter()
+                    .map(|source| format!("{}(absent)", token_source_name(source)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            AuthError::RefreshTokenExpired => f.write_str(REFRESH_TOKEN_EXPIRED_MESSAGE),
             AuthError::NoTokenSigner => f.write_str(
                 "An error occurred because no CookieSigner was configured on the Authority struct.",
             ),
             AuthError::TokenCreation(err) => f.write_fmt(format_args!(
                 "An error occurred creating the jwt.\n\t Error: \"{err}\""
             )),
-            AuthError::TokenValidation(err) => f.write_fmt(format_args!(
+            AuthError::KeyLoad(err) => f.write_fmt(format_args!(
+                "An error occurred loading a key.\n\t Error: \"{err}\""
+            )),
+            AuthError::TokenValidation(err, None) => f.write_fmt(format_args!(
                 "An error occurred validating the jwt.\n\t Error: \"{err}\""
             )),
+            AuthError::TokenValidation(err, Some(timing)) => f.write_fmt(format_args!(
+                "An error occurred validating the jwt.\n\t Error: \"{err}\"\n\t Timing: {timing}"
+            )),
+            AuthError::Introspection(err) => f.write_fmt(format_args!(
+                "An error occurred introspecting the token.\n\t Error: \"{err}\""
+            )),
+            AuthError::InactiveToken => f.write_str(
+                "An error occurred, the introspection endpoint reported the token as inactive.",
+            ),
+            AuthError::ClaimsDeserialization(err) => f.write_fmt(format_args!(
+                "An error occurred deserializing the claims of the jwt, its signature is valid but the claims do not match the expected schema.\n\t Error: \"{err}\""
+            )),
             AuthError::TokenParse(err) => f.write_fmt(format_args!(
                 "An error occurred parsing the jwt.\n\t Error: \"{err}\""
             )),
-            AuthError::RefreshAuthorizerDenied(err) | AuthError::RefreshAuthorizerCall(err) => {
-                f.write_str(&err.to_string())
+            AuthError::AlgorithmMismatch => f.write_str(
+                "An error occurred, the alg header of the jwt does not name the configured algorithm.",
+            ),
+            AuthError::TokenSuperseded => f.write_str(
+                "An error occurred, the jwt was superseded by a newer token version of its subject.",
+            ),
+            AuthError::TokenTypeMismatch => {
+                f.write_str("An error occurred, the typ header of the jwt did not match.")
             }
+            AuthError::FingerprintMismatch => f.write_str(
+                "An error occurred, the jwt was issued to a different device fingerprint.",
+            ),
+            AuthError::InsecureTransport => f.write_str(
+                "An error occurred, tokens passed as query parameters are only accepted over https.",
+            ),
+            AuthError::ClientNotAllowed => f.write_str(
+                "An error occurred, the jwt was issued to a client which is not allowed.",
+            ),
+            AuthError::CsrfMismatch => f.write_str(
+                "An error occurred, the csrf token of the request does not match the one of the jwt.",
+            ),
+            AuthError::ClaimsSchemaViolation(violation) => f.write_fmt(format_args!(
+                "An error occurred, the claims of the jwt do not match the schema: {violation}"
+            )),
+            AuthError::MissingClaim(claim) => f.write_fmt(format_args!(
+                "An error occurred, the jwt is missing the required \"{claim}\" claim."
+            )),
+            AuthError::SessionExpired => f.write_str(
+                "An error occurred, the jwt was issued longer ago than the maximum token age allows.",
+            ),
+            AuthError::ReauthRequired => f.write_str(
+                "An error occurred, the subject of the jwt authenticated longer ago than the maximum authentication age allows.",
+            ),
+            AuthError::UnknownKeyId => f.write_str(
+                "An error occurred, the jwt was signed with a key whose kid is unknown.",
+            ),
+            AuthError::Unsupported(operation) => f.write_fmt(format_args!(
+                "An error occurred, the authority does not support {operation}."
+            )),
+            AuthError::RefreshAuthorizerDenied(err)
+            | AuthError::RefreshAuthorizerCall(err)
+            | AuthError::ClaimsEnricher(err)
+            | AuthError::BodyRead(err) => f.write_str(&err.to_string()),
         }
     }
 }
@@ -99,22 +318,76 @@ impl std::fmt::Display for AuthError {
 impl ResponseError for AuthError {
     fn status_code(&self) -> StatusCode {
         match self {
-            AuthError::TokenCreation(_) | AuthError::NoTokenSigner => {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-            AuthError::TokenParse(_) => StatusCode::BAD_REQUEST,
-            AuthError::NoToken | AuthError::TokenValidation(_) => StatusCode::UNAUTHORIZED,
-            AuthError::RefreshAuthorizerCall(err) | AuthError::RefreshAuthorizerDenied(err) => {
-                err.as_response_error().status_code()
-            }
+            AuthError::TokenCreation(_)
+            | AuthError::Introspection(_)
+            | AuthError::KeyLoad(_)
+            | AuthError::NoTokenSigner
+            | AuthError::Unsupported(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AuthError::TokenParse(_) | AuthError::InsecureTransport => StatusCode::BAD_REQUEST,
+            AuthError::ClientNotAllowed | AuthError::CsrfMismatch => StatusCode::FORBIDDEN,
+            AuthError::AlgorithmMismatch
+            | AuthError::ClaimsDeserialization(_)
+            | AuthError::ClaimsSchemaViolation(_)
+            | AuthError::FingerprintMismatch
+            | AuthError::InactiveToken
+            | AuthError::MissingClaim(_)
+            | AuthError::NoToken(_)
+            | AuthError::RefreshTokenExpired
+            | AuthError::ReauthRequired
+            | AuthError::SessionExpired
+            | AuthError::TokenSuperseded
+            | AuthError::TokenTypeMismatch
+            | AuthError::TokenValidation(..)
+            | AuthError::UnknownKeyId => StatusCode::UNAUTHORIZED,
+            AuthError::RefreshAuthorizerCall(err)
+            | AuthError::RefreshAuthorizerDenied(err)
+            | AuthError::ClaimsEnricher(err)
+            | AuthError::BodyRead(err) => err.as_response_error().status_code(),
         }
     }
     fn error_response(&self) -> HttpResponse<BoxBody> {
         match self {
-            AuthError::RefreshAuthorizerDenied(err) | AuthError::RefreshAuthorizerCall(err) => {
-                err.error_response()
-            }
+            AuthError::RefreshAuthorizerDenied(err)
+            | AuthError::RefreshAuthorizerCall(err)
+            | AuthError::ClaimsEnricher(err)
+            | AuthError::BodyRead(err) => err.error_response(),
             _ => HttpResponse::build(self.status_code()).body(self.to_string()),
         }
     }
 }
+
+#[cfg(feature = "problem-details")]
+impl AuthError {
+    /**
+        Returns this error as a [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) problem details response,
+        a JSON object with the fields `type`, `title`, `status` and `detail` served as `application/problem+json`.
+
+        The `detail` is the message of this error, so it is as verbose as the regular error response.
+    */
+    pub fn to_problem_details(&self) -> HttpResponse<BoxBody> {
+        let status = self.status_code();
+        HttpResponse::build(status)
+            .content_type("application/problem+json")
+            .body(
+                serde_json::json!({
+                    "type": "about:blank",
+                    "title": status.canonical_reason().unwrap_or_default(),
+                    "status": status.as_u16(),
+                    "detail": self.to_string(),
+                })
+                .to_string(),
+            )
+    }
+
+    /*
+        Returns whether this error wraps an error returned by user code, whose response is kept as is.
+    */
+    pub(crate) fn wraps_actix_error(&self) -> bool {
+        matches!(
+            self,
+            AuthError::ClaimsEnricher(_)
+                | AuthError::RefreshAuthorizerCall(_)
+                | AuthError::RefreshAuthorizerDenied(_)
+        )
+    }
+}
@@ -0,0 +1,531 @@
+use crate::validate::deserialize_claims_unchecked;
+use crate::AuthResult;
+
+use std::future::ready;
+use std::future::Ready;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use actix_web::dev::Extensions;
+use actix_web::dev::Payload;
+use actix_web::error::ErrorBadRequest;
+use actix_web::Error as ActixWebError;
+use actix_web::FromRequest;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+use chrono::DateTime;
+use chrono::Utc;
+use jwt_compact::Claims as TokenClaims;
+use serde::Serialize;
+use serde_json::Value;
+
+/**
+    Returns a clone of the claims of type `Claims` inserted into the extensions of the `req` by the [`crate::Authority`].
+
+    This is the lookup performed by types deriving [`crate::FromRequest`],
+    usable where only a [`HttpRequest`] reference is available, like inside of a custom guard or error handler.
+    If the [`crate::Authority`] shares the claims, they are cloned out of the inserted `Arc<Claims>`.
+    Returns `None` if the request was not authenticated.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::extract_claims;
+    # use actix_web::HttpRequest;
+    #[derive(Clone)]
+    struct User {
+        id: u32,
+    }
+
+    fn is_admin(req: &HttpRequest) -> bool {
+        extract_claims::<User>(req).map_or(false, |user| user.id == 0)
+    }
+    ```
+*/
+pub fn extract_claims<Claims>(req: &HttpRequest) -> Option<Claims>
+where
+    Claims: Clone + 'static,
+{
+    get_claims(&req.extensions())
+}
+
+/**
+    Passes a reference to the claims of type `Claims` inserted by the [`crate::Authority`] to the `map` function,
+    returning its result.
+
+    This is the lookup performed by types deriving [`crate::FromRequest`] with the `#[from_request(map = "...")]` attribute.
+    Unlike [`extract_claims`], only the part of the claims returned by `map` has to be cloned.
+    Returns `None` if the request was not authenticated.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::map_claims;
+    # use actix_web::HttpRequest;
+    struct User {
+        id: u32,
+        scopes: Vec<String>,
+    }
+
+    fn user_id(req: &HttpRequest) -> Option<u32> {
+        map_claims(req, |user: &User| user.id)
+    }
+    ```
+*/
+pub fn map_claims<Claims, T, F>(req: &HttpRequest, map: F) -> Option<T>
+where
+    Claims: 'static,
+    F: FnOnce(&Claims) -> T,
+{
+    let extensions = req.extensions();
+    extensions
+        .get::<Claims>()
+        .or_else(|| extensions.get::<Arc<Claims>>().map(|claims| &**claims))
+        .map(map)
+}
+
+/*
+    Looks up the `Claims` in the `extensions`, whether they were inserted plainly or as `Arc<Claims>`.
+*/
+fn get_claims<Claims>(extensions: &Extensions) -> Option<Claims>
+where
+    Claims: Clone + 'static,
+{
+    extensions.get::<Claims>().cloned().or_else(|| {
+        extensions
+            .get::<Arc<Claims>>()
+            .map(|claims| Claims::clone(claims))
+    })
+}
+
+/**
+    Reference counted claims inserted by an [`crate::Authority`] with `share_claims` set to true.
+
+    Extracting this type only clones the `Arc`, not the claims themselves,
+    which is cheaper for large claims if the handler only reads them.
+    Extraction fails if the [`crate::Authority`] does not share its claims.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::SharedClaims;
+    # use actix_web::{get, Responder};
+    # use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize)]
+    struct User {
+        id: u32,
+        permissions: Vec<String>,
+    }
+
+    #[get("/permissions")]
+    async fn permissions(user: SharedClaims<User>) -> impl Responder {
+        user.permissions.join(", ")
+    }
+    ```
+*/
+#[derive(Debug)]
+pub struct SharedClaims<Claims>(pub Arc<Claims>);
+
+impl<Claims> SharedClaims<Claims> {
+    /**
+        Consumes the wrapper and returns the shared claims.
+    */
+    pub fn into_inner(self) -> Arc<Claims> {
+        self.0
+    }
+}
+
+impl<Claims> Deref for SharedClaims<Claims> {
+    type Target = Claims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<Claims> Clone for SharedClaims<Claims> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<Claims> FromRequest for SharedClaims<Claims>
+where
+    Claims: 'static,
+{
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Arc<Claims>>()
+                .map(|claims| Self(Arc::clone(claims)))
+                .ok_or_else(|| {
+                    ErrorBadRequest("could not extract shared claims from HttpRequest extensions")
+                }),
+        )
+    }
+}
+
+/**
+    Wrapper around the claims inserted by an [`crate::Authority`] which was configured with a `claims_marker`.
+
+    Request extensions are keyed by type, so two nested authorities using the same `Claims` type
+    would overwrite each others claims. By giving every authority its own `Marker` type,
+    the claims of each authority can be extracted separately.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::ScopedClaims;
+    # use actix_web::{get, Responder};
+    # use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Clone)]
+    struct User {
+        id: u32,
+    }
+
+    // passed to `AuthorityBuilder::claims_marker` of the outer and inner authority
+    struct Outer;
+    struct Inner;
+
+    #[get("/hello")]
+    async fn hello(
+        outer: ScopedClaims<User, Outer>,
+        inner: ScopedClaims<User, Inner>,
+    ) -> impl Responder {
+        format!("outer id: {}, inner id: {}", outer.id, inner.id)
+    }
+    ```
+*/
+pub struct ScopedClaims<Claims, Marker> {
+    claims: Claims,
+    marker: PhantomData<Marker>,
+}
+
+impl<Claims, Marker> ScopedClaims<Claims, Marker> {
+    /**
+        Returns a new [`ScopedClaims`] wrapping the `claims`.
+    */
+    pub fn new(claims: Claims) -> Self {
+        Self {
+            claims,
+            marker: PhantomData,
+        }
+    }
+
+    /**
+        Consumes the wrapper and returns the wrapped claims.
+    */
+    pub fn into_inner(self) -> Claims {
+        self.claims
+    }
+}
+
+impl<Claims, Marker> Deref for ScopedClaims<Claims, Marker> {
+    type Target = Claims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.claims
+    }
+}
+
+impl<Claims: Clone, Marker> Clone for ScopedClaims<Claims, Marker> {
+    fn clone(&self) -> Self {
+        Self::new(self.claims.clone())
+    }
+}
+
+impl<Claims, Marker> FromRequest for ScopedClaims<Claims, Marker>
+where
+    Claims: Clone + 'static,
+    Marker: 'static,
+{
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<Self>().cloned().ok_or_else(|| {
+            ErrorBadRequest("could not extract scoped claims from HttpRequest extensions")
+        }))
+    }
+}
+
+/**
+    Signals whether the tokens of the current request were automatically refreshed by the [`crate::Authority`].
+
+    The [`crate::Authority`] inserts `WasRefreshed(true)` into the request extensions whenever it issues new tokens.
+    Extracting this type never fails, if no refresh occurred `WasRefreshed(false)` is returned.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::WasRefreshed;
+    # use actix_web::{get, Responder};
+    #[get("/hello")]
+    async fn hello(WasRefreshed(was_refreshed): WasRefreshed) -> impl Responder {
+        if was_refreshed {
+            "Hello there, your session was just renewed."
+        } else {
+            "Hello there."
+        }
+    }
+    ```
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WasRefreshed(pub bool);
+
+impl FromRequest for WasRefreshed {
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<Self>()
+            .copied()
+            .unwrap_or_default()))
+    }
+}
+
+/**
+    Signals whether the access token of the current request had already expired.
+
+    The [`crate::Authority`] only accepts expired access tokens if `allow_expired` is set to true,
+    in which case it inserts `WasExpired(true)` into the request extensions.
+    Extracting this type never fails, if the token was still valid `WasExpired(false)` is returned.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::WasExpired;
+    # use actix_web::{get, Responder};
+    #[get("/dashboard")]
+    async fn dashboard(WasExpired(was_expired): WasExpired) -> impl Responder {
+        if was_expired {
+            "Your session expired, please log in again."
+        } else {
+            "Welcome back."
+        }
+    }
+    ```
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WasExpired(pub bool);
+
+impl FromRequest for WasExpired {
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<Self>()
+            .copied()
+            .unwrap_or_default()))
+    }
+}
+
+pub(crate) fn insert_scoped_claims<Claims, Marker>(extensions: &mut Extensions, claims: Claims)
+where
+    Claims: Clone + 'static,
+    Marker: 'static,
+{
+    extensions.insert(ScopedClaims::<Claims, Marker>::new(claims.clone()));
+    extensions.insert(claims);
+}
+
+/**
+    The validated custom claims of a request together with the registered claims of the access token they were taken from.
+
+    The [`crate::Authority`] inserts the registered claims of every authenticated request into the request extensions,
+    extracting this type saves handlers from re-parsing the token for its expiration or issuance time.
+    If the access token was just renewed, the registered claims of the renewed token are returned.
+
+    Registered claims not handled by `jwt-compact`, other than `sub`, have to be part of the custom claims.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::AuthenticatedClaims;
+    # use actix_web::{get, Responder};
+    # use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Clone)]
+    struct User {
+        id: u32,
+    }
+
+    #[get("/session")]
+    async fn session(claims: AuthenticatedClaims<User>) -> impl Responder {
+        format!("user {} expires at {:?}", claims.custom.id, claims.expiration)
+    }
+    ```
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthenticatedClaims<Claims> {
+    /**
+        The custom claims of the token.
+    */
+    pub custom: Claims,
+    /**
+        The `exp` claim of the token.
+    */
+    pub expiration: Option<DateTime<Utc>>,
+    /**
+        The `nbf` claim of the token.
+    */
+    pub not_before: Option<DateTime<Utc>>,
+    /**
+        The `iat` claim of the token.
+    */
+    pub issued_at: Option<DateTime<Utc>>,
+    /**
+        The `sub` claim of the token, numeric subjects are converted into their string representation.
+    */
+    pub subject: Option<String>,
+}
+
+impl<Claims> FromRequest for AuthenticatedClaims<Claims>
+where
+    Claims: Clone + 'static,
+{
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let extensions = req.extensions();
+        ready(
+            match (
+                get_claims::<Claims>(&extensions),
+                extensions.get::<RegisteredClaims>(),
+            ) {
+                (Some(custom), Some(registered_claims)) => Ok(Self {
+                    custom,
+                    expiration: registered_claims.expiration,
+                    not_before: registered_claims.not_before,
+                    issued_at: registered_claims.issued_at,
+                    subject: registered_claims.subject.clone(),
+                }),
+                _ => Err(ErrorBadRequest(
+                    "could not extract authenticated claims from HttpRequest extensions",
+                )),
+            },
+        )
+    }
+}
+
+/*
+    The registered claims of the access token of an authenticated request,
+    inserted separately from the custom claims so they do not have to be cloned.
+*/
+#[derive(Clone)]
+pub(crate) struct RegisteredClaims {
+    expiration: Option<DateTime<Utc>>,
+    not_before: Option<DateTime<Utc>>,
+    issued_at: Option<DateTime<Utc>>,
+    pub(crate) subject: Option<String>,
+}
+
+impl RegisteredClaims {
+    pub(crate) fn new<Claims>(claims: &TokenClaims<Claims>, subject: Option<String>) -> Self {
+        Self {
+            expiration: claims.expiration,
+            not_before: claims.not_before,
+            issued_at: claims.issued_at,
+            subject,
+        }
+    }
+
+    /*
+        Takes the registered claims of an already validated token, with the `sub` claim read from its custom claims.
+    */
+    pub(crate) fn from_validated_claims<Claims: Serialize>(claims: &TokenClaims<Claims>) -> Self {
+        let subject = serde_json::to_value(&claims.custom)
+            .ok()
+            .and_then(|custom| subject_claim(&custom));
+        Self::new(claims, subject)
+    }
+
+    /*
+        Reads the registered claims of a token which was just issued, so they match the token the client receives.
+    */
+    pub(crate) fn from_issued_token(token: &str) -> AuthResult<Self> {
+        let claims = deserialize_claims_unchecked::<_, Value>(token)?;
+        Ok(Self::new(&claims, subject_claim(&claims.custom)))
+    }
+}
+
+/*
+    Returns the `sub` claim of the `claims`, which may be a string or a number.
+*/
+pub(crate) fn subject_claim(claims: &Value) -> Option<String> {
+    match claims.get("sub") {
+        Some(Value::String(subject)) => Some(subject.clone()),
+        Some(Value::Number(subject)) => Some(subject.to_string()),
+        _ => None,
+    }
+}
+
+/**
+    The `sub` claim of the access token of an authenticated request, parsed into `T`.
+
+    This saves defining a custom claims type just to read the subject, like the id of a user.
+    It requires the [`crate::Authority`] to be configured with `insert_subject` set to true
+    and works with any `Claims` type containing a `sub` field, whether it is a string or a number.
+    Extraction fails with a `400 Bad Request` if the subject was not inserted, the token has no `sub` claim
+    or the claim could not be parsed into `T`.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::Subject;
+    # use actix_web::{get, Responder};
+    #[get("/profile")]
+    async fn profile(Subject(user_id): Subject<u64>) -> impl Responder {
+        format!("profile of user {user_id}")
+    }
+    ```
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Subject<T>(pub T);
+
+impl<T> Subject<T> {
+    /**
+        Consumes the wrapper and returns the parsed subject.
+    */
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Subject<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for Subject<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(match req.extensions().get::<TokenSubject>() {
+            Some(TokenSubject(Some(subject))) => subject.parse().map(Self).map_err(|err| {
+                ErrorBadRequest(format!(
+                    "the sub claim of the token could not be parsed: {err}"
+                ))
+            }),
+            Some(TokenSubject(None)) => Err(ErrorBadRequest("the token has no sub claim")),
+            None => Err(ErrorBadRequest(
+                "could not extract the subject from HttpRequest extensions",
+            )),
+        })
+    }
+}
+
+/*
+    The `sub` claim of the access token of an authenticated request,
+    inserted by an [`crate::Authority`] with `insert_subject` set to true.
+*/
+pub(crate) struct TokenSubject(pub(crate) Option<String>);
@@ -0,0 +1,66 @@
+use crate::validate::deserialize_claims_unchecked;
+use crate::AuthError;
+use crate::AuthResult;
+
+use std::sync::Arc;
+
+use actix_web::HttpRequest;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+pub(crate) type FingerprintExtractor = Arc<dyn Fn(&HttpRequest) -> String + Send + Sync>;
+
+/*
+    Custom claims of a token extended by the hashed fingerprint of the device it was issued to.
+*/
+#[derive(Serialize)]
+pub(crate) struct FingerprintClaims<'a, Claims> {
+    fgp: String,
+    #[serde(flatten)]
+    custom: &'a Claims,
+}
+
+impl<'a, Claims> FingerprintClaims<'a, Claims> {
+    pub(crate) fn new(fingerprint_hash: &str, custom: &'a Claims) -> Self {
+        Self {
+            fgp: fingerprint_hash.into(),
+            custom,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FingerprintClaim {
+    fgp: Option<String>,
+}
+
+/*
+    Only the hash of the fingerprint is stored inside of the token,
+    so the token does not leak the raw fingerprint, which might contain a client secret.
+*/
+pub(crate) fn hash_fingerprint(fingerprint: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(fingerprint.as_bytes()))
+}
+
+/*
+    Checks that an already validated token was issued to the device with the `fingerprint`.
+*/
+pub(crate) fn verify_fingerprint(token_value: &str, fingerprint: &str) -> AuthResult<()> {
+    let claims = deserialize_claims_unchecked::<_, FingerprintClaim>(token_value)?;
+    match claims.custom.fgp {
+        Some(ref fgp)
+            if bool::from(
+                fgp.as_bytes()
+                    .ct_eq(hash_fingerprint(fingerprint).as_bytes()),
+            ) =>
+        {
+            Ok(())
+        }
+        _ => Err(AuthError::FingerprintMismatch),
+    }
+}
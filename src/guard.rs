@@ -0,0 +1,40 @@
+use actix_web::guard::Guard;
+use actix_web::guard::GuardContext;
+
+/*
+    Marker inserted into the request extensions by the [`crate::Authority`] once a request has been authenticated,
+    independent of the `Claims` type of the authority.
+*/
+pub(crate) struct Authenticated;
+
+/**
+    Guard matching requests which were authenticated by an enclosing [`crate::AuthenticationService`].
+
+    Unlike extracting the claims, this gates a route without naming the `Claims` type,
+    for example to only match a route for authenticated users and fall through to another one otherwise.
+
+    # Ordering
+    Guards are evaluated while routing, so the guard only sees requests the middleware has already processed
+    if the middleware wraps the `App` or a scope **enclosing** the guarded scope, resource or route.
+    A guard placed on the very scope wrapped by the middleware is evaluated before the middleware runs and never matches.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::AuthGuard;
+    # use actix_web::{web, Responder};
+    async fn dashboard() -> impl Responder {
+        "only for authenticated users"
+    }
+
+    // register this scope inside of a scope protected by the `AuthenticationService`
+    let scope = web::scope("/dashboard").guard(AuthGuard).route("", web::get().to(dashboard));
+    ```
+*/
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AuthGuard;
+
+impl Guard for AuthGuard {
+    fn check(&self, ctx: &GuardContext<'_>) -> bool {
+        ctx.req_data().contains::<Authenticated>()
+    }
+}
@@ -21,7 +21,7 @@ macro_rules! pull_from_token_signer {
 }
 
 macro_rules! continue_if_matches_err_variant {
-    ($match:expr, $variant: path) => {
+    ($match:expr, $variant: pat) => {
         match $match {
             Ok(value) => return Ok(value),
             Err($variant) => (),
@@ -33,22 +33,34 @@ macro_rules! continue_if_matches_err_variant {
 macro_rules! make_token_update {
     () => {
         Ok(Some(TokenUpdate {
+            access_token: None,
             access_cookie: None,
+            refresh_token: None,
             refresh_cookie: None,
+            navigation_cookie: None,
+            csrf_cookie: None,
         }))
     };
 
-    ($access_cookie:expr) => {
+    ($access_token:expr, $access_cookie:expr) => {
         Ok(Some(TokenUpdate {
+            access_token: Some($access_token),
             access_cookie: Some($access_cookie),
+            refresh_token: None,
             refresh_cookie: None,
+            navigation_cookie: None,
+            csrf_cookie: None,
         }))
     };
 
-    ($access_cookie:expr, $refresh_cookie:expr) => {
+    ($access_token:expr, $access_cookie:expr, $refresh_token:expr, $refresh_cookie:expr, $csrf_cookie:expr) => {
         Ok(Some(TokenUpdate {
+            access_token: Some($access_token),
             access_cookie: Some($access_cookie),
+            refresh_token: Some($refresh_token),
             refresh_cookie: Some($refresh_cookie),
+            navigation_cookie: None,
+            csrf_cookie: $csrf_cookie,
         }))
     };
 }
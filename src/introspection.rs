@@ -0,0 +1,451 @@
+use crate::dyn_authority::ErasedAuthority;
+use crate::dyn_authority::VerifyFuture;
+use crate::extractors::subject_claim;
+use crate::extractors::RegisteredClaims;
+use crate::AuthError;
+use crate::AuthResult;
+use crate::DynAuthorityConfig;
+use crate::TokenDelivery;
+use crate::TokenSource;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceRequest;
+use actix_web::http::header::HeaderValue;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use chrono::DateTime;
+use chrono::Utc;
+use derive_builder::Builder;
+use jwt_compact::Claims as TokenClaims;
+use jwt_compact::ValidationError;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+
+/**
+    Validates opaque reference tokens through an [RFC 7662](https://www.rfc-editor.org/rfc/rfc7662) introspection endpoint,
+    instead of validating self-contained JWTs locally like the [`crate::Authority`] does.
+
+    The token is read from the `Authorization` header or the cookie called `access_token_name`
+    and posted to the `introspection_url`, authenticated with the `client_id` and `client_secret` if set.
+    The claims of active tokens are deserialized from the introspection response and inserted into the request extensions,
+    so they can be extracted like the claims of a JWT.
+    Active results are cached until the `exp` of the response, but at most for the `max_cache_duration`,
+    results without an `exp` are not cached at all.
+
+    It is used by wrapping it into a [`crate::DynAuthority`] and requires the `reqwest` feature.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{DynAuthority, IntrospectionAuthority};
+    # use actix_web::{web, App};
+    # use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Clone)]
+    struct User {
+        sub: String,
+    }
+
+    let introspection_authority = IntrospectionAuthority::<User>::new()
+        .introspection_url("https://idp.example.com/oauth2/introspect")
+        .client_id("resource-server")
+        .client_secret("secret")
+        .build()
+        .unwrap();
+
+    let app = App::new().service(
+        web::scope("/auth-only").wrap(DynAuthority::from(introspection_authority)),
+    );
+    ```
+*/
+#[derive(Builder)]
+#[builder(pattern = "owned", build_fn(private, name = "build_with_client"))]
+pub struct IntrospectionAuthority<Claims> {
+    /**
+        The URL of the introspection endpoint tokens are posted to.
+    */
+    #[builder(setter(into))]
+    introspection_url: String,
+    /**
+        The id the resource server authenticates with at the introspection endpoint using HTTP Basic authentication.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    client_id: Option<String>,
+    /**
+        The secret the resource server authenticates with at the introspection endpoint,
+        only used together with the `client_id`.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    client_secret: Option<String>,
+    /**
+        The name of the cookie the token is read from if there is no `Authorization` header.
+
+        Defaults to `"access_token"`
+    */
+    #[builder(default = "\"access_token\".into()")]
+    #[builder(setter(into))]
+    access_token_name: String,
+    /**
+        The maximum duration an active introspection result is cached for.
+        Tokens revoked at the identity provider are accepted for at most this duration.
+
+        Defaults to `Duration::from_secs(5 * 60)`
+    */
+    #[builder(default = "Duration::from_secs(5 * 60)")]
+    max_cache_duration: Duration,
+    /**
+        The maximum duration establishing a connection to the introspection endpoint may take,
+        only used by the default `client`.
+
+        Defaults to `Duration::from_secs(5)`
+    */
+    #[builder(default = "DEFAULT_CONNECT_TIMEOUT")]
+    connect_timeout: Duration,
+    /**
+        The maximum duration a call to the introspection endpoint may take until its response is read,
+        calls taking longer fail with [`AuthError::Introspection`].
+
+        Defaults to `Duration::from_secs(10)`
+    */
+    #[builder(default = "DEFAULT_REQUEST_TIMEOUT")]
+    request_timeout: Duration,
+    /**
+        The HTTP client used to call the introspection endpoint.
+        The `request_timeout` applies to every call, a custom client has to set its own connect timeout though.
+
+        Defaults to a `reqwest::Client` using the `connect_timeout`
+    */
+    client: reqwest::Client,
+    /**
+        If set to true, errors are rendered as `application/problem+json` responses.
+        Please refer to [`crate::AuthorityBuilder::problem_details_responses`] for more details.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    problem_details_responses: bool,
+    #[builder(setter(skip))]
+    cache: Mutex<HashMap<[u8; 32], CachedIntrospection>>,
+    #[builder(setter(skip))]
+    claims_marker: PhantomData<Claims>,
+}
+
+/**
+    The non-secret configuration of an [`IntrospectionAuthority`], as returned by [`IntrospectionAuthority::public_config`].
+
+    It never contains the `client_secret`.
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct IntrospectionConfig {
+    pub introspection_url: String,
+    pub client_id: Option<String>,
+    pub access_token_name: String,
+    pub max_cache_duration: Duration,
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+}
+
+/*
+    The default timeouts of calls to the introspection endpoint, without them a stalled endpoint would hang every request.
+*/
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl<Claims> IntrospectionAuthorityBuilder<Claims> {
+    /**
+        Builds a new [`IntrospectionAuthority`].
+
+        Unless a `client` is set, the default client is created using the `connect_timeout`,
+        failing to initialize its TLS backend is reported as a [`IntrospectionAuthorityBuilderError::ValidationError`].
+    */
+    pub fn build(
+        mut self,
+    ) -> Result<IntrospectionAuthority<Claims>, IntrospectionAuthorityBuilderError> {
+        if self.client.is_none() {
+            let client = reqwest::Client::builder()
+                .connect_timeout(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+                .build()
+                .map_err(|err| {
+                    IntrospectionAuthorityBuilderError::ValidationError(format!(
+                        "The default client could not be created: {err}"
+                    ))
+                })?;
+            self.client = Some(client);
+        }
+        self.build_with_client()
+    }
+}
+
+/*
+    An active introspection response and the point in time until it may be used without asking the endpoint again.
+*/
+struct CachedIntrospection {
+    response: Value,
+    valid_until: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct IntrospectionStatus {
+    active: bool,
+    exp: Option<i64>,
+}
+
+impl<Claims> IntrospectionAuthority<Claims>
+where
+    Claims: DeserializeOwned + 'static,
+{
+    /**
+        Returns a new [`IntrospectionAuthorityBuilder`].
+    */
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> IntrospectionAuthorityBuilder<Claims> {
+        IntrospectionAuthorityBuilder::default()
+    }
+
+    /**
+        Introspects the `token` and returns its claims if it is active.
+
+        Returns [`AuthError::InactiveToken`] if the introspection endpoint reports the token as inactive
+        and [`AuthError::Introspection`] if the endpoint could not be called.
+    */
+    pub async fn introspect(&self, token: &str) -> AuthResult<TokenClaims<Claims>> {
+        deserialize_token_claims(self.introspection_response(token).await?)
+    }
+
+    /**
+        Returns the configuration of this [`IntrospectionAuthority`] without the `client_secret`.
+    */
+    pub fn public_config(&self) -> IntrospectionConfig {
+        IntrospectionConfig {
+            introspection_url: self.introspection_url.clone(),
+            client_id: self.client_id.clone(),
+            access_token_name: self.access_token_name.clone(),
+            max_cache_duration: self.max_cache_duration,
+            connect_timeout: self.connect_timeout,
+            request_timeout: self.request_timeout,
+        }
+    }
+
+    /*
+        Returns the introspection response of an active `token`, from the cache if possible.
+    */
+    async fn introspection_response(&self, token: &str) -> AuthResult<Value> {
+        match self.cached_response(token) {
+            Some(response) => Ok(response),
+            None => self.request_introspection(token).await,
+        }
+    }
+
+    /*
+        Posts the `token` to the introspection endpoint, caching the response if it is active.
+    */
+    async fn request_introspection(&self, token: &str) -> AuthResult<Value> {
+        let mut request = self
+            .client
+            .post(&self.introspection_url)
+            .timeout(self.request_timeout)
+            .form(&[("token", token), ("token_type_hint", "access_token")]);
+        if let Some(ref client_id) = self.client_id {
+            request = request.basic_auth(client_id, self.client_secret.as_ref());
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| AuthError::Introspection(err.to_string()))?
+            .bytes()
+            .await
+            .map_err(|err| AuthError::Introspection(err.to_string()))?;
+        let response: Value = serde_json::from_slice(&response)
+            .map_err(|err| AuthError::Introspection(err.to_string()))?;
+        let status = IntrospectionStatus::deserialize(&response)
+            .map_err(|err| AuthError::Introspection(err.to_string()))?;
+        if !status.active {
+            return Err(AuthError::InactiveToken);
+        }
+        if let Some(expiration) = status.exp.and_then(|exp| DateTime::from_timestamp(exp, 0)) {
+            self.cache_response(token, response.clone(), expiration);
+        }
+        Ok(response)
+    }
+
+    fn cached_response(&self, token: &str) -> Option<Value> {
+        let cache = self.cache.lock().expect("The cache lock is not poisoned");
+        cache
+            .get(&cache_key(token))
+            .filter(|cached| cached.valid_until > Utc::now())
+            .map(|cached| cached.response.clone())
+    }
+
+    fn cache_response(&self, token: &str, response: Value, expiration: DateTime<Utc>) {
+        let now = Utc::now();
+        let valid_until = chrono::Duration::from_std(self.max_cache_duration)
+            .ok()
+            .and_then(|max_cache_duration| now.checked_add_signed(max_cache_duration))
+            .map_or(expiration, |max_valid_until| {
+                expiration.min(max_valid_until)
+            });
+        let mut cache = self.cache.lock().expect("The cache lock is not poisoned");
+        cache.retain(|_, cached| cached.valid_until > now);
+        cache.insert(
+            cache_key(token),
+            CachedIntrospection {
+                response,
+                valid_until,
+            },
+        );
+    }
+
+    /*
+        Reads the token from the `Authorization` header, falling back to the `access_token_name` cookie.
+    */
+    fn token_value(&self, req: &HttpRequest) -> AuthResult<String> {
+        match req.headers().get(AUTHORIZATION).map(HeaderValue::to_str) {
+            Some(Ok(header_value)) => header_value
+                .strip_prefix("Bearer ")
+                .map(|token_value| token_value.trim().to_owned())
+                .ok_or_else(|| AuthError::NoToken(vec![TokenSource::AuthorizationHeader])),
+            Some(_) | None => req
+                .cookie(&self.access_token_name)
+                .map(|cookie| cookie.value().to_owned())
+                .ok_or_else(|| {
+                    AuthError::NoToken(vec![TokenSource::AuthorizationHeader, TokenSource::Cookie])
+                }),
+        }
+    }
+}
+
+/*
+    Tokens are cached by their hash, so the cache does not hold usable credentials.
+*/
+fn cache_key(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+/*
+    The members of an introspection response are named like the claims of a JWT,
+    so `exp`, `nbf` and `iat` map to the registered claims and all other members to the custom claims.
+*/
+fn deserialize_token_claims<Claims>(response: Value) -> AuthResult<TokenClaims<Claims>>
+where
+    Claims: DeserializeOwned,
+{
+    serde_json::from_value(response)
+        .map_err(|err| AuthError::ClaimsDeserialization(ValidationError::MalformedClaims(err)))
+}
+
+impl<Claims> fmt::Debug for IntrospectionAuthority<Claims> {
+    /**
+        Formats the [`IntrospectionAuthority`] while redacting the `client_secret`.
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntrospectionAuthority")
+            .field("introspection_url", &self.introspection_url)
+            .field("client_id", &self.client_id)
+            .field("access_token_name", &self.access_token_name)
+            .field("max_cache_duration", &self.max_cache_duration)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("problem_details_responses", &self.problem_details_responses)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Claims> ErasedAuthority<Claims> for IntrospectionAuthority<Claims>
+where
+    Claims: DeserializeOwned + 'static,
+{
+    fn verify_service_request<'a>(&'a self, req: &'a mut ServiceRequest) -> VerifyFuture<'a> {
+        Box::pin(async move {
+            let token_value = self.token_value(req.request())?;
+            let response = self.introspection_response(&token_value).await?;
+            let subject = subject_claim(&response);
+            let claims = deserialize_token_claims::<Claims>(response)?;
+            req.extensions_mut()
+                .insert(RegisteredClaims::new(&claims, subject));
+            req.extensions_mut().insert(claims.custom);
+            Ok(None)
+        })
+    }
+
+    /*
+        Requests can not be introspected synchronously, so only tokens with a cached introspection result are accepted.
+    */
+    fn verify_http_request(&self, req: &HttpRequest) -> AuthResult<Claims> {
+        let token_value = self.token_value(req)?;
+        let response = self
+            .cached_response(&token_value)
+            .ok_or_else(|| AuthError::Introspection("the token was not introspected yet".into()))?;
+        deserialize_token_claims::<Claims>(response).map(|claims| claims.custom)
+    }
+
+    /*
+        The introspection endpoint only reports tokens which are active, so expired tokens can not be verified.
+    */
+    fn verify_token_ignoring_expiry(&self, _token: &str) -> AuthResult<TokenClaims<Claims>> {
+        Err(AuthError::Unsupported(
+            "verifying tokens while ignoring their expiry",
+        ))
+    }
+
+    fn public_config(&self) -> DynAuthorityConfig {
+        DynAuthorityConfig::Introspection(IntrospectionAuthority::public_config(self))
+    }
+
+    fn skips_authentication(&self, _req: &ServiceRequest) -> bool {
+        false
+    }
+
+    fn unauthenticated_redirect(
+        &self,
+        _req: &ServiceRequest,
+        _err: &AuthError,
+    ) -> Option<HttpResponse> {
+        None
+    }
+
+    fn set_cookie_header_value(&self, cookie: &Cookie<'_>) -> HeaderValue {
+        HeaderValue::from_str(&cookie.to_string())
+            .expect("Cookies only contain valid header characters")
+    }
+
+    fn token_delivery(&self) -> TokenDelivery {
+        TokenDelivery::Cookie
+    }
+
+    fn access_token_header_name(&self) -> &str {
+        &self.access_token_name
+    }
+
+    /*
+        Introspected tokens are never renewed, so this header is never written.
+    */
+    fn refresh_token_header_name(&self) -> &str {
+        "refresh_token"
+    }
+
+    fn no_store_token_responses(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "problem-details")]
+    fn problem_details_responses(&self) -> bool {
+        self.problem_details_responses
+    }
+}
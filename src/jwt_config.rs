@@ -0,0 +1,178 @@
+use crate::time::SharedTimeOptions;
+use crate::token_signer::is_verified_by;
+use crate::token_signer::validate_token_lifetimes;
+use crate::token_signer::DEFAULT_ACCESS_TOKEN_LIFETIME;
+use crate::token_signer::DEFAULT_REFRESH_TOKEN_LIFETIME;
+use crate::AuthorityBuilder;
+use crate::TokenSigner;
+
+use std::time::Duration;
+
+use derive_builder::Builder;
+use jwt_compact::Algorithm;
+use serde::Serialize;
+
+/**
+    Shared configuration of a [`TokenSigner`] and an [`crate::Authority`],
+    which are guaranteed to agree on the algorithm, keys, token names and time options.
+
+    Building a [`JwtConfig`] fails if the `verifying_key` does not match the `signing_key`,
+    [`JwtConfig::into_parts`] then returns the [`TokenSigner`] together with an [`AuthorityBuilder`] using it,
+    which can be customized further before being built.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::JwtConfig;
+    # use ed25519_compact::KeyPair;
+    # use jwt_compact::alg::Ed25519;
+    # use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Clone)]
+    struct User {
+        id: u32,
+    }
+
+    let KeyPair { pk, sk } = KeyPair::generate();
+
+    let (token_signer, authority_builder) = JwtConfig::new()
+        .algorithm(Ed25519)
+        .signing_key(sk)
+        .verifying_key(pk)
+        .access_token_name("session")
+        .build()
+        .unwrap()
+        .into_parts::<User, _, ()>();
+
+    let authority = authority_builder
+        .refresh_authorizer(|| async move { Ok::<(), actix_web::Error>(()) })
+        .build()
+        .unwrap();
+    ```
+*/
+#[derive(Builder)]
+#[builder(pattern = "owned", build_fn(validate = "Self::validate"))]
+pub struct JwtConfig<Algo>
+where
+    Algo: Algorithm,
+{
+    /**
+        The Cryptographic signing algorithm used to sign and verify tokens.
+    */
+    algorithm: Algo,
+    /**
+        Key used to sign tokens.
+    */
+    signing_key: Algo::SigningKey,
+    /**
+        Key used to verify tokens, it has to match the `signing_key`.
+    */
+    verifying_key: Algo::VerifyingKey,
+    /**
+        The name of the access tokens.
+
+        Defaults to `"access_token"`
+    */
+    #[builder(default = "\"access_token\".into()")]
+    #[builder(setter(into))]
+    access_token_name: String,
+    /**
+        The name of the refresh tokens.
+
+        Defaults to `"refresh_token"`
+    */
+    #[builder(default = "\"refresh_token\".into()")]
+    #[builder(setter(into))]
+    refresh_token_name: String,
+    /**
+        The lifetime duration of the access token.
+
+        Defaults to `Duration::from_secs(60)`
+    */
+    #[builder(default = "DEFAULT_ACCESS_TOKEN_LIFETIME")]
+    access_token_lifetime: Duration,
+    /**
+        The lifetime duration of the refresh token.
+
+        Defaults to `Duration::from_secs(30 * 60)`
+    */
+    #[builder(default = "DEFAULT_REFRESH_TOKEN_LIFETIME")]
+    refresh_token_lifetime: Duration,
+    /**
+        Used to issue and validate tokens, please refer to the [`crate::time`] module for helpers constructing them.
+
+        Defaults to `time::leeway(0)`
+    */
+    #[builder(default = "crate::time::leeway(0).into()")]
+    #[builder(setter(into))]
+    time_options: SharedTimeOptions,
+}
+
+impl<Algo> JwtConfig<Algo>
+where
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+{
+    /**
+        Returns a new [`JwtConfigBuilder`].
+    */
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> JwtConfigBuilder<Algo> {
+        JwtConfigBuilder::default()
+    }
+
+    /**
+        Splits the configuration into a [`TokenSigner`] and an [`AuthorityBuilder`] verifying the tokens it signs.
+
+        The [`AuthorityBuilder`] takes its token names, algorithm and time options from the [`TokenSigner`],
+        only the `refresh_authorizer` has to be set before building it.
+    */
+    pub fn into_parts<Claims, ReAuth, Args>(
+        self,
+    ) -> (
+        TokenSigner<Claims, Algo>,
+        AuthorityBuilder<Claims, Algo, ReAuth, Args>,
+    )
+    where
+        Claims: Serialize,
+    {
+        let token_signer = TokenSigner::new()
+            .algorithm(self.algorithm)
+            .signing_key(self.signing_key)
+            .access_token_name(self.access_token_name)
+            .refresh_token_name(self.refresh_token_name)
+            .access_token_lifetime(self.access_token_lifetime)
+            .refresh_token_lifetime(self.refresh_token_lifetime)
+            .time_options(self.time_options)
+            .build()
+            .expect("All required fields of the TokenSigner are set");
+        let authority_builder = AuthorityBuilder::default()
+            .token_signer(Some(token_signer.clone()))
+            .verifying_key(self.verifying_key);
+        (token_signer, authority_builder)
+    }
+}
+
+impl<Algo> JwtConfigBuilder<Algo>
+where
+    Algo: Algorithm,
+{
+    /*
+        Rejects mismatched key pairs, which would lead to every token signed with this configuration being rejected,
+        as well as token lifetimes the [`TokenSigner`] would reject.
+    */
+    fn validate(&self) -> Result<(), String> {
+        validate_token_lifetimes(
+            self.access_token_lifetime
+                .unwrap_or(DEFAULT_ACCESS_TOKEN_LIFETIME),
+            self.refresh_token_lifetime
+                .unwrap_or(DEFAULT_REFRESH_TOKEN_LIFETIME),
+        )?;
+        if let (Some(ref algorithm), Some(ref signing_key), Some(ref verifying_key)) =
+            (&self.algorithm, &self.signing_key, &self.verifying_key)
+        {
+            if !is_verified_by(algorithm, signing_key, algorithm, verifying_key) {
+                return Err("The verifying_key does not match the signing_key.".into());
+            }
+        }
+        Ok(())
+    }
+}
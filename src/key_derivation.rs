@@ -0,0 +1,91 @@
+use crate::AuthError;
+use crate::AuthResult;
+
+use hkdf::Hkdf;
+use jwt_compact::alg::Hs256Key;
+use jwt_compact::alg::Hs384Key;
+use jwt_compact::alg::Hs512Key;
+use sha2::Sha256;
+use sha2::Sha384;
+use sha2::Sha512;
+
+/*
+    Context string binding the derived keys to their use as HMAC signing keys of this crate.
+*/
+const HMAC_KEY_INFO: &[u8] = b"actix-jwt-auth-middleware hmac signing key";
+
+/*
+    The minimal length of the salt, as recommended by NIST SP 800-132.
+*/
+const MIN_SALT_LENGTH: usize = 16;
+
+/**
+    Derives a `HMAC` signing key of the correct length from a passphrase using `HKDF`.
+
+    Feeding a short passphrase directly into a `HMAC` key weakens the signature of every token,
+    this derives a key matching the output length of the hash function used by the algorithm instead.
+    The same passphrase and salt always derive the same key, so the salt has to be stored alongside the configuration.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{DeriveHmacKey, TokenSigner, AuthError};
+    # use jwt_compact::alg::{Hs256, Hs256Key};
+    # use serde::Serialize;
+    #[derive(Serialize)]
+    struct User {
+        id: u32,
+    }
+
+    let signing_key = Hs256Key::derive_from_passphrase(b"correct horse battery staple", b"a unique salt value")?;
+
+    let token_signer = TokenSigner::<User, _>::new()
+        .signing_key(signing_key)
+        .algorithm(Hs256)
+        .build()
+        .unwrap();
+    # Ok::<(), AuthError>(())
+    ```
+*/
+pub trait DeriveHmacKey: Sized {
+    /**
+        Derives a key from the `passphrase` and `salt`.
+
+        Returns [`AuthError::KeyLoad`] if the `passphrase` is empty
+        or the `salt` is shorter than 16 bytes.
+    */
+    fn derive_from_passphrase(passphrase: &[u8], salt: &[u8]) -> AuthResult<Self>;
+}
+
+macro_rules! impl_derive_hmac_key {
+    ($key:ty, $hash:ty, $length:literal) => {
+        impl DeriveHmacKey for $key {
+            fn derive_from_passphrase(passphrase: &[u8], salt: &[u8]) -> AuthResult<Self> {
+                check_passphrase_and_salt(passphrase, salt)?;
+
+                let mut key = [0; $length];
+                Hkdf::<$hash>::new(Some(salt), passphrase)
+                    .expand(HMAC_KEY_INFO, &mut key)
+                    .map_err(|err| AuthError::KeyLoad(err.to_string()))?;
+                Ok(<$key>::new(key))
+            }
+        }
+    };
+}
+
+impl_derive_hmac_key!(Hs256Key, Sha256, 32);
+impl_derive_hmac_key!(Hs384Key, Sha384, 48);
+impl_derive_hmac_key!(Hs512Key, Sha512, 64);
+
+fn check_passphrase_and_salt(passphrase: &[u8], salt: &[u8]) -> AuthResult<()> {
+    if passphrase.is_empty() {
+        return Err(AuthError::KeyLoad(
+            "the passphrase must not be empty".into(),
+        ));
+    }
+    if salt.len() < MIN_SALT_LENGTH {
+        return Err(AuthError::KeyLoad(format!(
+            "the salt has to be at least {MIN_SALT_LENGTH} bytes long"
+        )));
+    }
+    Ok(())
+}
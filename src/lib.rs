@@ -4,18 +4,34 @@ to provide a jwt authentication middleware for the [`actix-web`](https://github.
 
 The jwt implementation supports the revocation for tokens via `access` and `refresh` tokens.
 
-It provides multiple cryptographic signing and verifying algorithms such as `HS256`, `HS384`, `HS512`, `EdDSA` and `ES256`.
+It provides multiple cryptographic signing and verifying algorithms such as `HS256`, `HS384`, `HS512`, `EdDSA`, `ES256` and `RS256`, `RS384`, `RS512` (`rsa` feature).
 For more infos on that mater please refer to the [`Supported algorithms`](https://docs.rs/jwt-compact/latest/jwt_compact/#supported-algorithms) section of the [`jwt-compact`](https://github.com/slowli/jwt-compact) crate.
 
 # Features
 - easy use of custom jwt claims
 - automatic extraction of the custom claims
-- extraction of tokens from `query` parameters, `HTTP` headers, [`Authorization`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Authorization) header and `cookies`
+- declarative role checks for handlers via the `#[require_role(..)]` attribute macro
+- extraction of tokens from `query` parameters, `HTTP` headers, [`Authorization`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Authorization) header, `cookies` and form bodies
 - verify only mode (`public key` only)
+- sign only mode (`TokenSigner` only, no `Authority` or `public key`)
+- choosing the algorithm at runtime through the type-erased `DynAuthority`
 - automatic renewal of `access` token (very customizable)
+- logging a user out everywhere by bumping a per-user token version
+- [CSRF](https://owasp.org/www-community/attacks/csrf) protection of cookie sessions through the double-submit pattern
 - easy way to set expiration time of `access` and `refresh` tokens
+- a shared `JwtConfig` producing a `TokenSigner` and `Authority` which are guaranteed to match
 - simple `UseJWT` trait for protecting a `App` or `Scope` (`Resource` is currently experimental [#91611](https://github.com/rust-lang/rust/issues/91611))
 - refresh authorizer function that has access to application state
+- optional [`tracing`](https://docs.rs/tracing) events describing the outcome of every authentication attempt (`tracing` feature)
+- optional [`metrics`](https://docs.rs/metrics) counters like `jwt_auth_success_total`, `jwt_auth_expired_total`, `jwt_auth_refreshed_total` and `jwt_auth_denied_total` (`metrics` feature)
+- optional encryption of token cookies with `AES-256-GCM`, keeping confidential claims unreadable for the client (`jwe` feature)
+- optional [CBOR](https://cbor.io/) serialized claims (`cbor` feature)
+- optional validation of the claims against a [JSON Schema](https://json-schema.org/) (`jsonschema` feature)
+- optional validation of opaque tokens through an [RFC 7662](https://www.rfc-editor.org/rfc/rfc7662) introspection endpoint (`reqwest` feature)
+- optional verification of tokens with detached payloads following [RFC 7797](https://www.rfc-editor.org/rfc/rfc7797) (`detached-payload` feature)
+- loading of `base64` or `hex` encoded keys from environment variables
+- optional `RSA` algorithms and loading of `PEM` encoded `RSA` keys (`rsa` feature)
+- optional [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) `application/problem+json` error responses (`problem-details` feature)
 
 # Automatic Extraction of Claims
 This crate tightly integrates into the actix-web ecosystem,
@@ -45,6 +61,10 @@ async fn hello(user_claims: UserClaims) -> impl Responder {
 For this your custom claim type has to implement the [`FromRequest`](actix_web::FromRequest) trait
 or it has to be annotated with the `#[derive(actix-jwt-auth-middleware::FromRequest)]` macro which implements this trait for your type.
 
+The claims are always (de)serialized through the [`Serialize`](serde::Serialize)/[`Deserialize`](serde::Deserialize) implementations of your type,
+so attributes like `#[serde(rename_all = "camelCase")]` or `#[serde(skip_serializing_if = "Option::is_none")]` are honored
+when signing as well as when validating tokens.
+
 # Simple Example
 ```no_run
 # use actix_jwt_auth_middleware::use_jwt::UseJWTOnApp;
@@ -106,20 +126,129 @@ async fn hello(user: User) -> impl Responder {
 }
 ```
 For more examples please referee to the `examples` directory.
+
+# Signer Only Deployments
+A dedicated authentication service, which only issues tokens and leaves their validation to other services,
+does not need an [`Authority`] or a verifying key at all.
+The [`TokenSigner`] can be used on its own and shared with the handlers through the application data.
+```no_run
+# use actix_jwt_auth_middleware::{AuthResult, TokenSigner};
+# use actix_web::{post, web, App, HttpResponse, HttpServer};
+# use ed25519_compact::KeyPair;
+# use jwt_compact::alg::Ed25519;
+# use serde::Serialize;
+#[derive(Serialize)]
+struct User {
+    id: u32,
+}
+
+#[actix_web::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let KeyPair { sk, .. } = KeyPair::generate();
+
+    HttpServer::new(move || {
+        let token_signer = TokenSigner::<User, _>::new()
+            .signing_key(sk.clone())
+            .algorithm(Ed25519)
+            .build()
+            .expect("");
+
+        App::new()
+            .app_data(web::Data::new(token_signer))
+            .service(login)
+    })
+    .bind(("127.0.0.1", 8080))?
+    .run()
+    .await?;
+
+    Ok(())
+}
+
+#[post("/login")]
+async fn login(token_signer: web::Data<TokenSigner<User, Ed25519>>) -> AuthResult<HttpResponse> {
+    token_signer.login_response(&User { id: 1 })
+}
+```
+
+# Security
+Signatures are exclusively verified by the [`jwt-compact`](https://github.com/slowli/jwt-compact) crate,
+which compares the signatures of symmetric algorithms (`HS256`, `HS384`, `HS512`) in constant time.
+Tokens are only accepted if the `alg` header names the configured algorithm exactly,
+otherwise they are rejected with [`AuthError::AlgorithmMismatch`] before their signature is looked at.
+This rules out unsigned tokens using `"alg": "none"` as well as algorithm confusion,
+like a token signed with `HS256` using the public key of an `RS256` authority as its secret.
+This crate never compares signatures or secrets itself, any comparison of secret values added in the future
+has to be done in constant time as well.
 */
 
 #[doc(inline)]
+pub use actix_jwt_auth_middleware_derive::require_role;
 pub use actix_jwt_auth_middleware_derive::FromRequest;
+pub use async_token_signer::*;
 pub use authority::*;
+pub use claims_format::*;
+pub use claims_schema::*;
+pub use dyn_authority::*;
+pub use encryption::*;
+pub use env_keys::*;
 pub use errors::*;
+pub use extractors::*;
+pub use guard::*;
+#[cfg(feature = "reqwest")]
+pub use introspection::*;
+pub use jwt_config::*;
+pub use key_derivation::*;
 pub use middleware::*;
+#[cfg(feature = "rsa")]
+pub use pem::*;
+pub use profile::*;
+pub use rng::*;
+pub use role::*;
 pub use token_signer::*;
+pub use token_version::*;
+pub use unverified::*;
 
+mod async_token_signer;
+mod auth_time;
 mod authority;
+mod body_tokens;
+mod claims_format;
+mod claims_schema;
+mod client_id;
+mod compression;
+mod csrf;
+#[cfg(feature = "detached-payload")]
+mod detached;
+mod dyn_authority;
+mod encryption;
+mod env_keys;
 mod errors;
+mod extractors;
+mod fingerprint;
+mod guard;
 mod helper_macros;
+#[cfg(feature = "reqwest")]
+mod introspection;
+mod jwt_config;
+mod key_derivation;
 mod middleware;
+#[cfg(feature = "rsa")]
+mod pem;
+mod profile;
+mod rng;
+mod role;
+pub mod time;
 mod token_signer;
+mod token_version;
+mod unverified;
 /// Convenience `UseJWT` traits
 pub mod use_jwt;
 mod validate;
+
+/*
+    Items used by the code generated by the macros of the derive crate, not part of the public API.
+*/
+#[doc(hidden)]
+pub mod __private {
+    pub use actix_web;
+}
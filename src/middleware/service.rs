@@ -1,3 +1,4 @@
+use crate::dyn_authority::ErasedAuthority;
 use crate::Authority;
 
 use std::future::Future;
@@ -8,6 +9,8 @@ use std::sync::Arc;
 
 use actix_web::body::MessageBody;
 use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{HeaderName, HeaderValue, CACHE_CONTROL, PRAGMA, SET_COOKIE};
 use actix_web::{Error as ActixWebError, FromRequest, Handler};
 use jwt_compact::Algorithm;
 use serde::de::DeserializeOwned;
@@ -56,25 +59,90 @@ where
 
     forward_ready!(service);
 
-    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+    fn call(&self, req: ServiceRequest) -> Self::Future {
         let inner = Arc::clone(&self.inner);
         let service = Rc::clone(&self.service);
 
-        Box::pin(async move {
-            match inner.verify_service_request(&mut req).await {
-                Ok(token_update) => service.call(req).await.and_then(|mut res| {
-                    if let Some(token_update) = token_update {
-                        if let Some(auth_cookie) = token_update.access_cookie {
-                            res.response_mut().add_cookie(&auth_cookie)?
-                        }
-                        if let Some(refresh_cookie) = token_update.refresh_cookie {
-                            res.response_mut().add_cookie(&refresh_cookie)?
+        Box::pin(async move { authenticate_request(&*inner, service, req).await })
+    }
+}
+
+/*
+    Authenticates the `req` using the `authority` and passes it on to the wrapped `service`,
+    delivering renewed tokens alongside its response.
+*/
+pub(crate) async fn authenticate_request<A, S, Body, Claims>(
+    authority: &A,
+    service: Rc<S>,
+    mut req: ServiceRequest,
+) -> Result<ServiceResponse<Body>, ActixWebError>
+where
+    A: ErasedAuthority<Claims> + ?Sized,
+    S: Service<ServiceRequest, Response = ServiceResponse<Body>, Error = ActixWebError>,
+{
+    if authority.skips_authentication(&req) {
+        return service.call(req).await;
+    }
+    match authority.verify_service_request(&mut req).await {
+        Ok(token_update) => service.call(req).await.and_then(|mut res| {
+            if let Some(token_update) = token_update {
+                let token_delivery = authority.token_delivery();
+                if token_delivery.includes_cookie() {
+                    for cookie in [
+                        token_update.access_cookie,
+                        token_update.refresh_cookie,
+                        token_update.navigation_cookie,
+                        token_update.csrf_cookie,
+                    ]
+                    .into_iter()
+                    .flatten()
+                    {
+                        res.headers_mut()
+                            .append(SET_COOKIE, authority.set_cookie_header_value(&cookie));
+                    }
+                }
+                if token_delivery.includes_header() {
+                    for (header_name, token) in [
+                        (
+                            authority.access_token_header_name(),
+                            token_update.access_token,
+                        ),
+                        (
+                            authority.refresh_token_header_name(),
+                            token_update.refresh_token,
+                        ),
+                    ] {
+                        if let Some(token) = token {
+                            res.headers_mut().insert(
+                                HeaderName::from_bytes(header_name.as_bytes())
+                                    .map_err(ErrorInternalServerError)?,
+                                HeaderValue::from_str(&token)
+                                    .expect("Token should not contain ASCII characters (33-127)"),
+                            );
                         }
                     }
-                    Ok(res)
-                }),
-                Err(err) => Err(err.into()),
+                }
+                if authority.no_store_token_responses() {
+                    res.headers_mut()
+                        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                    res.headers_mut()
+                        .insert(PRAGMA, HeaderValue::from_static("no-cache"));
+                }
+            }
+            Ok(res)
+        }),
+        Err(err) => {
+            if let Some(response) = authority.unauthenticated_redirect(&req, &err) {
+                return Err(actix_web::error::InternalError::from_response(err, response).into());
             }
-        })
+            match err {
+                #[cfg(feature = "problem-details")]
+                err if authority.problem_details_responses() && !err.wraps_actix_error() => {
+                    let response = err.to_problem_details();
+                    Err(actix_web::error::InternalError::from_response(err, response).into())
+                }
+                err => Err(err.into()),
+            }
+        }
     }
 }
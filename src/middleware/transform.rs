@@ -76,9 +76,18 @@ where
     */
     pub fn new(
         authority: Authority<Claims, Algo, ReAuth, Args>,
+    ) -> AuthenticationService<Claims, Algo, ReAuth, Args> {
+        Self::from_shared(Arc::new(authority))
+    }
+
+    /*
+        Returns a new `AuthenticationService` sharing the [`Authority`], for example with a [`crate::DynAuthority`].
+    */
+    pub(crate) fn from_shared(
+        authority: Arc<Authority<Claims, Algo, ReAuth, Args>>,
     ) -> AuthenticationService<Claims, Algo, ReAuth, Args> {
         AuthenticationService {
-            inner: Arc::new(authority),
+            inner: authority,
             claims_marker: PhantomData,
         }
     }
@@ -0,0 +1,93 @@
+use crate::rng::random_token;
+use crate::validate::deserialize_claims_unchecked;
+use crate::AuthError;
+use crate::AuthResult;
+use crate::RandomSource;
+
+use jwt_compact::UntrustedToken;
+use serde::Serialize;
+use serde_json::Map;
+use serde_json::Value;
+
+/*
+    The `typ` header mandated by RFC 9068 for JWT access tokens.
+*/
+pub(crate) const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+
+/*
+    The claims every RFC 9068 access token has to carry.
+*/
+const REQUIRED_CLAIMS: [&str; 7] = ["iss", "exp", "aud", "sub", "client_id", "iat", "jti"];
+
+/**
+    Configuration for issuing OAuth 2.0 JWT access tokens following [RFC 9068](https://www.rfc-editor.org/rfc/rfc9068).
+
+    Access tokens created by a [`crate::TokenSigner`] with this profile carry the `typ` header `"at+jwt"`
+    as well as the `iss`, `aud` and a freshly generated `jti` claim.
+    The `sub` and `client_id` claims have to be part of the custom claims.
+*/
+#[derive(Clone, Debug)]
+pub struct AccessTokenProfile {
+    issuer: String,
+    audience: String,
+}
+
+impl AccessTokenProfile {
+    /**
+        Returns a new [`AccessTokenProfile`] issuing tokens from `issuer` for the `audience`.
+    */
+    pub fn new(issuer: impl Into<String>, audience: impl Into<String>) -> Self {
+        Self {
+            issuer: issuer.into(),
+            audience: audience.into(),
+        }
+    }
+
+    pub(crate) fn claims<'a, Claims>(
+        &'a self,
+        custom: &'a Claims,
+        random_source: &dyn RandomSource,
+    ) -> ProfileClaims<'a, Claims> {
+        ProfileClaims {
+            iss: &self.issuer,
+            aud: &self.audience,
+            jti: random_token(random_source, 16),
+            custom,
+        }
+    }
+}
+
+/*
+    Custom claims of an access token extended by the registered claims of the profile.
+*/
+#[derive(Serialize)]
+pub(crate) struct ProfileClaims<'a, Claims> {
+    iss: &'a str,
+    aud: &'a str,
+    jti: String,
+    #[serde(flatten)]
+    custom: &'a Claims,
+}
+
+/*
+    Checks that an already validated token carries the `typ` header and the claims mandated by RFC 9068.
+*/
+pub(crate) fn validate_access_token_profile(token_value: &str) -> AuthResult<()> {
+    let untrusted_token = UntrustedToken::new(token_value)?;
+    if untrusted_token.header().token_type.as_deref() != Some(ACCESS_TOKEN_TYPE) {
+        return Err(AuthError::TokenTypeMismatch);
+    }
+
+    let claims = deserialize_claims_unchecked::<_, Map<String, Value>>(token_value)?;
+    for claim in REQUIRED_CLAIMS {
+        let is_present = match claim {
+            "exp" => claims.expiration.is_some(),
+            "iat" => claims.issued_at.is_some(),
+            _ => claims.custom.contains_key(claim),
+        };
+        if !is_present {
+            return Err(AuthError::MissingClaim(claim));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+/**
+    A source of the random bytes behind the security sensitive values generated by a [`crate::TokenSigner`],
+    namely the `jti` claim of tokens following an [`crate::AccessTokenProfile`] and CSRF tokens.
+
+    [`OsRandom`], the CSPRNG of the operating system, is used by default and is the only source suitable for production.
+    Implementing this trait around a seeded RNG makes these values deterministic in tests.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{RandomSource, TokenSigner};
+    # use ed25519_compact::KeyPair;
+    # use jwt_compact::alg::Ed25519;
+    # use serde::Serialize;
+    #[derive(Serialize)]
+    struct User {
+        id: u32,
+    }
+
+    #[derive(Debug)]
+    struct FixedRandom(u8);
+
+    impl RandomSource for FixedRandom {
+        fn fill_bytes(&self, dest: &mut [u8]) {
+            dest.fill(self.0);
+        }
+    }
+
+    let token_signer = TokenSigner::<User, _>::new()
+        .signing_key(KeyPair::generate().sk)
+        .algorithm(Ed25519)
+        .random_source(FixedRandom(42))
+        .build()
+        .unwrap();
+    ```
+*/
+pub trait RandomSource: fmt::Debug + Send + Sync {
+    /**
+        Fills all of `dest` with random bytes.
+    */
+    fn fill_bytes(&self, dest: &mut [u8]);
+}
+
+/**
+    The CSPRNG of the operating system, the default [`RandomSource`].
+*/
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsRandom;
+
+impl RandomSource for OsRandom {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        getrandom::getrandom(dest).expect("The operating system should provide randomness");
+    }
+}
+
+pub(crate) type SharedRandomSource = Arc<dyn RandomSource>;
+
+/*
+    Returns `len` bytes of the `random_source`, encoded with the URL safe base64 alphabet without padding.
+*/
+pub(crate) fn random_token(random_source: &dyn RandomSource, len: usize) -> String {
+    let mut token = vec![0; len];
+    random_source.fill_bytes(&mut token);
+    URL_SAFE_NO_PAD.encode(token)
+}
@@ -0,0 +1,37 @@
+/**
+    Implemented by claims which carry a role, used by handlers annotated with the [`crate::require_role`] attribute macro.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{require_role, FromRequest, HasRole};
+    # use actix_web::Responder;
+    #[derive(Clone, FromRequest)]
+    struct User {
+        id: u32,
+        role: Role,
+    }
+
+    #[derive(Clone, PartialEq)]
+    enum Role {
+        Basic,
+        Admin,
+    }
+
+    impl HasRole<Role> for User {
+        fn has_role(&self, role: &Role) -> bool {
+            self.role == *role
+        }
+    }
+
+    #[require_role(Role::Admin)]
+    async fn admin(user: User) -> impl Responder {
+        format!("Hello admin {}", user.id)
+    }
+    ```
+*/
+pub trait HasRole<Role> {
+    /**
+        Returns `true` if the claims grant the `role`.
+    */
+    fn has_role(&self, role: &Role) -> bool;
+}
@@ -0,0 +1,167 @@
+/*!
+Helpers for constructing the time options used by the [`crate::TokenSigner`] and the [`crate::Authority`].
+
+The builders of this crate accept any [`TimeOptions`] of [`jwt_compact`] and store them as [`SharedTimeOptions`],
+whose clock may capture state, like the instant returned by [`fixed_clock`].
+[`TimeOptions`] are the only part of the public API using [`chrono`] durations,
+[`SharedTimeOptions::new`] and the helpers below take [`std::time::Duration`]s or plain seconds instead,
+matching the configuration everywhere else.
+*/
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use jwt_compact::TimeOptions;
+
+/**
+    The leeway and clock used to issue and validate tokens.
+
+    Any [`TimeOptions`] with a `Send + Sync` clock can be converted into these,
+    which is what the `time_options` setters of the builders in this crate do.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{time::SharedTimeOptions, TokenSigner};
+    # use chrono::Utc;
+    # use ed25519_compact::KeyPair;
+    # use jwt_compact::alg::Ed25519;
+    # use serde::Serialize;
+    # use std::time::Duration;
+    #[derive(Serialize)]
+    struct User {
+        id: u32,
+    }
+
+    let token_signer = TokenSigner::<User, _>::new()
+        .signing_key(KeyPair::generate().sk)
+        .algorithm(Ed25519)
+        .time_options(SharedTimeOptions::new(Duration::from_secs(10), Utc::now))
+        .build()
+        .unwrap();
+    ```
+*/
+#[derive(Clone)]
+pub struct SharedTimeOptions {
+    leeway: TimeDelta,
+    clock_fn: Arc<dyn Fn() -> DateTime<Utc> + Send + Sync>,
+}
+
+impl SharedTimeOptions {
+    /**
+        Creates time options accepting tokens up to `leeway` after they expired, with `clock_fn` as the current time.
+
+        A `leeway` exceeding the range of [`TimeDelta`] is saturated.
+    */
+    pub fn new<F>(leeway: Duration, clock_fn: F) -> Self
+    where
+        F: Fn() -> DateTime<Utc> + Send + Sync + 'static,
+    {
+        Self {
+            leeway: saturating_time_delta(leeway),
+            clock_fn: Arc::new(clock_fn),
+        }
+    }
+
+    /**
+        Returns the leeway of these time options.
+    */
+    pub fn leeway(&self) -> Duration {
+        self.leeway.to_std().unwrap_or_default()
+    }
+
+    /**
+        Returns the current time according to the clock of these time options.
+    */
+    pub fn now(&self) -> DateTime<Utc> {
+        (self.clock_fn)()
+    }
+
+    /*
+        Returns these time options with their leeway replaced by `leeway`.
+    */
+    pub(crate) fn with_leeway(&self, leeway: Duration) -> Self {
+        Self {
+            leeway: saturating_time_delta(leeway),
+            clock_fn: Arc::clone(&self.clock_fn),
+        }
+    }
+
+    /*
+        Borrows these time options as the [`TimeOptions`] expected by jwt-compact.
+    */
+    pub(crate) fn as_time_options(&self) -> TimeOptions<impl Fn() -> DateTime<Utc> + '_> {
+        TimeOptions::new(self.leeway, || self.now())
+    }
+}
+
+impl<F> From<TimeOptions<F>> for SharedTimeOptions
+where
+    F: Fn() -> DateTime<Utc> + Send + Sync + 'static,
+{
+    fn from(time_options: TimeOptions<F>) -> Self {
+        Self {
+            leeway: time_options.leeway,
+            clock_fn: Arc::new(time_options.clock_fn),
+        }
+    }
+}
+
+impl Default for SharedTimeOptions {
+    fn default() -> Self {
+        TimeOptions::default().into()
+    }
+}
+
+impl fmt::Debug for SharedTimeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedTimeOptions")
+            .field("leeway", &self.leeway)
+            .finish_non_exhaustive()
+    }
+}
+
+/**
+    Returns [`TimeOptions`] using the current time, which accept tokens up to `seconds` after they expired.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::{time, TokenSigner};
+    # use ed25519_compact::KeyPair;
+    # use jwt_compact::alg::Ed25519;
+    # use serde::Serialize;
+    #[derive(Serialize)]
+    struct User {
+        id: u32,
+    }
+
+    let token_signer = TokenSigner::<User, _>::new()
+        .signing_key(KeyPair::generate().sk)
+        .algorithm(Ed25519)
+        .time_options(time::leeway(10))
+        .build()
+        .unwrap();
+    ```
+*/
+pub fn leeway(seconds: u64) -> TimeOptions {
+    TimeOptions::from_leeway(saturating_time_delta(Duration::from_secs(seconds)))
+}
+
+/**
+    Returns [`TimeOptions`] without any leeway whose clock always returns the `datetime`, this is meant for tests.
+*/
+pub fn fixed_clock(
+    datetime: DateTime<Utc>,
+) -> TimeOptions<impl Fn() -> DateTime<Utc> + Copy + Send + Sync + 'static> {
+    TimeOptions::new(TimeDelta::zero(), move || datetime)
+}
+
+/*
+    Converts the `duration` into a [`TimeDelta`], saturating at [`TimeDelta::MAX`].
+*/
+fn saturating_time_delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
@@ -1,33 +1,175 @@
+use crate::authority::TokenKind;
+use crate::claims_format::ClaimsFormat;
+use crate::compression::compress_claims;
+use crate::compression::deflate_header;
+use crate::csrf::generate_csrf_token;
+use crate::csrf::CsrfClaims;
+use crate::csrf::CsrfToken;
+use crate::encryption::TokenEncryptionKey;
+use crate::fingerprint::hash_fingerprint;
+use crate::fingerprint::FingerprintClaims;
+use crate::fingerprint::FingerprintExtractor;
+use crate::profile::AccessTokenProfile;
+use crate::profile::ACCESS_TOKEN_TYPE;
+use crate::rng::SharedRandomSource;
+use crate::time::SharedTimeOptions;
 use crate::AuthError;
 use crate::AuthResult;
+use crate::OsRandom;
+use crate::RandomSource;
 
+use std::borrow::Cow;
+use std::fmt;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
 
+use actix_web::cookie::time::Duration as CookieDuration;
 use actix_web::cookie::Cookie;
+use actix_web::cookie::CookieBuilder;
 use actix_web::cookie::SameSite;
 use actix_web::http::header::HeaderValue;
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::http::header::PRAGMA;
+use actix_web::http::header::SET_COOKIE;
+use actix_web::Error as ActixWebError;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::HttpResponseBuilder;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::DateTime;
 use chrono::TimeDelta;
+use chrono::Utc;
 use derive_builder::Builder;
 use jwt_compact::Algorithm;
 use jwt_compact::AlgorithmExt;
 use jwt_compact::Claims as TokenClaims;
+use jwt_compact::CreationError;
+use jwt_compact::Empty;
 use jwt_compact::Header;
-use jwt_compact::TimeOptions;
+use jwt_compact::UntrustedToken;
 use serde::Serialize;
+use serde_json::Value;
+
+/*
+    The default lifetime of access tokens, shared with the [`crate::JwtConfig`].
+*/
+pub(crate) const DEFAULT_ACCESS_TOKEN_LIFETIME: Duration = Duration::from_secs(60);
+
+/*
+    The default lifetime of refresh tokens, shared with the [`crate::JwtConfig`].
+*/
+pub(crate) const DEFAULT_REFRESH_TOKEN_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+type ClaimsEnricherFuture<Claims> = Pin<Box<dyn Future<Output = Result<Claims, ActixWebError>>>>;
+type ClaimsEnricher<Claims> = Arc<dyn Fn(Claims) -> ClaimsEnricherFuture<Claims> + Send + Sync>;
+
+/*
+    The template of token cookies if no `cookie_builder` is set.
+*/
+pub(crate) fn default_cookie_builder() -> CookieBuilder<'static> {
+    Cookie::build("", "")
+        .secure(true)
+        .same_site(SameSite::Strict)
+}
+
+/*
+    Prepends the `cookie_name_prefix` to the `cookie_name`, if one is set.
+*/
+pub(crate) fn prefixed_cookie_name(cookie_name_prefix: Option<&str>, cookie_name: &str) -> String {
+    match cookie_name_prefix {
+        Some(cookie_name_prefix) => format!("{cookie_name_prefix}{cookie_name}"),
+        None => cookie_name.to_string(),
+    }
+}
+
+/*
+    The CSRF token of the token the `req` was authenticated with, if any.
+*/
+fn request_csrf_token(req: &HttpRequest) -> Option<String> {
+    req.extensions()
+        .get::<CsrfToken>()
+        .map(|CsrfToken(csrf_token)| csrf_token.clone())
+}
+
+/*
+    Turns a finished cookie back into a builder carrying all of its attributes.
+*/
+fn into_cookie_builder(cookie: Cookie<'static>) -> CookieBuilder<'static> {
+    let mut cookie_builder = Cookie::build(cookie.name().to_owned(), cookie.value().to_owned());
+    if let Some(expires) = cookie.expires() {
+        cookie_builder = cookie_builder.expires(expires);
+    }
+    if let Some(max_age) = cookie.max_age() {
+        cookie_builder = cookie_builder.max_age(max_age);
+    }
+    if let Some(domain) = cookie.domain() {
+        cookie_builder = cookie_builder.domain(domain.to_owned());
+    }
+    if let Some(path) = cookie.path() {
+        cookie_builder = cookie_builder.path(path.to_owned());
+    }
+    if let Some(secure) = cookie.secure() {
+        cookie_builder = cookie_builder.secure(secure);
+    }
+    if let Some(http_only) = cookie.http_only() {
+        cookie_builder = cookie_builder.http_only(http_only);
+    }
+    if let Some(same_site) = cookie.same_site() {
+        cookie_builder = cookie_builder.same_site(same_site);
+    }
+    cookie_builder
+}
+
+/**
+    The value of the `Priority` attribute of token cookies,
+    which controls the order in which browsers evict cookies once the limit per domain is reached.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CookiePriority {
+    /**
+        Cookies with a `Low` priority are evicted first.
+    */
+    Low,
+    /**
+        The priority of cookies without a `Priority` attribute.
+    */
+    Medium,
+    /**
+        Cookies with a `High` priority are evicted last.
+    */
+    High,
+}
+
+impl CookiePriority {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "Low",
+            Self::Medium => "Medium",
+            Self::High => "High",
+        }
+    }
+}
 
 /**
     The [`TokenSigner`] is a convenience struct,
     which holds configuration values as well as a private key for generation JWTs.
 
     For example, the [`crate::Authority`] uses it to automatically refresh the `access`/`refresh` token.
+
+    The [`TokenSigner`] only requires a signing key, so services which issue tokens without ever validating them
+    can use it on its own, without an [`crate::Authority`] or a verifying key.
     # Example
     ```rust
     # use std::time::Duration;
-    # use actix_jwt_auth_middleware::{TokenSigner, AuthError};
+    # use actix_jwt_auth_middleware::{time, TokenSigner, AuthError};
     # use serde::Serialize;
     # use ed25519_compact::KeyPair;
-    # use jwt_compact::{alg::Ed25519, TimeOptions};
+    # use jwt_compact::alg::Ed25519;
     #[derive(Serialize, Clone)]
     struct User {
         id: u32
@@ -44,7 +186,7 @@ use serde::Serialize;
         // makes every refresh token generated be valid for 2 hours
         .refresh_token_lifetime(Duration::from_secs(120 * 60))
         // generated tokens can still be used up to 10 seconds after they expired
-        .time_options(TimeOptions::from_leeway(chrono::Duration::seconds(10)))
+        .time_options(time::leeway(10))
         .algorithm(Ed25519)
         .build().unwrap();
 
@@ -56,7 +198,7 @@ use serde::Serialize;
     Please refer to the [`TokenSignerBuilder`] for a detailed description of Options available on this struct.
 */
 #[derive(Builder)]
-#[builder(pattern = "owned")]
+#[builder(pattern = "owned", build_fn(validate = "Self::validate"))]
 pub struct TokenSigner<Claims, Algo>
 where
     Algo: Algorithm,
@@ -76,8 +218,8 @@ where
 
         Defaults to `Duration::from_secs(60)`
     */
-    #[builder(default = "Duration::from_secs(60)")]
-    access_token_lifetime: Duration,
+    #[builder(default = "DEFAULT_ACCESS_TOKEN_LIFETIME")]
+    pub(crate) access_token_lifetime: Duration,
     /**
         The name of the future refresh tokens.
 
@@ -93,8 +235,8 @@ where
 
         Defaults to `Duration::from_secs(30 * 60)`
     */
-    #[builder(default = "Duration::from_secs(30 * 60)")]
-    refresh_token_lifetime: Duration,
+    #[builder(default = "DEFAULT_REFRESH_TOKEN_LIFETIME")]
+    pub(crate) refresh_token_lifetime: Duration,
     /**
         JWT Header used in the creation of access and refresh tokens.
 
@@ -104,6 +246,29 @@ where
     */
     #[builder(default)]
     header: Header,
+    /**
+        The `typ` header of the created tokens, for example `"JWT"` or `"at+jwt"` for
+        [RFC 9068](https://www.rfc-editor.org/rfc/rfc9068) access tokens.
+
+        When set, this overrides the `token_type` of the `header`.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    token_type: Option<String>,
+    /**
+        If set, access tokens are issued following [RFC 9068](https://www.rfc-editor.org/rfc/rfc9068),
+        carrying the `typ` header `"at+jwt"` as well as the `iss`, `aud` and `jti` claims.
+
+        Refresh tokens are not affected by this option.
+        Please refer to [`AccessTokenProfile`] for more details.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    access_token_profile: Option<AccessTokenProfile>,
     /**
         The Cryptographic signing algorithm used in the process of creation of access and refresh tokens.
 
@@ -115,15 +280,236 @@ where
         Key used to sign tokens.
     */
     signing_key: Algo::SigningKey,
+    /**
+        The Cryptographic signing algorithm used in the process of creation of refresh tokens.
+
+        Together with the `refresh_signing_key`, this allows long lived refresh tokens to be signed with a different key than access tokens,
+        for example one which is kept offline from the services only verifying access tokens.
+
+        Defaults to `None`, in which case refresh tokens are signed with the `algorithm`.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    pub(crate) refresh_algorithm: Option<Algo>,
+    /**
+        Key used to sign refresh tokens.
+
+        Defaults to `None`, in which case refresh tokens are signed with the `signing_key`.
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    refresh_signing_key: Option<Algo::SigningKey>,
     /**
         Used in the creating of the `token`, the current time stamp is taken from this.
 
-        Please refer to the structs own documentation for more details.
+        Please refer to the structs own documentation for more details,
+        the [`crate::time`] module provides helpers for constructing them.
+
+        Defaults to `time::leeway(0)`
+    */
+    #[builder(default = "crate::time::leeway(0).into()")]
+    #[builder(setter(into))]
+    pub(crate) time_options: SharedTimeOptions,
+    /**
+        Template used in the creation of access and refresh token cookies.
+
+        The name and value of the template are replaced by the token name and the signed token,
+        every other attribute (`Secure`, `SameSite`, `HttpOnly`, `Path`, ...) is kept as configured.
+
+        Setting `.secure(false)` on this builder allows the cookies to be set over plain `http://localhost` during development,
+        please do not do this in production.
+
+        Defaults to `Cookie::build("", "").secure(true).same_site(SameSite::Strict)`
+    */
+    #[builder(default = "default_cookie_builder()")]
+    cookie_builder: CookieBuilder<'static>,
+    /**
+        If set to true, the `Max-Age` of every token cookie matches the lifetime of the token it carries,
+        so the cookie is removed by the browser once the token expired.
+
+        A `Max-Age` or `Expires` attribute set on the `cookie_builder` takes precedence.
+        If set to false, the cookies are session cookies, unless configured otherwise on the `cookie_builder`.
+        Please note that browsers do not send expired cookies, so expired refresh tokens can not be renewed
+        through the `refresh_leeway` or `renew_refresh_token_automatically` of the [`crate::Authority`] while this is set.
+
+        Defaults to `true`
+    */
+    #[builder(default = "true")]
+    cookie_max_age_from_token_lifetime: bool,
+    /**
+        If set to true, the serialized claims are DEFLATE compressed before they are signed
+        and the `zip` header of the token is set to `"DEF"`.
+
+        This keeps tokens with large claims under the size limit browsers impose on cookies.
+        The [`crate::Authority`] transparently decompresses tokens carrying this header.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    compress_claims: bool,
+    /**
+        The format the claims are serialized in.
+
+        Please refer to [`ClaimsFormat`] for more details.
+
+        Defaults to `ClaimsFormat::Json`
+    */
+    #[builder(default = "ClaimsFormat::Json")]
+    pub(crate) claims_format: ClaimsFormat,
+    /**
+        The maximum number of token bytes stored in a single cookie created by [`TokenSigner::create_cookies_chunked`],
+        building the [`TokenSigner`] fails if it is `0`.
+
+        Defaults to `4000`
+    */
+    #[builder(default = "4000")]
+    cookie_chunk_size: usize,
+    /**
+        If set to true, the token is base64url encoded before it is placed in a cookie.
+
+        Some reverse proxies and CDNs mangle the `.` characters of a raw JWT inside of a cookie value,
+        the [`crate::Authority`] pulls this flag from its `token_signer` and decodes the cookie before validation.
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    pub(crate) encode_cookie_value: bool,
+    /**
+        The `Domain` attribute set on every cookie created by this struct,
+        for example `"example.com"` to share the cookies between `app.example.com` and `api.example.com`.
+
+        When set, this overrides the domain configured on the `cookie_builder`.
+        The domain has to be a plain host name, without a scheme, port or path.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    cookie_domain: Option<String>,
+    /**
+        The `Path` attribute set on refresh token cookies, for example `"/api/refresh"`,
+        so browsers only send the refresh token to the endpoints renewing tokens instead of every endpoint.
+
+        When set, this overrides the path configured on the `cookie_builder` for refresh token cookies only,
+        the access token cookie keeps covering its usual path.
+        Please note that an [`crate::Authority`] only sees the refresh token on requests to this path,
+        so it can only renew expired access tokens automatically there.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    refresh_cookie_path: Option<String>,
+    /**
+        A prefix prepended to the name of every cookie created by this struct, for example `"t1_"`,
+        resulting in the cookies `t1_access_token` and `t1_refresh_token`.
+
+        This keeps the cookies of several tenants served from different paths of the same host apart,
+        without having to configure the `Path` of every cookie.
+        The [`crate::Authority`] applies the same prefix when looking up token cookies.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    pub(crate) cookie_name_prefix: Option<String>,
+    /**
+        The name of a secondary access token cookie with the `SameSite=Lax` attribute,
+        set by [`Self::login_response`] and renewed by the [`crate::Authority`] alongside the access token cookie.
+
+        Browsers do not send `SameSite=Strict` cookies on top-level navigations from other sites,
+        so following a link from an email would land the user logged out.
+        The [`crate::Authority`] accepts the access token from either cookie,
+        keeping the `Strict` cookie for same-site requests while cross-site navigations fall back to the `Lax` one.
+        This trades some of the CSRF protection of `Strict` cookies for usability,
+        state changing handlers should not be reachable through `GET` requests.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    pub(crate) navigation_cookie_name: Option<String>,
+    /**
+        The name of a cookie carrying a CSRF token for the double-submit pattern, for example `"csrf_token"`.
+
+        [`Self::login_response`] and [`Self::create_login_cookies`] generate a random CSRF token,
+        embed it as the `csrf` claim into the access and refresh token and set it as a cookie readable by JavaScript.
+        The client has to echo the value of this cookie in a header,
+        which an [`crate::Authority`] with a `csrf_header_name` compares to the claim.
+        Tokens renewed by the [`crate::Authority`] keep the CSRF token of the token they were renewed from,
+        the CSRF cookie therefore stays valid for the whole session.
+
+        Please note that tokens created by the other functions of this struct do not carry a CSRF token.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(into, strip_option))]
+    csrf_cookie_name: Option<String>,
+    /**
+        The `Priority` attribute of the token cookies.
+
+        The cookie type of actix-web has no notion of this attribute,
+        so it is only part of the value returned by [`TokenSigner::set_cookie_header_value`],
+        which the [`crate::Authority`] uses for renewed token cookies.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    cookie_priority: Option<CookiePriority>,
+    /**
+        If set to true, the token cookies carry the `Partitioned` attribute,
+        storing them separately per top level site as specified by [CHIPS](https://developer.mozilla.org/en-US/docs/Web/Privacy/Privacy_sandbox/Partitioned_cookies).
+        This allows authenticated widgets to be embedded into third party sites, browsers require `Secure` cookies for this.
+
+        Like the `cookie_priority`, this attribute is only part of the value returned by [`TokenSigner::set_cookie_header_value`].
+
+        Defaults to `false`
+    */
+    #[builder(default = "false")]
+    cookie_partitioned: bool,
+    /**
+        Async function used to augment the claims before they are signed in [`TokenSigner::create_access_cookie_async`].
+
+        Please refer to [`TokenSignerBuilder::claims_enricher`] for more details.
 
-        Defaults to `TimeOptions::from_leeway(Duration::seconds(0))`
+        Defaults to `None`
     */
-    #[builder(default = "TimeOptions::from_leeway(TimeDelta::try_seconds(0).unwrap())")]
-    pub(crate) time_options: TimeOptions,
+    #[builder(setter(custom), default = "None")]
+    claims_enricher: Option<ClaimsEnricher<Claims>>,
+    /**
+        Function used to compute a fingerprint of the device a request originates from,
+        binding tokens created by [`TokenSigner::create_access_cookie_for_request`] to that device.
+
+        Please refer to [`TokenSignerBuilder::fingerprint_extractor`] for more details.
+
+        Defaults to `None`
+    */
+    #[builder(setter(custom), default = "None")]
+    pub(crate) fingerprint_extractor: Option<FingerprintExtractor>,
+    /**
+        Key used to encrypt tokens before they are placed in a cookie,
+        keeping confidential claims unreadable for the client.
+
+        Encryption only takes effect with the `jwe` feature enabled,
+        without a key plain signed tokens are used.
+        The [`crate::Authority`] pulls this key from its `token_signer` to decrypt cookies.
+
+        Defaults to `None`
+    */
+    #[builder(default = "None")]
+    #[builder(setter(strip_option))]
+    pub(crate) encryption_key: Option<TokenEncryptionKey>,
+    /**
+        Source of the random bytes of the `jti` claim and of CSRF tokens.
+
+        Please refer to [`RandomSource`] for more details.
+
+        Defaults to [`OsRandom`]
+    */
+    #[builder(setter(custom), default = "Arc::new(OsRandom)")]
+    random_source: SharedRandomSource,
     #[doc(hidden)]
     #[builder(setter(skip), default = "PhantomData")]
     claims_marker: PhantomData<Claims>,
@@ -159,23 +545,25 @@ where
     /**
         Creates a refresh token header value.
 
-        Internally it calls [`Self::create_header_value`] while passing the previously defined
-        `refresh_token_lifetime` value on this struct.
+        The token is valid for the previously defined `refresh_token_lifetime`
+        and signed with the `refresh_signing_key` if one is set.
     */
     #[inline]
     pub fn create_refresh_header_value(&self, claims: &Claims) -> AuthResult<HeaderValue> {
-        self.create_header_value(claims, self.refresh_token_lifetime)
+        let token = self.refresh_token(claims, TokenBinding::default(), None)?;
+        Ok(HeaderValue::from_str(&token)
+            .expect("Token should not contain ASCII characters (33-127)"))
     }
 
     /**
         Creates a access token header value.
 
-        Internally it calls [`Self::create_header_value`] while passing the previously defined
-        `access_token_lifetime` value on this struct.
+        Internally it calls [`Self::create_access_token`].
     */
-    #[inline]
     pub fn create_access_header_value(&self, claims: &Claims) -> AuthResult<HeaderValue> {
-        self.create_header_value(claims, self.access_token_lifetime)
+        let token = self.create_access_token(claims)?;
+        Ok(HeaderValue::from_str(&token)
+            .expect("Token should not contain ASCII characters (33-127)"))
     }
 
     /**
@@ -199,11 +587,10 @@ where
 
         This value is typically set as the Authorization header, also known as Bearer Authentication.
 
-        Internally it it calls [`Self::create_signed_token`]
-        while passing the previously defined value of the `access_token_lifetime` on this struct.
+        Internally it it calls [`Self::create_access_token`].
     */
     pub fn create_bearer_header_value(&self, claims: &Claims) -> AuthResult<HeaderValue> {
-        let token = self.create_signed_token(claims, self.access_token_lifetime)?;
+        let token = self.create_access_token(claims)?;
         Ok(HeaderValue::from_str(&format!("Bearer {token}"))
             .expect("Token should not contain ASCII characters (33-127)"))
     }
@@ -211,26 +598,334 @@ where
     /**
         Creates a access token cookie.
 
-        Internally it calls [`Self::create_cookie`] while passing the previously defined
-        `access_token_name` and `access_token_lifetime` values on this struct.
+        Internally it calls [`Self::create_access_token`] and names the cookie after
+        the previously defined `access_token_name` value on this struct.
     */
-    #[inline]
     pub fn create_access_cookie(&self, claims: &Claims) -> AuthResult<Cookie<'static>> {
-        self.create_cookie(claims, &self.access_token_name, self.access_token_lifetime)
+        let token = self.create_access_token(claims)?;
+        Ok(self.build_cookie(
+            self.access_token_name.clone(),
+            self.seal_cookie_token(token),
+            self.access_token_lifetime,
+        ))
+    }
+
+    /**
+        Returns a [`CookieBuilder`] pre-populated like the cookie returned by [`Self::create_access_cookie`],
+        so individual attributes can be changed before finishing it.
+
+        The `Priority` and `Partitioned` attributes are not part of the builder,
+        they are still appended by [`Self::set_cookie_header_value`].
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::TokenSigner;
+        # use actix_web::cookie::SameSite;
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        # use serde::Serialize;
+        #[derive(Serialize)]
+        struct User {
+            id: u32,
+        }
+
+        let token_signer = TokenSigner::<User, _>::new()
+            .signing_key(KeyPair::generate().sk)
+            .algorithm(Ed25519)
+            .build()
+            .unwrap();
+
+        let cookie = token_signer
+            .access_cookie_builder_for(&User { id: 1 })
+            .unwrap()
+            .same_site(SameSite::Lax)
+            .path("/app")
+            .finish();
+        ```
+    */
+    pub fn access_cookie_builder_for(&self, claims: &Claims) -> AuthResult<CookieBuilder<'static>> {
+        Ok(into_cookie_builder(self.create_access_cookie(claims)?))
+    }
+
+    /**
+        Returns a copy of the `access_cookie` named after the `navigation_cookie_name` with the `SameSite=Lax` attribute,
+        or `None` if no `navigation_cookie_name` is set.
+
+        Handlers setting the cookies themselves should set this cookie alongside the access token cookie,
+        [`Self::login_response`] already does.
+    */
+    pub fn navigation_cookie(&self, access_cookie: &Cookie<'_>) -> Option<Cookie<'static>> {
+        let navigation_cookie_name = self.navigation_cookie_name.as_ref()?;
+        let mut navigation_cookie = access_cookie.clone().into_owned();
+        navigation_cookie.set_name(prefixed_cookie_name(
+            self.cookie_name_prefix.as_deref(),
+            navigation_cookie_name,
+        ));
+        navigation_cookie.set_same_site(SameSite::Lax);
+        Some(navigation_cookie)
+    }
+
+    /**
+        Creates a access token cookie after passing the `claims` through the `claims_enricher`.
+
+        This allows the claims to be populated asynchronously, for example with the current roles of a user fetched from a database.
+        If no `claims_enricher` is set, this behaves like [`Self::create_access_cookie`].
+    */
+    pub async fn create_access_cookie_async(&self, claims: Claims) -> AuthResult<Cookie<'static>> {
+        let claims = match self.claims_enricher {
+            Some(ref claims_enricher) => claims_enricher(claims)
+                .await
+                .map_err(AuthError::ClaimsEnricher)?,
+            None => claims,
+        };
+        self.create_access_cookie(&claims)
     }
 
     /**
         Creates a refresh token cookie.
 
-        Internally it calls [`Self::create_cookie`] while passing the previously defined
-        `refresh_token_name` and `refresh_token_lifetime` values on this struct.
+        The cookie is named after the previously defined `refresh_token_name` value on this struct,
+        the token is valid for the `refresh_token_lifetime` and signed with the `refresh_signing_key` if one is set.
     */
-    #[inline]
     pub fn create_refresh_cookie(&self, claims: &Claims) -> AuthResult<Cookie<'static>> {
-        self.create_cookie(
+        self.create_refresh_cookie_with_lifetime(claims, self.refresh_token_lifetime)
+    }
+
+    /**
+        Creates a refresh token cookie valid for the `token_lifetime` instead of the `refresh_token_lifetime`.
+
+        This allows a login handler to choose the length of a session, for example a longer one for "remember me".
+        Please note that refresh tokens renewed through `renew_refresh_token_automatically` of the [`crate::Authority`]
+        are valid for the `refresh_token_lifetime` again.
+        A `token_lifetime` whose expiration can not be represented is rejected with [`AuthError::TokenCreation`].
+    */
+    pub fn create_refresh_cookie_with_lifetime(
+        &self,
+        claims: &Claims,
+        token_lifetime: Duration,
+    ) -> AuthResult<Cookie<'static>> {
+        let token = self.fingerprinted_token(
             claims,
-            &self.refresh_token_name,
+            token_lifetime,
+            None,
+            None,
+            TokenBinding::default(),
+            TokenKind::Refresh,
+        )?;
+        Ok(self.build_refresh_cookie(self.seal_cookie_token(token), token_lifetime))
+    }
+
+    /**
+        Returns a `200 OK` response setting the access and refresh token cookies, with the `claims` serialized as JSON as its body.
+        If a `navigation_cookie_name` is set, the navigation cookie is set as well.
+
+        This is a shorthand for the usual login handler, the cookies are set using [`Self::set_cookie_header_value`].
+        The response carries the `Cache-Control: no-store` and `Pragma: no-cache` headers, so it is never stored by caches.
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::{AuthResult, TokenSigner};
+        # use actix_web::{get, web, HttpResponse};
+        # use jwt_compact::alg::Ed25519;
+        # use serde::Serialize;
+        #[derive(Serialize)]
+        struct User {
+            id: u32,
+        }
+
+        #[get("/login")]
+        async fn login(token_signer: web::Data<TokenSigner<User, Ed25519>>) -> AuthResult<HttpResponse> {
+            token_signer.login_response(&User { id: 1 })
+        }
+        ```
+    */
+    pub fn login_response(&self, claims: &Claims) -> AuthResult<HttpResponse> {
+        Ok(self.login_response_builder(claims)?.json(claims))
+    }
+
+    /**
+        Returns a `200 OK` response setting the access and refresh token cookies without a body.
+
+        Please refer to [`Self::login_response`] for more details.
+    */
+    pub fn login_response_without_body(&self, claims: &Claims) -> AuthResult<HttpResponse> {
+        Ok(self.login_response_builder(claims)?.finish())
+    }
+
+    fn login_response_builder(&self, claims: &Claims) -> AuthResult<HttpResponseBuilder> {
+        let mut response = HttpResponse::Ok();
+        response
+            .insert_header((CACHE_CONTROL, "no-store"))
+            .insert_header((PRAGMA, "no-cache"));
+        for cookie in self.create_login_cookies(claims)? {
+            response.append_header((SET_COOKIE, self.set_cookie_header_value(&cookie)));
+        }
+        Ok(response)
+    }
+
+    /**
+        Creates every cookie set by [`Self::login_response`], for handlers building the response themselves.
+
+        These are the access and refresh token cookies, followed by the navigation cookie if a `navigation_cookie_name` is set
+        and the CSRF cookie if a `csrf_cookie_name` is set.
+    */
+    pub fn create_login_cookies(&self, claims: &Claims) -> AuthResult<Vec<Cookie<'static>>> {
+        let csrf_token = self
+            .csrf_cookie_name
+            .as_ref()
+            .map(|_| generate_csrf_token(&*self.random_source));
+        let binding = TokenBinding {
+            fingerprint: None,
+            csrf_token: csrf_token.as_deref(),
+        };
+        let access_token = self.access_token(claims, binding)?;
+        let refresh_token = self.refresh_token(claims, binding, None)?;
+        let access_cookie = self.build_cookie(
+            self.access_token_name.clone(),
+            self.seal_cookie_token(access_token),
+            self.access_token_lifetime,
+        );
+        let refresh_cookie = self.build_refresh_cookie(
+            self.seal_cookie_token(refresh_token),
             self.refresh_token_lifetime,
+        );
+        let navigation_cookie = self.navigation_cookie(&access_cookie);
+
+        let mut cookies = vec![access_cookie, refresh_cookie];
+        cookies.extend(navigation_cookie);
+        cookies.extend(csrf_token.and_then(|csrf_token| self.csrf_cookie(csrf_token)));
+        Ok(cookies)
+    }
+
+    /*
+        Creates the CSRF cookie carrying the CSRF token the `req` was authenticated with, if a `csrf_cookie_name` is set.
+
+        It lives as long as the refresh token, so it has to be set again whenever the refresh token is renewed.
+    */
+    pub(crate) fn csrf_cookie_for_request(&self, req: &HttpRequest) -> Option<Cookie<'static>> {
+        self.csrf_cookie(request_csrf_token(req)?)
+    }
+
+    fn csrf_cookie(&self, csrf_token: String) -> Option<Cookie<'static>> {
+        let csrf_cookie_name = self.csrf_cookie_name.as_ref()?;
+        let mut csrf_cookie = self.build_cookie(
+            csrf_cookie_name.clone(),
+            csrf_token,
+            self.refresh_token_lifetime,
+        );
+        csrf_cookie.set_http_only(false);
+        Some(csrf_cookie)
+    }
+
+    /**
+        Creates a access token cookie bound to the device the `req` originates from.
+
+        The fingerprint computed by the `fingerprint_extractor` is stored hashed in the `fgp` claim of the token.
+        If the `req` was authenticated with a token carrying a CSRF token, the new token carries it as well.
+        If no `fingerprint_extractor` is set, this behaves like [`Self::create_access_cookie`].
+    */
+    pub fn create_access_cookie_for_request(
+        &self,
+        claims: &Claims,
+        req: &HttpRequest,
+    ) -> AuthResult<Cookie<'static>> {
+        let token = self.access_token_for_request(claims, req)?;
+        Ok(self.access_cookie_from_token(token))
+    }
+
+    /*
+        Creates a access token bound to the device the `req` originates from, like [`Self::create_access_cookie_for_request`].
+    */
+    pub(crate) fn access_token_for_request(
+        &self,
+        claims: &Claims,
+        req: &HttpRequest,
+    ) -> AuthResult<String> {
+        let fingerprint = self.request_fingerprint(req);
+        let csrf_token = request_csrf_token(req);
+        self.access_token(
+            claims,
+            TokenBinding {
+                fingerprint: fingerprint.as_deref(),
+                csrf_token: csrf_token.as_deref(),
+            },
+        )
+    }
+
+    /**
+        Creates a refresh token cookie bound to the device the `req` originates from.
+
+        If no `fingerprint_extractor` is set, this behaves like [`Self::create_refresh_cookie`].
+    */
+    pub fn create_refresh_cookie_for_request(
+        &self,
+        claims: &Claims,
+        req: &HttpRequest,
+    ) -> AuthResult<Cookie<'static>> {
+        let token = self.renewed_refresh_token_for_request(claims, req, None)?;
+        Ok(self.refresh_cookie_from_token(token))
+    }
+
+    /*
+        Creates a refresh token bound to the device the `req` originates from,
+        which keeps the `issued_at` of the refresh token it renews so the age of the session is still measured from the login.
+    */
+    pub(crate) fn renewed_refresh_token_for_request(
+        &self,
+        claims: &Claims,
+        req: &HttpRequest,
+        issued_at: Option<DateTime<Utc>>,
+    ) -> AuthResult<String> {
+        let fingerprint = self.request_fingerprint(req);
+        let csrf_token = request_csrf_token(req);
+        self.refresh_token(
+            claims,
+            TokenBinding {
+                fingerprint: fingerprint.as_deref(),
+                csrf_token: csrf_token.as_deref(),
+            },
+            issued_at,
+        )
+    }
+
+    /*
+        Wraps an access token in a cookie, assuming it is valid for the `access_token_lifetime`.
+    */
+    pub(crate) fn access_cookie_from_token(&self, token: String) -> Cookie<'static> {
+        self.build_cookie(
+            self.access_token_name.clone(),
+            self.seal_cookie_token(token),
+            self.access_token_lifetime,
+        )
+    }
+
+    /*
+        Wraps a refresh token in a cookie, assuming it is valid for the `refresh_token_lifetime`.
+    */
+    pub(crate) fn refresh_cookie_from_token(&self, token: String) -> Cookie<'static> {
+        self.build_refresh_cookie(self.seal_cookie_token(token), self.refresh_token_lifetime)
+    }
+
+    fn request_fingerprint(&self, req: &HttpRequest) -> Option<String> {
+        self.fingerprint_extractor
+            .as_ref()
+            .map(|fingerprint_extractor| hash_fingerprint(&fingerprint_extractor(req)))
+    }
+
+    /*
+        Creates a access token whose `fgp` claim holds the `fingerprint_hash`.
+    */
+    pub(crate) fn fingerprinted_access_token(
+        &self,
+        claims: &Claims,
+        fingerprint_hash: &str,
+    ) -> AuthResult<String> {
+        self.access_token(
+            claims,
+            TokenBinding {
+                fingerprint: Some(fingerprint_hash),
+                csrf_token: None,
+            },
         )
     }
 
@@ -239,6 +934,7 @@ where
 
         Internally it calls [`Self::create_signed_token`] while
         passing the `claims` as well as the `token_lifetime`.
+        The attributes of the cookie are taken from the `cookie_builder` field on this struct.
 
         * `cookie_name` the name of the resulting cookie
     */
@@ -249,15 +945,173 @@ where
         token_lifetime: Duration,
     ) -> AuthResult<Cookie<'static>> {
         let token = self.create_signed_token(claims, token_lifetime)?;
-        Ok(Cookie::build(cookie_name.to_string(), token)
-            .same_site(SameSite::Strict)
-            .secure(true)
-            .finish())
+        Ok(self.build_cookie(
+            cookie_name.to_string(),
+            self.seal_cookie_token(token),
+            token_lifetime,
+        ))
+    }
+
+    /**
+        Creates a access token and splits it across multiple cookies if necessary.
+
+        Internally it calls [`Self::create_access_token`] and names the cookies after
+        the previously defined `access_token_name` value on this struct.
+    */
+    pub fn create_access_cookies_chunked(
+        &self,
+        claims: &Claims,
+    ) -> AuthResult<Vec<Cookie<'static>>> {
+        let token = self.create_access_token(claims)?;
+        Ok(self.chunk_cookies(
+            &self.access_token_name,
+            self.seal_cookie_token(token),
+            self.access_token_lifetime,
+        ))
+    }
+
+    /**
+        Creates a refresh token and splits it across multiple cookies if necessary.
+
+        The cookies are named after the previously defined `refresh_token_name` value on this struct,
+        the token is valid for the `refresh_token_lifetime` and signed with the `refresh_signing_key` if one is set.
+    */
+    pub fn create_refresh_cookies_chunked(
+        &self,
+        claims: &Claims,
+    ) -> AuthResult<Vec<Cookie<'static>>> {
+        let token = self.refresh_token(claims, TokenBinding::default(), None)?;
+        let mut cookies = self.chunk_cookies(
+            &self.refresh_token_name,
+            self.seal_cookie_token(token),
+            self.refresh_token_lifetime,
+        );
+        for cookie in &mut cookies {
+            self.set_refresh_cookie_path(cookie);
+        }
+        Ok(cookies)
+    }
+
+    /**
+        Creates a token and splits it across multiple cookies,
+        each holding at most `cookie_chunk_size` bytes of the token.
+
+        Browsers limit the size of a single cookie to roughly 4KB, so large tokens are stored in the cookies
+        `{cookie_name}.0`, `{cookie_name}.1`, ... which are stitched back together by the [`crate::Authority`].
+        If the token fits into a single cookie, a single cookie named `cookie_name` is returned.
+    */
+    pub fn create_cookies_chunked(
+        &self,
+        claims: &Claims,
+        cookie_name: &str,
+        token_lifetime: Duration,
+    ) -> AuthResult<Vec<Cookie<'static>>> {
+        let token = self.create_signed_token(claims, token_lifetime)?;
+        Ok(self.chunk_cookies(cookie_name, self.seal_cookie_token(token), token_lifetime))
+    }
+
+    fn chunk_cookies(
+        &self,
+        cookie_name: &str,
+        token: String,
+        token_lifetime: Duration,
+    ) -> Vec<Cookie<'static>> {
+        if token.len() <= self.cookie_chunk_size {
+            return vec![self.build_cookie(cookie_name.to_string(), token, token_lifetime)];
+        }
+
+        token
+            .as_bytes()
+            .chunks(self.cookie_chunk_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                self.build_cookie(
+                    format!("{cookie_name}.{index}"),
+                    String::from_utf8(chunk.to_vec())
+                        .expect("Tokens only consist of ASCII characters"),
+                    token_lifetime,
+                )
+            })
+            .collect()
+    }
+
+    /*
+        Encrypts the token if an `encryption_key` is set and encodes it if `encode_cookie_value` is set.
+    */
+    fn seal_cookie_token(&self, token: String) -> String {
+        #[cfg(feature = "jwe")]
+        let token = match self.encryption_key {
+            Some(ref encryption_key) => encryption_key.encrypt(&token),
+            None => token,
+        };
+        if self.encode_cookie_value {
+            URL_SAFE_NO_PAD.encode(token)
+        } else {
+            token
+        }
+    }
+
+    /**
+        Serializes the `cookie` as the value of a `Set-Cookie` header,
+        appending the `Priority` and `Partitioned` attributes configured on this struct.
+
+        Please use this instead of [`actix_web::HttpResponseBuilder::cookie`] if either of the attributes is configured.
+    */
+    pub fn set_cookie_header_value(&self, cookie: &Cookie<'_>) -> HeaderValue {
+        let mut header_value = cookie.to_string();
+        if let Some(cookie_priority) = self.cookie_priority {
+            header_value.push_str("; Priority=");
+            header_value.push_str(cookie_priority.as_str());
+        }
+        if self.cookie_partitioned {
+            header_value.push_str("; Partitioned");
+        }
+        HeaderValue::from_str(&header_value)
+            .expect("Cookie should not contain ASCII characters (33-127)")
+    }
+
+    fn build_cookie(
+        &self,
+        cookie_name: String,
+        token: String,
+        token_lifetime: Duration,
+    ) -> Cookie<'static> {
+        let mut cookie = self.cookie_builder.clone().finish();
+        cookie.set_name(prefixed_cookie_name(
+            self.cookie_name_prefix.as_deref(),
+            &cookie_name,
+        ));
+        cookie.set_value(token);
+        if let Some(ref cookie_domain) = self.cookie_domain {
+            cookie.set_domain(cookie_domain.clone());
+        }
+        if self.cookie_max_age_from_token_lifetime
+            && cookie.max_age().is_none()
+            && cookie.expires().is_none()
+        {
+            cookie.set_max_age(CookieDuration::try_from(token_lifetime).ok());
+        }
+        cookie
+    }
+
+    /*
+        Builds a refresh token cookie, restricted to the `refresh_cookie_path` if one is set.
+    */
+    fn build_refresh_cookie(&self, token: String, token_lifetime: Duration) -> Cookie<'static> {
+        let mut cookie = self.build_cookie(self.refresh_token_name.clone(), token, token_lifetime);
+        self.set_refresh_cookie_path(&mut cookie);
+        cookie
+    }
+
+    fn set_refresh_cookie_path(&self, cookie: &mut Cookie<'static>) {
+        if let Some(ref refresh_cookie_path) = self.refresh_cookie_path {
+            cookie.set_path(refresh_cookie_path.clone());
+        }
     }
 
     /**
         Creates a signed token using the previously defined
-        [`TimeOptions`], [`Header`] and [`jwt_compact::Algorithm::SigningKey`]
+        [`crate::time::SharedTimeOptions`], [`Header`] and [`jwt_compact::Algorithm::SigningKey`]
         values on this struct.
 
         * `claims` reference to an object of the generic type `Claims` which will be incorporated inside of the JWT string
@@ -269,14 +1123,553 @@ where
         claims: &Claims,
         token_lifetime: Duration,
     ) -> AuthResult<String> {
-        let token_claims = TokenClaims::new(claims).set_duration_and_issuance(
-            &self.time_options,
-            TimeDelta::from_std(token_lifetime).unwrap(),
-        );
+        self.sign_token(claims, token_lifetime, None, None, TokenKind::Access)
+    }
+
+    /**
+        Creates a signed token like [`TokenSigner::create_signed_token`],
+        but with claims only known at runtime instead of the generic `Claims`.
+
+        This is useful for gateways passing on claims they do not statically model.
+        The [`crate::Authority`] still has to be able to deserialize the claims into its own `Claims` type.
+
+        * `claims` JSON object which will be incorporated inside of the JWT string
+
+        * `token_lifetime` duration for which the token is valid for
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::TokenSigner;
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        # use serde::Serialize;
+        # use std::time::Duration;
+        # #[derive(Serialize)]
+        # struct User {
+        #     id: u32,
+        # }
+        let token_signer = TokenSigner::<User, _>::new()
+            .signing_key(KeyPair::generate().sk)
+            .algorithm(Ed25519)
+            .build()
+            .unwrap();
+
+        let token = token_signer
+            .create_signed_token_value(
+                &serde_json::json!({ "id": 1, "tenant": "acme" }),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        ```
+    */
+    pub fn create_signed_token_value(
+        &self,
+        claims: &Value,
+        token_lifetime: Duration,
+    ) -> AuthResult<String> {
+        self.sign_token(claims, token_lifetime, None, None, TokenKind::Access)
+    }
 
-        self.algorithm
-            .token(&self.header, &token_claims, &self.signing_key)
-            .map_err(AuthError::TokenCreation)
+    /**
+        Creates a signed token from fully specified `token_claims`, keeping their `exp`, `nbf` and `iat` claims as they are.
+
+        Only the claims which are unset are filled in, a missing `iat` is set to the current time
+        and a missing `exp` to the `iat` plus the `access_token_lifetime`.
+        This is useful for replicating tokens issued by another system, for the common case please use [`TokenSigner::create_signed_token`].
+
+        # Example
+        ```rust
+        # use actix_jwt_auth_middleware::TokenSigner;
+        # use chrono::{TimeDelta, TimeZone, Utc};
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        # use jwt_compact::Claims;
+        # use serde::Serialize;
+        # #[derive(Serialize)]
+        # struct User {
+        #     id: u32,
+        # }
+        let token_signer = TokenSigner::<User, _>::new()
+            .signing_key(KeyPair::generate().sk)
+            .algorithm(Ed25519)
+            .build()
+            .unwrap();
+
+        let mut claims = Claims::new(User { id: 1 });
+        claims.issued_at = Some(Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+        claims.expiration = Some(Utc.timestamp_opt(1_700_003_600, 0).unwrap());
+
+        let token = token_signer.create_signed_token_with_claims(claims).unwrap();
+        ```
+    */
+    pub fn create_signed_token_with_claims(
+        &self,
+        mut token_claims: TokenClaims<Claims>,
+    ) -> AuthResult<String> {
+        let issued_at = *token_claims
+            .issued_at
+            .get_or_insert_with(|| self.time_options.now());
+        if token_claims.expiration.is_none() {
+            token_claims.expiration =
+                Some(expiration_after(issued_at, self.access_token_lifetime)?);
+        }
+        self.sign_token_claims(&token_claims, None, TokenKind::Access)
+    }
+
+    /**
+        Creates a signed access token valid for the previously defined `access_token_lifetime`.
+
+        If an `access_token_profile` is set, the token follows [RFC 9068](https://www.rfc-editor.org/rfc/rfc9068).
+    */
+    pub fn create_access_token(&self, claims: &Claims) -> AuthResult<String> {
+        self.access_token(claims, TokenBinding::default())
+    }
+
+    fn access_token(&self, claims: &Claims, binding: TokenBinding<'_>) -> AuthResult<String> {
+        match self.access_token_profile {
+            Some(ref access_token_profile) => self.fingerprinted_token(
+                &access_token_profile.claims(claims, &*self.random_source),
+                self.access_token_lifetime,
+                None,
+                Some(ACCESS_TOKEN_TYPE),
+                binding,
+                TokenKind::Access,
+            ),
+            None => self.fingerprinted_token(
+                claims,
+                self.access_token_lifetime,
+                None,
+                None,
+                binding,
+                TokenKind::Access,
+            ),
+        }
+    }
+
+    fn refresh_token(
+        &self,
+        claims: &Claims,
+        binding: TokenBinding<'_>,
+        issued_at: Option<DateTime<Utc>>,
+    ) -> AuthResult<String> {
+        self.fingerprinted_token(
+            claims,
+            self.refresh_token_lifetime,
+            issued_at,
+            None,
+            binding,
+            TokenKind::Refresh,
+        )
+    }
+
+    fn fingerprinted_token<C: Serialize>(
+        &self,
+        claims: &C,
+        token_lifetime: Duration,
+        issued_at: Option<DateTime<Utc>>,
+        token_type: Option<&str>,
+        binding: TokenBinding<'_>,
+        kind: TokenKind,
+    ) -> AuthResult<String> {
+        match (binding.csrf_token, binding.fingerprint) {
+            (Some(csrf_token), Some(fingerprint)) => self.sign_token(
+                &CsrfClaims::new(csrf_token, &FingerprintClaims::new(fingerprint, claims)),
+                token_lifetime,
+                issued_at,
+                token_type,
+                kind,
+            ),
+            (Some(csrf_token), None) => self.sign_token(
+                &CsrfClaims::new(csrf_token, claims),
+                token_lifetime,
+                issued_at,
+                token_type,
+                kind,
+            ),
+            (None, Some(fingerprint)) => self.sign_token(
+                &FingerprintClaims::new(fingerprint, claims),
+                token_lifetime,
+                issued_at,
+                token_type,
+                kind,
+            ),
+            (None, None) => self.sign_token(claims, token_lifetime, issued_at, token_type, kind),
+        }
+    }
+
+    /*
+        Signs the `claims` valid for the `token_lifetime` from now on,
+        the `iat` claim is set to `issued_at` instead of the current time if it is set.
+    */
+    fn sign_token<C: Serialize>(
+        &self,
+        claims: &C,
+        token_lifetime: Duration,
+        issued_at: Option<DateTime<Utc>>,
+        token_type: Option<&str>,
+        kind: TokenKind,
+    ) -> AuthResult<String> {
+        let now = self.time_options.now();
+        let mut token_claims = TokenClaims::new(claims);
+        token_claims.expiration = Some(expiration_after(now, token_lifetime)?);
+        token_claims.issued_at = Some(issued_at.unwrap_or(now));
+
+        self.sign_token_claims(&token_claims, token_type, kind)
+    }
+
+    /*
+        Signs the `token_claims` without touching their registered claims,
+        compressing the custom claims if `compress_claims` is set.
+    */
+    fn sign_token_claims<C: Serialize>(
+        &self,
+        token_claims: &TokenClaims<C>,
+        token_type: Option<&str>,
+        kind: TokenKind,
+    ) -> AuthResult<String> {
+        let header = self.token_header(token_type);
+
+        if self.compress_claims {
+            let mut compressed_claims = TokenClaims::new(compress_claims(&token_claims.custom)?);
+            compressed_claims.expiration = token_claims.expiration;
+            compressed_claims.not_before = token_claims.not_before;
+            compressed_claims.issued_at = token_claims.issued_at;
+
+            self.encode_token(&deflate_header(&header), &compressed_claims, kind)
+        } else {
+            self.encode_token(&header, token_claims, kind)
+        }
+    }
+
+    /*
+        Signs the `claims` serialized in the configured `claims_format`.
+    */
+    fn encode_token<H: Serialize, C: Serialize>(
+        &self,
+        header: &Header<H>,
+        claims: &TokenClaims<C>,
+        kind: TokenKind,
+    ) -> AuthResult<String> {
+        let (algorithm, signing_key) = self.signing_pair(kind);
+        match self.claims_format {
+            ClaimsFormat::Json => algorithm.token(header, claims, signing_key),
+            #[cfg(feature = "cbor")]
+            ClaimsFormat::Cbor => algorithm.compact_token(header, claims, signing_key),
+        }
+        .map_err(AuthError::TokenCreation)
+    }
+
+    /*
+        Returns the `header` with the `token_type` applied,
+        falling back to the `token_type` configured on this struct.
+    */
+    fn token_header(&self, token_type: Option<&str>) -> Cow<'_, Header> {
+        match token_type.or(self.token_type.as_deref()) {
+            Some(token_type) => Cow::Owned(self.header.clone().with_token_type(token_type)),
+            None => Cow::Borrowed(&self.header),
+        }
+    }
+}
+
+impl<Claims, Algo> TokenSigner<Claims, Algo>
+where
+    Algo: Algorithm,
+{
+    /*
+        Returns the algorithm and key used to sign tokens of the `kind`,
+        refresh tokens fall back to the access token algorithm and key.
+    */
+    fn signing_pair(&self, kind: TokenKind) -> (&Algo, &Algo::SigningKey) {
+        match kind {
+            TokenKind::Access => (&self.algorithm, &self.signing_key),
+            TokenKind::Refresh => (
+                self.refresh_algorithm.as_ref().unwrap_or(&self.algorithm),
+                self.refresh_signing_key
+                    .as_ref()
+                    .unwrap_or(&self.signing_key),
+            ),
+        }
+    }
+
+    /*
+        Returns whether a token of the `kind` signed by this struct is accepted by the `algorithm` using the `verifying_key`.
+    */
+    pub(crate) fn is_verified_by(
+        &self,
+        algorithm: &Algo,
+        verifying_key: &Algo::VerifyingKey,
+        kind: TokenKind,
+    ) -> bool {
+        let (signing_algorithm, signing_key) = self.signing_pair(kind);
+        is_verified_by(signing_algorithm, signing_key, algorithm, verifying_key)
+    }
+}
+
+/*
+    The values binding a token to the client it is issued to, embedded into the token as additional claims.
+*/
+#[derive(Clone, Copy, Default)]
+struct TokenBinding<'a> {
+    /* the hashed fingerprint of the device */
+    fingerprint: Option<&'a str>,
+    csrf_token: Option<&'a str>,
+}
+
+/*
+    Returns whether a token signed by the `signing_algorithm` using the `signing_key`
+    is accepted by the `algorithm` using the `verifying_key`.
+*/
+pub(crate) fn is_verified_by<Algo: Algorithm>(
+    signing_algorithm: &Algo,
+    signing_key: &Algo::SigningKey,
+    algorithm: &Algo,
+    verifying_key: &Algo::VerifyingKey,
+) -> bool {
+    let token = signing_algorithm
+        .token(&Header::empty(), &TokenClaims::empty(), signing_key)
+        .expect("Empty claims should always be serializable");
+    UntrustedToken::new(&token).is_ok_and(|untrusted_token| {
+        algorithm
+            .validator::<Empty>(verifying_key)
+            .validate(&untrusted_token)
+            .is_ok()
+    })
+}
+
+/*
+    Rejects access tokens living at least as long as refresh tokens,
+    which would expire the refresh token before the access token ever needs to be renewed through it.
+*/
+/*
+    Returns the point in time a token issued at `issued_at` expires after its `token_lifetime`,
+    failing instead of panicking if it lies beyond the range of [`DateTime`].
+*/
+fn expiration_after(
+    issued_at: DateTime<Utc>,
+    token_lifetime: Duration,
+) -> AuthResult<DateTime<Utc>> {
+    TimeDelta::from_std(token_lifetime)
+        .ok()
+        .and_then(|token_lifetime| issued_at.checked_add_signed(token_lifetime))
+        .ok_or_else(|| {
+            AuthError::TokenCreation(CreationError::Claims(
+                <serde_json::Error as serde::ser::Error>::custom(format!(
+                    "the token lifetime of {token_lifetime:?} exceeds the range of expiration times"
+                )),
+            ))
+        })
+}
+
+pub(crate) fn validate_token_lifetimes(
+    access_token_lifetime: Duration,
+    refresh_token_lifetime: Duration,
+) -> Result<(), String> {
+    for (name, token_lifetime) in [
+        ("access_token_lifetime", access_token_lifetime),
+        ("refresh_token_lifetime", refresh_token_lifetime),
+    ] {
+        if TimeDelta::from_std(token_lifetime).is_err() {
+            return Err(format!(
+                "The {name} ({token_lifetime:?}) exceeds the range of durations tokens can be valid for."
+            ));
+        }
+    }
+    if access_token_lifetime >= refresh_token_lifetime {
+        return Err(format!(
+            "The access_token_lifetime ({access_token_lifetime:?}) has to be shorter than the refresh_token_lifetime ({refresh_token_lifetime:?}), \
+            the refresh token is used to renew expired access tokens and would otherwise expire first."
+        ));
+    }
+    Ok(())
+}
+
+impl<Claims, Algo> TokenSignerBuilder<Claims, Algo>
+where
+    Algo: Algorithm,
+{
+    fn validate(&self) -> Result<(), String> {
+        validate_token_lifetimes(
+            self.access_token_lifetime
+                .unwrap_or(DEFAULT_ACCESS_TOKEN_LIFETIME),
+            self.refresh_token_lifetime
+                .unwrap_or(DEFAULT_REFRESH_TOKEN_LIFETIME),
+        )?;
+        if let Some(Some(ref cookie_domain)) = self.cookie_domain {
+            if cookie_domain.trim_start_matches('.').is_empty()
+                || cookie_domain.contains(['/', ':', ' ', ';'])
+            {
+                return Err(format!(
+                    "\"{cookie_domain}\" is not a valid cookie domain, expected a plain host name like \"example.com\""
+                ));
+            }
+        }
+        if let Some(Some(ref refresh_cookie_path)) = self.refresh_cookie_path {
+            if !refresh_cookie_path.starts_with('/') || refresh_cookie_path.contains(';') {
+                return Err(format!(
+                    "\"{refresh_cookie_path}\" is not a valid cookie path, expected an absolute path like \"/api/refresh\""
+                ));
+            }
+        }
+        if self.cookie_chunk_size == Some(0) {
+            return Err("The cookie_chunk_size has to be at least 1 byte.".into());
+        }
+        Ok(())
+    }
+}
+
+impl<Claims, Algo> TokenSignerBuilder<Claims, Algo>
+where
+    Claims: 'static,
+    Algo: Algorithm,
+{
+    /**
+        Sets an async function which is called in [`TokenSigner::create_access_cookie_async`]
+        to augment the claims before they are signed.
+
+        Returning the `Err` variant will abort the creation of the cookie with a wrapped [`AuthError::ClaimsEnricher`].
+        ```rust
+        # use actix_jwt_auth_middleware::TokenSigner;
+        # use serde::Serialize;
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        #[derive(Serialize)]
+        struct User {
+            id: u32,
+            roles: Vec<String>,
+        }
+
+        let token_signer = TokenSigner::<User, _>::new()
+            .signing_key(KeyPair::generate().sk)
+            .algorithm(Ed25519)
+            .claims_enricher(|mut user: User| async move {
+                // fetch the current roles of the user from a database
+                user.roles = vec!["admin".to_string()];
+                Ok(user)
+            })
+            .build()
+            .unwrap();
+        ```
+    */
+    pub fn claims_enricher<F, Fut>(mut self, claims_enricher: F) -> Self
+    where
+        F: Fn(Claims) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Claims, ActixWebError>> + 'static,
+    {
+        let claims_enricher: ClaimsEnricher<Claims> =
+            Arc::new(move |claims: Claims| -> ClaimsEnricherFuture<Claims> {
+                Box::pin(claims_enricher(claims))
+            });
+        self.claims_enricher = Some(Some(claims_enricher));
+        self
+    }
+
+    /**
+        Sets a function computing a fingerprint of the device a request originates from,
+        for example a hash of the `User-Agent` header and a random secret stored in a separate hardened cookie.
+
+        Tokens created by [`TokenSigner::create_access_cookie_for_request`] carry the hashed fingerprint,
+        the [`crate::Authority`] pulls this function from its `token_signer` and rejects tokens
+        presented with a different fingerprint with [`AuthError::FingerprintMismatch`].
+        This mitigates the theft of tokens as described by the
+        [OWASP JWT cheat sheet](https://cheatsheetseries.owasp.org/cheatsheets/JSON_Web_Token_for_Java_Cheat_Sheet.html#token-sidejacking).
+        ```rust
+        # use actix_jwt_auth_middleware::TokenSigner;
+        # use actix_web::http::header::USER_AGENT;
+        # use serde::Serialize;
+        # use ed25519_compact::KeyPair;
+        # use jwt_compact::alg::Ed25519;
+        #[derive(Serialize)]
+        struct User {
+            id: u32,
+        }
+
+        let token_signer = TokenSigner::<User, _>::new()
+            .signing_key(KeyPair::generate().sk)
+            .algorithm(Ed25519)
+            .fingerprint_extractor(|req| {
+                let user_agent = req
+                    .headers()
+                    .get(USER_AGENT)
+                    .and_then(|user_agent| user_agent.to_str().ok())
+                    .unwrap_or_default();
+                let secret = req
+                    .cookie("fingerprint_secret")
+                    .map(|cookie| cookie.value().to_string())
+                    .unwrap_or_default();
+                format!("{user_agent}{secret}")
+            })
+            .build()
+            .unwrap();
+        ```
+    */
+    pub fn fingerprint_extractor<F>(mut self, fingerprint_extractor: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> String + Send + Sync + 'static,
+    {
+        self.fingerprint_extractor = Some(Some(Arc::new(fingerprint_extractor)));
+        self
+    }
+
+    /**
+        Sets the source of the random bytes of the `jti` claim and of CSRF tokens.
+
+        Only meant for deterministic tests, production code should keep the default [`OsRandom`].
+        Please refer to [`RandomSource`] for an example.
+    */
+    pub fn random_source<R>(mut self, random_source: R) -> Self
+    where
+        R: RandomSource + 'static,
+    {
+        self.random_source = Some(Arc::new(random_source));
+        self
+    }
+}
+
+impl<Claims, Algo> TokenSigner<Claims, Algo>
+where
+    Algo: Algorithm + Clone,
+    Algo::SigningKey: Clone,
+{
+    /**
+        Returns a new [`TokenSigner`] for the claims type `Other`, sharing the keys and configuration of this one.
+
+        The `claims_enricher` is tied to the claims type and therefore not carried over.
+    */
+    pub fn with_claims<Other>(&self) -> TokenSigner<Other, Algo> {
+        TokenSigner {
+            access_token_name: Clone::clone(&self.access_token_name),
+            access_token_lifetime: Clone::clone(&self.access_token_lifetime),
+            refresh_token_name: Clone::clone(&self.refresh_token_name),
+            refresh_token_lifetime: Clone::clone(&self.refresh_token_lifetime),
+            header: Clone::clone(&self.header),
+            token_type: Clone::clone(&self.token_type),
+            access_token_profile: Clone::clone(&self.access_token_profile),
+            algorithm: Clone::clone(&self.algorithm),
+            signing_key: Clone::clone(&self.signing_key),
+            refresh_algorithm: Clone::clone(&self.refresh_algorithm),
+            refresh_signing_key: Clone::clone(&self.refresh_signing_key),
+            time_options: Clone::clone(&self.time_options),
+            cookie_builder: Clone::clone(&self.cookie_builder),
+            cookie_max_age_from_token_lifetime: Clone::clone(
+                &self.cookie_max_age_from_token_lifetime,
+            ),
+            compress_claims: Clone::clone(&self.compress_claims),
+            claims_format: Clone::clone(&self.claims_format),
+            cookie_chunk_size: Clone::clone(&self.cookie_chunk_size),
+            encode_cookie_value: Clone::clone(&self.encode_cookie_value),
+            cookie_domain: Clone::clone(&self.cookie_domain),
+            refresh_cookie_path: Clone::clone(&self.refresh_cookie_path),
+            cookie_name_prefix: Clone::clone(&self.cookie_name_prefix),
+            navigation_cookie_name: Clone::clone(&self.navigation_cookie_name),
+            csrf_cookie_name: Clone::clone(&self.csrf_cookie_name),
+            cookie_priority: Clone::clone(&self.cookie_priority),
+            cookie_partitioned: Clone::clone(&self.cookie_partitioned),
+            claims_enricher: None,
+            fingerprint_extractor: Clone::clone(&self.fingerprint_extractor),
+            encryption_key: Clone::clone(&self.encryption_key),
+            random_source: Clone::clone(&self.random_source),
+            claims_marker: PhantomData,
+        }
     }
 }
 
@@ -293,10 +1686,87 @@ where
             refresh_token_name: Clone::clone(&self.refresh_token_name),
             refresh_token_lifetime: Clone::clone(&self.refresh_token_lifetime),
             header: Clone::clone(&self.header),
+            token_type: Clone::clone(&self.token_type),
+            access_token_profile: Clone::clone(&self.access_token_profile),
             algorithm: Clone::clone(&self.algorithm),
             signing_key: Clone::clone(&self.signing_key),
+            refresh_algorithm: Clone::clone(&self.refresh_algorithm),
+            refresh_signing_key: Clone::clone(&self.refresh_signing_key),
             time_options: Clone::clone(&self.time_options),
+            cookie_builder: Clone::clone(&self.cookie_builder),
+            cookie_max_age_from_token_lifetime: Clone::clone(
+                &self.cookie_max_age_from_token_lifetime,
+            ),
+            compress_claims: Clone::clone(&self.compress_claims),
+            claims_format: Clone::clone(&self.claims_format),
+            cookie_chunk_size: Clone::clone(&self.cookie_chunk_size),
+            encode_cookie_value: Clone::clone(&self.encode_cookie_value),
+            cookie_domain: Clone::clone(&self.cookie_domain),
+            refresh_cookie_path: Clone::clone(&self.refresh_cookie_path),
+            cookie_name_prefix: Clone::clone(&self.cookie_name_prefix),
+            navigation_cookie_name: Clone::clone(&self.navigation_cookie_name),
+            csrf_cookie_name: Clone::clone(&self.csrf_cookie_name),
+            cookie_priority: Clone::clone(&self.cookie_priority),
+            cookie_partitioned: Clone::clone(&self.cookie_partitioned),
+            claims_enricher: Clone::clone(&self.claims_enricher),
+            fingerprint_extractor: Clone::clone(&self.fingerprint_extractor),
+            encryption_key: Clone::clone(&self.encryption_key),
+            random_source: Clone::clone(&self.random_source),
             claims_marker: Clone::clone(&self.claims_marker),
         }
     }
 }
+
+impl<Claims, Algo> fmt::Debug for TokenSigner<Claims, Algo>
+where
+    Algo: Algorithm,
+{
+    /**
+        Formats the [`TokenSigner`] while redacting the `signing_key`.
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenSigner")
+            .field("access_token_name", &self.access_token_name)
+            .field("access_token_lifetime", &self.access_token_lifetime)
+            .field("refresh_token_name", &self.refresh_token_name)
+            .field("refresh_token_lifetime", &self.refresh_token_lifetime)
+            .field("header", &self.header)
+            .field("token_type", &self.token_type)
+            .field("access_token_profile", &self.access_token_profile)
+            .field("algorithm", &self.algorithm.name())
+            .field("signing_key", &format_args!("<redacted>"))
+            .field(
+                "refresh_algorithm",
+                &self.refresh_algorithm.as_ref().map(Algorithm::name),
+            )
+            .field(
+                "refresh_signing_key",
+                &self.refresh_signing_key.as_ref().map(|_| "<redacted>"),
+            )
+            .field("time_options", &self.time_options)
+            .field("cookie_builder", &self.cookie_builder)
+            .field(
+                "cookie_max_age_from_token_lifetime",
+                &self.cookie_max_age_from_token_lifetime,
+            )
+            .field("compress_claims", &self.compress_claims)
+            .field("claims_format", &self.claims_format)
+            .field("cookie_chunk_size", &self.cookie_chunk_size)
+            .field("encode_cookie_value", &self.encode_cookie_value)
+            .field("cookie_domain", &self.cookie_domain)
+            .field("refresh_cookie_path", &self.refresh_cookie_path)
+            .field("cookie_name_prefix", &self.cookie_name_prefix)
+            .field("navigation_cookie_name", &self.navigation_cookie_name)
+            .field("csrf_cookie_name", &self.csrf_cookie_name)
+            .field("cookie_priority", &self.cookie_priority)
+            .field("cookie_partitioned", &self.cookie_partitioned)
+            .field("claims_enricher", &self.claims_enricher.is_some())
+            .field(
+                "fingerprint_extractor",
+                &self.fingerprint_extractor.is_some(),
+            )
+            .field("encryption_key", &self.encryption_key)
+            .field("random_source", &self.random_source)
+            .finish()
+    }
+}
@@ -0,0 +1,77 @@
+use crate::AuthError;
+use crate::AuthResult;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+type TokenVersionCheckFuture = Pin<Box<dyn Future<Output = AuthResult<()>>>>;
+
+pub(crate) type TokenVersionCheck<Claims> =
+    Arc<dyn Fn(&Claims) -> TokenVersionCheckFuture + Send + Sync>;
+
+/**
+    Implemented by claims which carry a version, used by the [`crate::Authority`] to invalidate
+    every outstanding token of a subject at once.
+
+    The version is part of the claims, so the [`crate::TokenSigner`] embeds it into every token it signs
+    and renewed tokens keep the version of the refresh token they were renewed from.
+    Bumping the version of a subject, for example stored alongside the user in a database,
+    logs the subject out everywhere without having to keep track of the issued tokens.
+
+    Please refer to [`crate::AuthorityBuilder::min_token_version`] for more details.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::TokenVersion;
+    # use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Clone)]
+    struct User {
+        sub: String,
+        token_version: u64,
+    }
+
+    impl TokenVersion for User {
+        fn subject(&self) -> String {
+            self.sub.clone()
+        }
+
+        fn token_version(&self) -> u64 {
+            self.token_version
+        }
+    }
+    ```
+*/
+pub trait TokenVersion {
+    /**
+        Returns the subject whose minimal token version is looked up.
+    */
+    fn subject(&self) -> String;
+
+    /**
+        Returns the version the token was issued with.
+    */
+    fn token_version(&self) -> u64;
+}
+
+/*
+    Wraps the `min_token_version` lookup into a check rejecting claims with an outdated version.
+*/
+pub(crate) fn token_version_check<Claims, F, Fut>(min_token_version: F) -> TokenVersionCheck<Claims>
+where
+    Claims: TokenVersion,
+    F: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = u64> + 'static,
+{
+    Arc::new(move |claims: &Claims| -> TokenVersionCheckFuture {
+        let token_version = claims.token_version();
+        let min_token_version = min_token_version(claims.subject());
+        Box::pin(async move {
+            if token_version < min_token_version.await {
+                Err(AuthError::TokenSuperseded)
+            } else {
+                Ok(())
+            }
+        })
+    })
+}
@@ -0,0 +1,38 @@
+use crate::validate::deserialize_claims_unchecked;
+use crate::AuthResult;
+
+use serde::de::DeserializeOwned;
+
+/**
+    Decodes the custom claims of the `token` **without verifying its signature or expiration**.
+
+    The returned claims are completely untrusted, anyone can craft a token carrying arbitrary claims.
+    Only use them for logging or debugging, for example to log the `sub` of incoming requests in a layer above the [`crate::Authority`],
+    never for any authentication or authorization decision.
+    Compressed and `CBOR` serialized claims are decoded as well, encrypted token cookies can not be decoded.
+    Compressed claims inflating to more than 64 KiB are rejected, so crafted tokens can not exhaust the memory of the server.
+
+    Returns an error if the `token` is malformed or its claims can not be deserialized into `Claims`.
+
+    # Example
+    ```rust
+    # use actix_jwt_auth_middleware::peek_unverified_claims;
+    # use serde::Deserialize;
+    #[derive(Deserialize)]
+    struct Subject {
+        sub: String,
+    }
+
+    fn log_subject(token: &str) {
+        if let Ok(Subject { sub }) = peek_unverified_claims::<Subject>(token) {
+            println!("request claiming to be from {sub}");
+        }
+    }
+    ```
+*/
+pub fn peek_unverified_claims<Claims>(token: &str) -> AuthResult<Claims>
+where
+    Claims: DeserializeOwned,
+{
+    deserialize_claims_unchecked(token).map(|claims| claims.custom)
+}
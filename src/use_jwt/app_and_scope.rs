@@ -1,5 +1,8 @@
 use crate::AuthenticationService;
 use crate::Authority;
+use crate::DynAuthority;
+
+use std::sync::Arc;
 
 use actix_web::dev::ServiceFactory;
 use actix_web::dev::ServiceRequest;
@@ -37,6 +40,21 @@ macro_rules! impl_use_jwt_for {
                 authority: Authority<Claims, Algo, ReAuth, Args>,
                 scope: Scope,
             ) -> Self;
+
+            /**
+                Behaves like [`Self::use_jwt`], but additionally adds the `authority` as app data in form of a [`DynAuthority`],
+                so handlers can extract it through [`Data<DynAuthority<Claims>>`](Data) to verify tokens explicitly.
+
+                Sharing the `authority` requires it to be `Send + Sync`.
+            */
+            fn use_jwt_with_authority_data(
+                self,
+                authority: Authority<Claims, Algo, ReAuth, Args>,
+                scope: Scope,
+            ) -> Self
+            where
+                ReAuth: 'static,
+                Authority<Claims, Algo, ReAuth, Args>: Send + Sync;
         }
 
         impl<Claims, Algo, ReAuth, Args, T> $trait_name<Claims, Algo, ReAuth, Args> for $type<T>
@@ -60,6 +78,25 @@ macro_rules! impl_use_jwt_for {
                 }
                 .service(scope.wrap(AuthenticationService::new(authority)))
             }
+
+            fn use_jwt_with_authority_data(
+                self,
+                authority: Authority<Claims, Algo, ReAuth, Args>,
+                scope: Scope,
+            ) -> Self
+            where
+                ReAuth: 'static,
+                Authority<Claims, Algo, ReAuth, Args>: Send + Sync,
+            {
+                let authority = Arc::new(authority);
+                if let Some(token_signer) = authority.token_signer() {
+                    self.app_data(Data::new(token_signer))
+                } else {
+                    self
+                }
+                .app_data(Data::new(DynAuthority::from_shared(Arc::clone(&authority))))
+                .service(scope.wrap(AuthenticationService::from_shared(authority)))
+            }
         }
     };
 }
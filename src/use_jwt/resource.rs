@@ -1,9 +1,13 @@
 use crate::AuthenticationService;
 use crate::Authority;
+use crate::DynAuthority;
+
+use std::sync::Arc;
 
 use actix_web::dev::ServiceFactory;
 use actix_web::dev::ServiceRequest;
 use actix_web::dev::ServiceResponse;
+use actix_web::web::Data;
 use actix_web::Error as ActixWebError;
 use actix_web::FromRequest;
 use actix_web::Handler;
@@ -43,6 +47,27 @@ where
             InitError = (),
         >,
     >;
+
+    /**
+        Behaves like [`Self::use_jwt`], but additionally adds the `authority` as app data in form of a [`DynAuthority`],
+        so handlers can extract it through [`Data<DynAuthority<Claims>>`](Data) to verify tokens explicitly.
+
+        Sharing the `authority` requires it to be `Send + Sync`.
+    */
+    fn use_jwt_with_authority_data(
+        self,
+        authority: Authority<Claims, Algorithm, ReAuth, Args>,
+    ) -> Resource<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse,
+            Error = ActixWebError,
+            InitError = (),
+        >,
+    >
+    where
+        Authority<Claims, Algorithm, ReAuth, Args>: Send + Sync;
 }
 
 impl<Claims, Algorithm, ReAuth, Args> UseJWTOnResource<Claims, Algorithm, ReAuth, Args> for Resource
@@ -67,4 +92,24 @@ where
     > {
         self.wrap(AuthenticationService::new(authority))
     }
+
+    fn use_jwt_with_authority_data(
+        self,
+        authority: Authority<Claims, Algorithm, ReAuth, Args>,
+    ) -> Resource<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse,
+            Error = ActixWebError,
+            InitError = (),
+        >,
+    >
+    where
+        Authority<Claims, Algorithm, ReAuth, Args>: Send + Sync,
+    {
+        let authority = Arc::new(authority);
+        self.app_data(Data::new(DynAuthority::from_shared(Arc::clone(&authority))))
+            .wrap(AuthenticationService::from_shared(authority))
+    }
 }
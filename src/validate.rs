@@ -1,37 +1,183 @@
+use crate::claims_format::check_claims_format;
+use crate::claims_format::ClaimsFormat;
+use crate::compression::decompress_claims;
+use crate::compression::CompressedClaims;
+use crate::compression::CompressionHeader;
+use crate::time::SharedTimeOptions;
 use crate::AuthError;
 use crate::AuthResult;
+use crate::TokenTiming;
 
 use jwt_compact::Algorithm;
 use jwt_compact::AlgorithmExt;
-use jwt_compact::TimeOptions;
-use jwt_compact::Token;
+use jwt_compact::Claims as TokenClaims;
+use jwt_compact::Empty;
 use jwt_compact::UntrustedToken;
+use jwt_compact::ValidationError;
 use serde::de::DeserializeOwned;
 
+use std::time::Duration;
+
+/*
+    The checks [`validate_jwt`] applies to a token after verifying its signature.
+*/
+pub(crate) struct ValidationOptions<'a> {
+    pub(crate) time_options: &'a SharedTimeOptions,
+    pub(crate) claims_format: ClaimsFormat,
+    pub(crate) expected_token_type: Option<&'a str>,
+    pub(crate) max_age: Option<Duration>,
+    pub(crate) require_expiration: bool,
+}
+
 pub(crate) fn validate_jwt<T, Algo, Claims>(
     value: &T,
     algorithm: &Algo,
     verifying_key: &Algo::VerifyingKey,
-    time_options: &TimeOptions,
-) -> AuthResult<Token<Claims>>
+    options: &ValidationOptions<'_>,
+) -> AuthResult<TokenClaims<Claims>>
+where
+    T: AsRef<str>,
+    Algo: Algorithm,
+    Claims: DeserializeOwned,
+{
+    let claims = verify_jwt_signature(
+        value,
+        algorithm,
+        verifying_key,
+        options.claims_format,
+        options.expected_token_type,
+    )?;
+    validate_lifetime(
+        &claims,
+        options.time_options,
+        options.max_age,
+        options.require_expiration,
+    )?;
+    Ok(claims)
+}
+
+/*
+    Checks the expiration of a token whose signature was already verified and,
+    if a `max_age` is set, that it was not issued longer ago.
+    Tokens without an `exp` claim are rejected by jwt-compact, unless `require_expiration` is unset,
+    expired tokens are rejected together with their [`TokenTiming`] to diagnose clock skew.
+*/
+pub(crate) fn validate_lifetime<Claims>(
+    claims: &TokenClaims<Claims>,
+    time_options: &SharedTimeOptions,
+    max_age: Option<Duration>,
+    require_expiration: bool,
+) -> AuthResult<()> {
+    if claims.expiration.is_some() || require_expiration {
+        claims
+            .validate_expiration(&time_options.as_time_options())
+            .map_err(|err| match err {
+                ValidationError::Expired => AuthError::TokenValidation(
+                    err,
+                    Some(TokenTiming {
+                        expiration: claims.expiration,
+                        not_before: claims.not_before,
+                        now: time_options.now(),
+                    }),
+                ),
+                err => err.into(),
+            })?;
+    }
+    if let Some(max_age) = max_age {
+        validate_age(claims, time_options, max_age)?;
+    }
+    Ok(())
+}
+
+/*
+    Checks that the token was issued at most `max_age` ago, independent of its expiration.
+    Tokens without an `iat` claim can not be bounded and are rejected.
+*/
+pub(crate) fn validate_age<Claims>(
+    claims: &TokenClaims<Claims>,
+    time_options: &SharedTimeOptions,
+    max_age: Duration,
+) -> AuthResult<()> {
+    let issued_at = claims.issued_at.ok_or(AuthError::MissingClaim("iat"))?;
+    let age = (time_options.now() - issued_at)
+        .to_std()
+        .unwrap_or_default();
+    if age > max_age {
+        return Err(AuthError::SessionExpired);
+    }
+    Ok(())
+}
+
+/*
+    Validates the `alg` header, signature, claims format and `typ` header of a token without checking its expiration,
+    decompressing the claims if necessary.
+
+    jwt-compact rejects tokens whose `alg` header does not name the configured algorithm exactly before verifying the signature,
+    which rules out algorithm confusion, like tokens signed with `HS256` using a public key as their secret.
+*/
+pub(crate) fn verify_jwt_signature<T, Algo, Claims>(
+    value: &T,
+    algorithm: &Algo,
+    verifying_key: &Algo::VerifyingKey,
+    claims_format: ClaimsFormat,
+    expected_token_type: Option<&str>,
+) -> AuthResult<TokenClaims<Claims>>
 where
     T: AsRef<str>,
     Algo: Algorithm,
     Claims: DeserializeOwned,
 {
-    match UntrustedToken::new(&value) {
-        Ok(untrusted_token) => {
-            match algorithm
-                .validator(verifying_key)
-                .validate(&untrusted_token)
-            {
-                Ok(token) => match token.claims().validate_expiration(time_options) {
-                    Ok(_) => Ok(token),
-                    Err(err) => Err(AuthError::TokenValidation(err)),
-                },
-                Err(err) => Err(err.into()),
-            }
+    let untrusted_token = UntrustedToken::<CompressionHeader>::try_from(value.as_ref())?;
+    check_claims_format(value.as_ref(), claims_format)?;
+    let claims = if untrusted_token.header().other_fields.is_deflated() {
+        let (_, compressed_claims) = algorithm
+            .validator::<CompressedClaims>(verifying_key)
+            .validate(&untrusted_token)?
+            .into_parts();
+        decompress_claims(compressed_claims)?
+    } else {
+        algorithm
+            .validator::<Empty>(verifying_key)
+            .validate(&untrusted_token)?;
+        untrusted_token
+            .deserialize_claims_unchecked::<Claims>()
+            .map_err(map_malformed_claims)?
+    };
+    if let Some(expected_token_type) = expected_token_type {
+        if untrusted_token.header().token_type.as_deref() != Some(expected_token_type) {
+            return Err(AuthError::TokenTypeMismatch);
         }
-        Err(err) => Err(err.into()),
+    }
+    Ok(claims)
+}
+
+/*
+    jwt-compact deserializes the claims before verifying the signature,
+    so the signature is verified against the registered claims alone and the `Claims` are deserialized afterwards.
+    Malformed claims then point to a token whose claims do not match the `Claims` type, instead of a forged token.
+*/
+fn map_malformed_claims(err: ValidationError) -> AuthError {
+    match err {
+        ValidationError::MalformedClaims(_) => AuthError::ClaimsDeserialization(err),
+        #[cfg(feature = "cbor")]
+        ValidationError::MalformedCborClaims(_) => AuthError::ClaimsDeserialization(err),
+        err => err.into(),
+    }
+}
+
+/*
+    Deserializes the claims of a token without validating its signature,
+    decompressing them if necessary.
+*/
+pub(crate) fn deserialize_claims_unchecked<T, Claims>(value: &T) -> AuthResult<TokenClaims<Claims>>
+where
+    T: AsRef<str> + ?Sized,
+    Claims: DeserializeOwned,
+{
+    let untrusted_token = UntrustedToken::<CompressionHeader>::try_from(value.as_ref())?;
+    if untrusted_token.header().other_fields.is_deflated() {
+        decompress_claims(untrusted_token.deserialize_claims_unchecked::<CompressedClaims>()?)
+    } else {
+        Ok(untrusted_token.deserialize_claims_unchecked::<Claims>()?)
     }
 }
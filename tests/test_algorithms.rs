@@ -0,0 +1,230 @@
+use actix_jwt_auth_middleware::{AuthError, Authority, DeriveHmacKey, TokenSigner};
+use actix_web::cookie::Cookie;
+use actix_web::test::TestRequest;
+use jwt_compact::alg::{Hs256, Hs256Key};
+use jwt_compact::TimeOptions;
+use jwt_compact::ValidationError::InvalidSignature;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct TestClaims {}
+
+const HS256_SECRET: &[u8] = b"a-secret-that-is-long-enough-for-hs256";
+
+fn hs256_token_signer() -> TokenSigner<TestClaims, Hs256> {
+    TokenSigner::new()
+        .algorithm(Hs256)
+        .signing_key(Hs256Key::new(HS256_SECRET))
+        .build()
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn hs256_valid_access_token() {
+    let token_signer = hs256_token_signer();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(Hs256Key::new(HS256_SECRET))
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok())
+}
+
+/*
+    The signature comparison of symmetric algorithms happens in constant time inside of jwt-compact,
+    this test makes sure a tampered signature is rejected through that path.
+*/
+#[actix_web::test]
+async fn hs256_tampered_signature() {
+    let token_signer = hs256_token_signer();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(Hs256Key::new(HS256_SECRET))
+        .token_signer(Some(token_signer.clone()))
+        .renew_access_token_automatically(false)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    let (message, signature) = cookie.value().rsplit_once('.').unwrap();
+    let tampered_first_char = if signature.starts_with('A') { 'B' } else { 'A' };
+    let tampered_token = format!("{message}.{tampered_first_char}{}", &signature[1..]);
+
+    let mut req = TestRequest::default()
+        .cookie(Cookie::new("access_token", tampered_token))
+        .to_srv_request();
+
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing tampered signature case"),
+        AuthError::TokenValidation(InvalidSignature, None)
+    )
+}
+
+#[actix_web::test]
+async fn hs256_key_derived_from_passphrase() {
+    const SALT: &[u8] = b"sixteen byte salt";
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Hs256)
+        .signing_key(Hs256Key::derive_from_passphrase(b"passphrase", SALT).unwrap())
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(Hs256Key::derive_from_passphrase(b"passphrase", SALT).unwrap())
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+    assert!(matches!(
+        Hs256Key::derive_from_passphrase(b"passphrase", b"short salt"),
+        Err(AuthError::KeyLoad(_))
+    ));
+}
+
+#[actix_web::test]
+async fn algorithm_mismatch() {
+    use ed25519_compact::KeyPair;
+    use jwt_compact::alg::Ed25519;
+
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KeyPair::generate().pk)
+        .time_options(TimeOptions::default())
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(
+            hs256_token_signer()
+                .create_access_cookie(&TestClaims {})
+                .unwrap(),
+        )
+        .to_srv_request();
+
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .unwrap_err(),
+        AuthError::AlgorithmMismatch
+    );
+}
+
+#[actix_web::test]
+async fn keys_from_env() {
+    use actix_jwt_auth_middleware::KeyEncoding;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use ed25519_compact::KeyPair;
+    use jwt_compact::alg::Ed25519;
+
+    let key_pair = KeyPair::generate();
+    std::env::set_var("KEYS_FROM_ENV_SIGNING_KEY", STANDARD.encode(*key_pair.sk));
+    std::env::set_var("KEYS_FROM_ENV_VERIFYING_KEY", STANDARD.encode(*key_pair.pk));
+    std::env::set_var(
+        "KEYS_FROM_ENV_HMAC_KEY",
+        HS256_SECRET
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<String>(),
+    );
+    std::env::set_var("KEYS_FROM_ENV_MALFORMED_KEY", "not a key");
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key_from_env("KEYS_FROM_ENV_SIGNING_KEY", KeyEncoding::Base64)
+        .unwrap()
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key_from_env("KEYS_FROM_ENV_VERIFYING_KEY", KeyEncoding::Base64)
+        .unwrap()
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Hs256)
+        .verifying_key_from_env("KEYS_FROM_ENV_HMAC_KEY", KeyEncoding::Hex)
+        .unwrap()
+        .time_options(TimeOptions::default())
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(
+            hs256_token_signer()
+                .create_access_cookie(&TestClaims {})
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    for (name, encoding) in [
+        ("KEYS_FROM_ENV_MISSING_KEY", KeyEncoding::Base64),
+        ("KEYS_FROM_ENV_MALFORMED_KEY", KeyEncoding::Base64),
+        ("KEYS_FROM_ENV_MALFORMED_KEY", KeyEncoding::Hex),
+        ("KEYS_FROM_ENV_HMAC_KEY", KeyEncoding::Base64),
+    ] {
+        assert!(matches!(
+            TokenSigner::<TestClaims, Ed25519>::new().signing_key_from_env(name, encoding),
+            Err(AuthError::KeyLoad(_))
+        ));
+    }
+}
+
+#[cfg(feature = "rsa")]
+#[actix_web::test]
+async fn rs256_valid_access_token() {
+    use actix_jwt_auth_middleware::FromPem;
+    use jwt_compact::alg::{Rsa, RsaPrivateKey, RsaPublicKey};
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Rsa::rs256())
+        .signing_key(RsaPrivateKey::from_pem(include_str!("keys/rsa_private.pem")).unwrap())
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(RsaPublicKey::from_pem(include_str!("keys/rsa_public.pem")).unwrap())
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+    assert_eq!(
+        RsaPrivateKey::from_pem(include_str!("keys/rsa_private_pkcs1.pem")).unwrap(),
+        RsaPrivateKey::from_pem(include_str!("keys/rsa_private.pem")).unwrap()
+    );
+    assert!(matches!(
+        RsaPublicKey::from_pem("not a pem encoded key"),
+        Err(AuthError::KeyLoad(_))
+    ));
+}
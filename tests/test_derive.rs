@@ -0,0 +1,14 @@
+#[test]
+fn derive_with_renamed_actix_web() {
+    trybuild::TestCases::new().pass("tests/ui/renamed_actix_web.rs");
+}
+
+#[test]
+fn derive_for_keyword_type_names() {
+    trybuild::TestCases::new().pass("tests/ui/keyword_type_names.rs");
+}
+
+#[test]
+fn derive_with_mapped_claims() {
+    trybuild::TestCases::new().pass("tests/ui/mapped_claims.rs");
+}
@@ -0,0 +1,1925 @@
+use actix_jwt_auth_middleware::use_jwt::UseJWTOnApp;
+#[cfg(feature = "reqwest")]
+use actix_jwt_auth_middleware::IntrospectionAuthority;
+#[cfg(feature = "jwe")]
+use actix_jwt_auth_middleware::TokenEncryptionKey;
+use actix_jwt_auth_middleware::{
+    extract_claims, require_role, AuthError, AuthGuard, AuthenticatedClaims, AuthenticationService,
+    Authority, DynAuthority, DynAuthorityConfig, FromRequest, HasRole, ScopedClaims, SharedClaims,
+    Subject, TokenDelivery, TokenSigner, TokenSource,
+};
+#[cfg(feature = "problem-details")]
+use actix_web::body::to_bytes;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::error::InternalError;
+#[cfg(feature = "problem-details")]
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::http::header::{
+    HeaderName, HeaderValue, ACCEPT, CACHE_CONTROL, LOCATION, PRAGMA, SET_COOKIE, USER_AGENT,
+};
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{
+    call_and_read_body, call_and_read_body_json, call_service, init_service, read_body,
+    try_call_service, TestRequest,
+};
+#[cfg(feature = "reqwest")]
+use actix_web::HttpServer;
+use actix_web::{web, App, HttpRequest, HttpResponse};
+use chrono::{TimeDelta, Utc};
+use ed25519_compact::KeyPair;
+use jwt_compact::alg::{Ed25519, Hs256, Hs256Key};
+use jwt_compact::{TimeOptions, UntrustedToken};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "reqwest")]
+use std::net::TcpListener;
+#[cfg(feature = "reqwest")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "reqwest")]
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct TestClaims {
+    id: u32,
+}
+
+lazy_static! {
+    static ref KEY_PAIR: KeyPair = KeyPair::generate();
+    static ref OTHER_KEY_PAIR: KeyPair = KeyPair::generate();
+    static ref TOKEN_SIGNER: TokenSigner<TestClaims, Ed25519> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+}
+
+struct Outer;
+struct Inner;
+
+async fn scoped_ids(
+    outer: ScopedClaims<TestClaims, Outer>,
+    inner: ScopedClaims<TestClaims, Inner>,
+) -> String {
+    format!("{} {}", outer.id, inner.id)
+}
+
+#[actix_web::test]
+async fn nested_scopes_with_same_claims_type() {
+    let outer_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .access_token_name("outer_access_token")
+        .build()
+        .unwrap();
+    let inner_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(OTHER_KEY_PAIR.sk.clone())
+        .access_token_name("inner_access_token")
+        .build()
+        .unwrap();
+
+    let outer_authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(outer_token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .claims_marker::<Outer>()
+        .build()
+        .unwrap();
+    let inner_authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(OTHER_KEY_PAIR.pk)
+        .token_signer(Some(inner_token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .claims_marker::<Inner>()
+        .build()
+        .unwrap();
+
+    let app = init_service(
+        App::new().service(
+            web::scope("/outer")
+                .wrap(AuthenticationService::new(outer_authority))
+                .service(
+                    web::scope("/inner")
+                        .wrap(AuthenticationService::new(inner_authority))
+                        .route("/ids", web::get().to(scoped_ids)),
+                ),
+        ),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/outer/inner/ids")
+        .cookie(
+            outer_token_signer
+                .create_access_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .cookie(
+            inner_token_signer
+                .create_access_cookie(&TestClaims { id: 2 })
+                .unwrap(),
+        )
+        .to_request();
+
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"1 2");
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, FromRequest)]
+#[serde(rename_all = "camelCase")]
+struct CamelCaseClaims {
+    user_id: u32,
+    display_name: String,
+}
+
+async fn camel_case_claims(claims: CamelCaseClaims) -> String {
+    format!("{} {}", claims.user_id, claims.display_name)
+}
+
+#[actix_web::test]
+async fn serde_attributes_are_honored() {
+    let token_signer: TokenSigner<CamelCaseClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<CamelCaseClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/claims", web::get().to(camel_case_claims)),
+    ))
+    .await;
+
+    let cookie = token_signer
+        .create_access_cookie(&CamelCaseClaims {
+            user_id: 1,
+            display_name: "Ferris".to_string(),
+        })
+        .unwrap();
+
+    let raw_claims = UntrustedToken::new(cookie.value())
+        .unwrap()
+        .deserialize_claims_unchecked::<serde_json::Value>()
+        .unwrap()
+        .custom;
+    assert_eq!(raw_claims["userId"], 1);
+    assert_eq!(raw_claims["displayName"], "Ferris");
+
+    let req = TestRequest::get()
+        .uri("/claims")
+        .cookie(cookie)
+        .to_request();
+
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"1 Ferris");
+}
+
+#[actix_web::test]
+async fn refreshed_tokens_delivered_as_header() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .token_delivery(TokenDelivery::Header)
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/hello")
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = call_service(&app, req).await;
+
+    assert!(res.status().is_success());
+    assert!(res.headers().contains_key("access_token"));
+    assert_eq!(res.response().cookies().count(), 0);
+}
+
+#[actix_web::test]
+async fn refreshed_tokens_delivered_as_plain_tokens_in_named_header() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .encode_cookie_value(true)
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .decode_cookie_value(true)
+        .token_delivery(TokenDelivery::Header)
+        .access_token_header_name("x-access-token")
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/hello")
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = call_service(&app, req).await;
+
+    assert!(res.status().is_success());
+    assert!(!res.headers().contains_key("access_token"));
+    let access_token = res
+        .headers()
+        .get("x-access-token")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(UntrustedToken::new(access_token).is_ok());
+}
+
+#[actix_web::test]
+async fn refresh_renewal_threshold() {
+    let token_signer = TOKEN_SIGNER.clone();
+
+    for (threshold, renewed_cookies) in [(0.0, 1), (1.0, 2)] {
+        let authority: Authority<TestClaims, _, _, _> = Authority::new()
+            .verifying_key(KEY_PAIR.pk)
+            .token_signer(Some(token_signer.clone()))
+            .refresh_authorizer(|| async { Ok(()) })
+            .renew_refresh_token_automatically(true)
+            .refresh_renewal_threshold(threshold)
+            .build()
+            .unwrap();
+
+        let app = init_service(App::new().use_jwt(
+            authority,
+            web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+        ))
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/hello")
+            .cookie(
+                token_signer
+                    .create_refresh_cookie(&TestClaims { id: 1 })
+                    .unwrap(),
+            )
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert!(res.status().is_success());
+        assert_eq!(res.response().cookies().count(), renewed_cookies);
+    }
+
+    assert!(Authority::<TestClaims, _, _, _>::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer))
+        .refresh_authorizer(|| async { Ok(()) })
+        .refresh_renewal_threshold(1.5)
+        .build()
+        .is_err());
+}
+
+#[actix_web::test]
+async fn auth_guard() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let guarded_scope = || {
+        web::scope("/guarded")
+            .guard(AuthGuard)
+            .route("", web::get().to(|| async { "hello" }))
+    };
+    let app = init_service(
+        App::new()
+            .use_jwt(authority, web::scope("/protected").service(guarded_scope()))
+            .service(guarded_scope()),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/protected/guarded")
+        .cookie(
+            token_signer
+                .create_access_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    assert!(call_service(&app, req).await.status().is_success());
+
+    let req = TestRequest::get()
+        .uri("/guarded")
+        .cookie(
+            token_signer
+                .create_access_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(
+        call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn skip_auth_for_preflight_requests() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(
+            TokenSigner::new()
+                .algorithm(Ed25519)
+                .signing_key(KEY_PAIR.sk.clone())
+                .build()
+                .unwrap(),
+        ))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::route().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let req = TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/hello")
+        .to_request();
+    assert!(call_service(&app, req).await.status().is_success());
+
+    let req = TestRequest::get().uri("/hello").to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing unauthenticated GET request")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn skip_auth_for_paths() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(
+            TokenSigner::new()
+                .algorithm(Ed25519)
+                .signing_key(KEY_PAIR.sk.clone())
+                .build()
+                .unwrap(),
+        ))
+        .refresh_authorizer(|| async { Ok(()) })
+        .skip_auth_for_paths(vec!["/api/login".into(), "/api/health/*".into()])
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("/api").default_service(web::to(|| async { "hello" })),
+    ))
+    .await;
+
+    for path in ["/api/login", "/api/health/live", "/api/health/ready"] {
+        let req = TestRequest::get().uri(path).to_request();
+        assert!(call_service(&app, req).await.status().is_success());
+    }
+    for path in ["/api/login/other", "/api/health", "/api/hello"] {
+        let req = TestRequest::get().uri(path).to_request();
+        let res = try_call_service(&app, req)
+            .await
+            .expect_err("Testing path which is not exempt from authentication")
+            .error_response();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+async fn session(claims: AuthenticatedClaims<TestClaims>) -> String {
+    format!(
+        "{} {} {}",
+        claims.custom.id,
+        claims.issued_at.is_some(),
+        claims.expiration > claims.issued_at
+    )
+}
+
+#[actix_web::test]
+async fn authenticated_claims() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/session", web::get().to(session)),
+    ))
+    .await;
+
+    for cookie in [
+        token_signer.create_access_cookie(&TestClaims { id: 1 }),
+        token_signer.create_refresh_cookie(&TestClaims { id: 1 }),
+    ] {
+        let req = TestRequest::get()
+            .uri("/session")
+            .cookie(cookie.unwrap())
+            .to_request();
+
+        assert_eq!(&call_and_read_body(&app, req).await[..], b"1 true true");
+    }
+}
+
+#[actix_web::test]
+async fn authenticated_claims_of_renewed_access_token() {
+    #[derive(Serialize, Deserialize, Clone)]
+    struct SubjectClaims {
+        sub: String,
+    }
+
+    let token_signer: TokenSigner<SubjectClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<SubjectClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/session",
+            web::get().to(|claims: AuthenticatedClaims<SubjectClaims>| async move {
+                format!(
+                    "{} {}",
+                    claims.subject.unwrap_or_default(),
+                    claims.expiration.unwrap().timestamp()
+                )
+            }),
+        ),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/session")
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&SubjectClaims {
+                    sub: "ferris".into(),
+                })
+                .unwrap(),
+        )
+        .to_request();
+    let res = call_service(&app, req).await;
+    let access_cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "access_token")
+        .unwrap();
+    let expiration = UntrustedToken::new(access_cookie.value())
+        .unwrap()
+        .deserialize_claims_unchecked::<SubjectClaims>()
+        .unwrap()
+        .expiration
+        .unwrap()
+        .timestamp();
+
+    assert_eq!(
+        read_body(res).await,
+        format!("ferris {expiration}").as_bytes()
+    );
+}
+
+#[actix_web::test]
+async fn extract_claims_from_http_request() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/id",
+            web::get().to(|req: HttpRequest| async move {
+                extract_claims::<TestClaims>(&req)
+                    .map(|claims| claims.id.to_string())
+                    .unwrap_or_default()
+            }),
+        ),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/id")
+        .cookie(
+            token_signer
+                .create_access_cookie(&TestClaims { id: 7 })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "7");
+
+    assert!(extract_claims::<TestClaims>(&TestRequest::default().to_http_request()).is_none());
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, FromRequest)]
+struct RoleClaims {
+    id: u32,
+    role: Role,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+enum Role {
+    Basic,
+    Admin,
+}
+
+impl HasRole<Role> for RoleClaims {
+    fn has_role(&self, role: &Role) -> bool {
+        self.role == *role
+    }
+}
+
+#[require_role(Role::Admin)]
+async fn admin(claims: RoleClaims) -> String {
+    format!("admin {}", claims.id)
+}
+
+#[actix_web::test]
+async fn require_role_attribute() {
+    let token_signer: TokenSigner<RoleClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<RoleClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/admin", web::get().to(admin)),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/admin")
+        .cookie(
+            token_signer
+                .create_access_cookie(&RoleClaims {
+                    id: 1,
+                    role: Role::Admin,
+                })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "admin 1");
+
+    let req = TestRequest::get()
+        .uri("/admin")
+        .cookie(
+            token_signer
+                .create_access_cookie(&RoleClaims {
+                    id: 2,
+                    role: Role::Basic,
+                })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(
+        call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+}
+
+fn dyn_authority(algorithm: &str) -> (DynAuthority<TestClaims>, String) {
+    match algorithm {
+        "HS256" => {
+            let key = Hs256Key::new(b"a-secret-that-is-long-enough-for-hs256");
+            let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+                .algorithm(Hs256)
+                .signing_key(key.clone())
+                .build()
+                .unwrap();
+            let access_token = token_signer
+                .create_access_token(&TestClaims { id: 1 })
+                .unwrap();
+            let authority: Authority<TestClaims, _, _, _> = Authority::new()
+                .verifying_key(key)
+                .token_signer(Some(token_signer))
+                .refresh_authorizer(|| async { Ok(()) })
+                .build()
+                .unwrap();
+            (DynAuthority::new(authority), access_token)
+        }
+        _ => {
+            let token_signer = TOKEN_SIGNER.clone();
+            let access_token = token_signer
+                .create_access_token(&TestClaims { id: 2 })
+                .unwrap();
+            let authority: Authority<TestClaims, _, _, _> = Authority::new()
+                .verifying_key(KEY_PAIR.pk)
+                .token_signer(Some(token_signer))
+                .refresh_authorizer(|| async { Ok(()) })
+                .build()
+                .unwrap();
+            (DynAuthority::new(authority), access_token)
+        }
+    }
+}
+
+#[actix_web::test]
+async fn dyn_authority_selected_at_runtime() {
+    for (algorithm, expected_id) in [("HS256", "1"), ("EdDSA", "2")] {
+        let (authority, access_token) = dyn_authority(algorithm);
+        assert!(matches!(
+            authority.public_config(),
+            DynAuthorityConfig::Authority(config) if config.algorithm == algorithm
+        ));
+
+        let app = init_service(App::new().service(web::scope("").wrap(authority).route(
+            "/id",
+            web::get().to(|claims: AuthenticatedClaims<TestClaims>| async move {
+                claims.custom.id.to_string()
+            }),
+        )))
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/id")
+            .cookie(Cookie::new("access_token", access_token))
+            .to_request();
+        assert_eq!(call_and_read_body(&app, req).await, expected_id);
+
+        let req = TestRequest::get().uri("/id").to_request();
+        assert_eq!(
+            try_call_service(&app, req)
+                .await
+                .expect_err("Testing no token case")
+                .error_response()
+                .status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
+
+#[actix_web::test]
+async fn shared_claims() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .share_claims(true)
+        .build()
+        .unwrap();
+
+    let app = init_service(
+        App::new().use_jwt(
+            authority,
+            web::scope("")
+                .route(
+                    "/shared",
+                    web::get().to(|claims: SharedClaims<TestClaims>| async move {
+                        claims.id.to_string()
+                    }),
+                )
+                .route(
+                    "/cloned",
+                    web::get().to(|req: HttpRequest| async move {
+                        extract_claims::<TestClaims>(&req)
+                            .map(|claims| claims.id.to_string())
+                            .unwrap_or_default()
+                    }),
+                ),
+        ),
+    )
+    .await;
+
+    let access_cookie = token_signer
+        .create_access_cookie(&TestClaims { id: 3 })
+        .unwrap();
+    for uri in ["/shared", "/cloned"] {
+        let req = TestRequest::get()
+            .uri(uri)
+            .cookie(access_cookie.clone())
+            .to_request();
+        assert_eq!(call_and_read_body(&app, req).await, "3");
+    }
+}
+
+/*
+    Exercises the whole middleware stack through `use_jwt`,
+    including the response path setting the cookies of renewed tokens.
+*/
+#[actix_web::test]
+async fn middleware_end_to_end() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let expired_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() - TimeDelta::hours(1)
+        }))
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/id",
+            web::get().to(|req: HttpRequest| async move {
+                extract_claims::<TestClaims>(&req)
+                    .map(|claims| claims.id.to_string())
+                    .unwrap_or_default()
+            }),
+        ),
+    ))
+    .await;
+
+    let req = TestRequest::get().uri("/id").to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing no token case")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = TestRequest::get()
+        .uri("/id")
+        .cookie(
+            token_signer
+                .create_access_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!res.headers().contains_key(SET_COOKIE));
+
+    let req = TestRequest::get()
+        .uri("/id")
+        .cookie(
+            expired_token_signer
+                .create_access_cookie(&TestClaims { id: 2 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing expired access token case")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = TestRequest::get()
+        .uri("/id")
+        .cookie(
+            expired_token_signer
+                .create_access_cookie(&TestClaims { id: 2 })
+                .unwrap(),
+        )
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&TestClaims { id: 2 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let renewed_cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == token_signer.access_token_name())
+        .map(Cookie::into_owned)
+        .expect("the renewed access token should be set as cookie");
+    let renewed_claims = UntrustedToken::new(renewed_cookie.value())
+        .unwrap()
+        .deserialize_claims_unchecked::<TestClaims>()
+        .unwrap();
+    assert_eq!(renewed_claims.custom, TestClaims { id: 2 });
+    assert_eq!(read_body(res).await, "2");
+
+    let req = TestRequest::get()
+        .uri("/id")
+        .cookie(renewed_cookie)
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "2");
+}
+
+#[derive(Deserialize)]
+struct ContinuationForm {
+    step: String,
+}
+
+#[actix_web::test]
+async fn body_tokens() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .enable_body_tokens(true)
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/continue",
+            web::post().to(
+                |req: HttpRequest, form: web::Form<ContinuationForm>| async move {
+                    let claims = extract_claims::<TestClaims>(&req).unwrap();
+                    format!("{} {}", claims.id, form.step)
+                },
+            ),
+        ),
+    ))
+    .await;
+
+    let access_token = token_signer
+        .create_access_token(&TestClaims { id: 4 })
+        .unwrap();
+    let req = TestRequest::post()
+        .uri("/continue")
+        .set_form([("step", "consent"), ("access_token", access_token.as_str())])
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "4 consent");
+
+    let req = TestRequest::post()
+        .uri("/continue")
+        .set_json(serde_json::json!({ "access_token": access_token }))
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing token in a JSON body case")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn no_store_token_responses() {
+    let token_signer = TOKEN_SIGNER.clone();
+
+    for no_store_token_responses in [true, false] {
+        let authority: Authority<TestClaims, _, _, _> = Authority::new()
+            .verifying_key(KEY_PAIR.pk)
+            .token_signer(Some(token_signer.clone()))
+            .refresh_authorizer(|| async { Ok(()) })
+            .no_store_token_responses(no_store_token_responses)
+            .build()
+            .unwrap();
+
+        let app = init_service(App::new().use_jwt(
+            authority,
+            web::scope("").route("/", web::get().to(|| async { "" })),
+        ))
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/")
+            .cookie(
+                token_signer
+                    .create_access_cookie(&TestClaims { id: 1 })
+                    .unwrap(),
+            )
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert!(!res.headers().contains_key(CACHE_CONTROL));
+
+        let req = TestRequest::get()
+            .uri("/")
+            .cookie(
+                token_signer
+                    .create_refresh_cookie(&TestClaims { id: 1 })
+                    .unwrap(),
+            )
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert!(res.status().is_success());
+        if no_store_token_responses {
+            assert_eq!(res.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+            assert_eq!(res.headers().get(PRAGMA).unwrap(), "no-cache");
+        } else {
+            assert!(!res.headers().contains_key(CACHE_CONTROL));
+            assert!(!res.headers().contains_key(PRAGMA));
+        }
+    }
+}
+
+#[actix_web::test]
+async fn request_mutator() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .request_mutator(|req, claims: &TestClaims| {
+            req.headers_mut().insert(
+                HeaderName::from_static("x-user-id"),
+                HeaderValue::from(claims.id),
+            );
+        })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/",
+            web::get().to(|req: HttpRequest| async move {
+                req.headers()
+                    .get("x-user-id")
+                    .and_then(|user_id| user_id.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string()
+            }),
+        ),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(
+            token_signer
+                .create_access_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "1");
+
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&TestClaims { id: 2 })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "2");
+
+    let req = TestRequest::get()
+        .uri("/")
+        .insert_header(("x-user-id", "3"))
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing no token case")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn unauthenticated_redirect() {
+    let authority: Authority<TestClaims, Ed25519, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(TimeOptions::default())
+        .refresh_authorizer(|| async { Ok(()) })
+        .unauthenticated_redirect("/login")
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/hello?tab=1")
+        .insert_header((ACCEPT, "text/html,application/xhtml+xml"))
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing unauthenticated browser request")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(
+        res.headers().get(LOCATION).unwrap(),
+        "/login?next=%2Fhello%3Ftab%3D1"
+    );
+
+    let req = TestRequest::get()
+        .uri("/hello")
+        .insert_header((ACCEPT, "application/json"))
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing unauthenticated API request")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[derive(FromRequest)]
+#[from_request(map = "|claims: &CamelCaseClaims| DisplayName(claims.display_name.clone())")]
+struct DisplayName(String);
+
+#[actix_web::test]
+async fn mapped_claims() {
+    let token_signer: TokenSigner<CamelCaseClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<CamelCaseClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/name",
+            web::get().to(|DisplayName(name): DisplayName| async move { name }),
+        ),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/name")
+        .cookie(
+            token_signer
+                .create_access_cookie(&CamelCaseClaims {
+                    user_id: 1,
+                    display_name: "Ferris".to_string(),
+                })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"Ferris");
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, FromRequest)]
+struct FlattenedClaims {
+    id: u32,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+#[actix_web::test]
+async fn flattened_claims() {
+    let token_signer: TokenSigner<FlattenedClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<FlattenedClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/claims",
+            web::get().to(|claims: FlattenedClaims| async move { web::Json(claims) }),
+        ),
+    ))
+    .await;
+
+    let claims = FlattenedClaims {
+        id: 1,
+        extra: HashMap::from([
+            ("tenant".to_string(), serde_json::json!("acme")),
+            ("groups".to_string(), serde_json::json!(["admins", "users"])),
+        ]),
+    };
+
+    let access_token = token_signer
+        .create_signed_token(&claims, Duration::from_secs(60))
+        .unwrap();
+    let signed_claims = UntrustedToken::new(&access_token)
+        .unwrap()
+        .deserialize_claims_unchecked::<FlattenedClaims>()
+        .unwrap();
+    assert_eq!(signed_claims.custom, claims);
+
+    let req = TestRequest::get()
+        .uri("/claims")
+        .cookie(Cookie::new("access_token", access_token))
+        .to_request();
+    let extracted_claims: FlattenedClaims = call_and_read_body_json(&app, req).await;
+    assert_eq!(extracted_claims, claims);
+
+    let req = TestRequest::get()
+        .uri("/claims")
+        .cookie(token_signer.create_refresh_cookie(&claims).unwrap())
+        .to_request();
+    let res = call_service(&app, req).await;
+    let renewed_cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == token_signer.access_token_name())
+        .map(Cookie::into_owned)
+        .expect("the renewed access token should be set as cookie");
+    let renewed_claims = UntrustedToken::new(renewed_cookie.value())
+        .unwrap()
+        .deserialize_claims_unchecked::<FlattenedClaims>()
+        .unwrap();
+    assert_eq!(renewed_claims.custom, claims);
+    let extracted_claims: FlattenedClaims = serde_json::from_slice(&read_body(res).await).unwrap();
+    assert_eq!(extracted_claims, claims);
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SubjectClaims {
+    sub: String,
+}
+
+#[actix_web::test]
+async fn subject_extractor() {
+    let token_signer: TokenSigner<SubjectClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<SubjectClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .insert_subject(true)
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/",
+            web::get().to(|Subject(user_id): Subject<u32>| async move { user_id.to_string() }),
+        ),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(
+            token_signer
+                .create_access_cookie(&SubjectClaims { sub: "42".into() })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "42");
+
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(
+            token_signer
+                .create_access_cookie(&SubjectClaims {
+                    sub: "alice".into(),
+                })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(
+        call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn navigation_cookie() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .navigation_cookie_name("navigation_token")
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/id",
+            web::get().to(|req: HttpRequest| async move {
+                extract_claims::<TestClaims>(&req)
+                    .map(|claims| claims.id.to_string())
+                    .unwrap_or_default()
+            }),
+        ),
+    ))
+    .await;
+
+    let access_cookie = token_signer
+        .create_access_cookie(&TestClaims { id: 1 })
+        .unwrap();
+    let navigation_cookie = token_signer
+        .navigation_cookie(&access_cookie)
+        .expect("a navigation_cookie_name is set");
+    assert_eq!(navigation_cookie.name(), "navigation_token");
+    assert_eq!(navigation_cookie.same_site(), Some(SameSite::Lax));
+    assert_eq!(access_cookie.same_site(), Some(SameSite::Strict));
+
+    let req = TestRequest::get()
+        .uri("/id")
+        .cookie(navigation_cookie)
+        .to_request();
+    assert_eq!(call_and_read_body(&app, req).await, "1");
+
+    let req = TestRequest::get()
+        .uri("/id")
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&TestClaims { id: 2 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let renewed_navigation_cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "navigation_token")
+        .map(Cookie::into_owned)
+        .expect("the navigation cookie should be renewed alongside the access token");
+    assert_eq!(renewed_navigation_cookie.same_site(), Some(SameSite::Lax));
+
+    let login_response = token_signer.login_response(&TestClaims { id: 3 }).unwrap();
+    assert_eq!(login_response.cookies().count(), 3);
+}
+
+#[actix_web::test]
+async fn csrf_double_submit() {
+    #[derive(Deserialize)]
+    struct CsrfClaim {
+        csrf: String,
+    }
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .csrf_cookie_name("csrf_token")
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .csrf_header_name("x-csrf-token")
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/", web::route().to(|| async { "ok" })),
+    ))
+    .await;
+
+    let cookies = token_signer
+        .create_login_cookies(&TestClaims { id: 1 })
+        .unwrap();
+    let find_cookie = |name: &str| {
+        cookies
+            .iter()
+            .find(|cookie| cookie.name() == name)
+            .cloned()
+            .unwrap()
+    };
+    let access_cookie = find_cookie("access_token");
+    let refresh_cookie = find_cookie("refresh_token");
+    let csrf_cookie = find_cookie("csrf_token");
+    assert_eq!(csrf_cookie.http_only(), Some(false));
+    assert_eq!(
+        UntrustedToken::new(access_cookie.value())
+            .unwrap()
+            .deserialize_claims_unchecked::<CsrfClaim>()
+            .unwrap()
+            .custom
+            .csrf,
+        csrf_cookie.value()
+    );
+
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(access_cookie.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+    for csrf_header in [None, Some("forged")] {
+        let mut req = TestRequest::post().uri("/").cookie(access_cookie.clone());
+        if let Some(csrf_header) = csrf_header {
+            req = req.insert_header(("x-csrf-token", csrf_header));
+        }
+        let err = try_call_service(&app, req.to_request())
+            .await
+            .expect_err("Testing csrf mismatch case");
+        assert_eq!(err.as_error::<AuthError>(), Some(&AuthError::CsrfMismatch));
+        assert_eq!(err.error_response().status(), StatusCode::FORBIDDEN);
+    }
+
+    let req = TestRequest::post()
+        .uri("/")
+        .cookie(access_cookie)
+        .insert_header(("x-csrf-token", csrf_cookie.value()))
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = TestRequest::post()
+        .uri("/")
+        .cookie(refresh_cookie)
+        .insert_header(("x-csrf-token", csrf_cookie.value()))
+        .to_request();
+    let res = call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let renewed_cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "access_token")
+        .expect("the renewed access token should be set as cookie");
+    assert_eq!(
+        UntrustedToken::new(renewed_cookie.value())
+            .unwrap()
+            .deserialize_claims_unchecked::<CsrfClaim>()
+            .unwrap()
+            .custom
+            .csrf,
+        csrf_cookie.value()
+    );
+}
+
+#[actix_web::test]
+async fn csrf_checked_methods() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .csrf_cookie_name("csrf_token")
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .csrf_header_name("x-csrf-token")
+        .csrf_checked_methods(vec![Method::DELETE])
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/", web::route().to(|| async { "ok" })),
+    ))
+    .await;
+
+    let access_cookie = token_signer
+        .create_login_cookies(&TestClaims { id: 1 })
+        .unwrap()
+        .remove(0);
+
+    let req = TestRequest::post()
+        .uri("/")
+        .cookie(access_cookie.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = TestRequest::delete()
+        .uri("/")
+        .cookie(access_cookie)
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing unchecked csrf token case")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn cookie_name_prefix() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let tenant_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_name_prefix("t1_")
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(tenant_token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/", web::get().to(|| async { "ok" })),
+    ))
+    .await;
+
+    let access_cookie = tenant_token_signer
+        .create_access_cookie(&TestClaims { id: 1 })
+        .unwrap();
+    assert_eq!(access_cookie.name(), "t1_access_token");
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(access_cookie)
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(
+            tenant_token_signer
+                .create_refresh_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res
+        .response()
+        .cookies()
+        .any(|cookie| cookie.name() == "t1_access_token"));
+
+    let req = TestRequest::get()
+        .uri("/")
+        .cookie(
+            token_signer
+                .create_access_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing unprefixed cookie case")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, FromRequest)]
+struct AdminClaims {
+    id: u32,
+    permissions: Vec<String>,
+}
+
+async fn admin_permissions(claims: AdminClaims) -> String {
+    claims.permissions.join(",")
+}
+
+#[actix_web::test]
+async fn authority_with_other_claims() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let user_authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+    let admin_authority = user_authority.with_claims::<AdminClaims>();
+    let admin_token_signer = token_signer.with_claims::<AdminClaims>();
+
+    let app = init_service(
+        App::new()
+            .service(
+                web::scope("/admin")
+                    .wrap(AuthenticationService::new(admin_authority))
+                    .route("/permissions", web::get().to(admin_permissions)),
+            )
+            .service(
+                web::scope("/user")
+                    .wrap(AuthenticationService::new(user_authority))
+                    .route(
+                        "/id",
+                        web::get().to(|req: HttpRequest| async move {
+                            extract_claims::<TestClaims>(&req).unwrap().id.to_string()
+                        }),
+                    ),
+            ),
+    )
+    .await;
+
+    let admin_cookie = admin_token_signer
+        .create_access_cookie(&AdminClaims {
+            id: 1,
+            permissions: vec!["read".into(), "write".into()],
+        })
+        .unwrap();
+    let user_cookie = token_signer
+        .create_access_cookie(&TestClaims { id: 2 })
+        .unwrap();
+
+    let req = TestRequest::get()
+        .uri("/admin/permissions")
+        .cookie(admin_cookie)
+        .to_request();
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"read,write");
+
+    let req = TestRequest::get()
+        .uri("/user/id")
+        .cookie(user_cookie.clone())
+        .to_request();
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"2");
+
+    let req = TestRequest::get()
+        .uri("/admin/permissions")
+        .cookie(user_cookie)
+        .to_request();
+    let err = try_call_service(&app, req)
+        .await
+        .expect_err("Tokens without the admin claims are rejected");
+    assert_eq!(err.error_response().status(), 401);
+}
+
+async fn explicitly_verified_id(
+    req: HttpRequest,
+    authority: web::Data<DynAuthority<TestClaims>>,
+) -> String {
+    match authority.verify_http_request(&req) {
+        Ok(claims) => claims.id.to_string(),
+        Err(_) => "unauthenticated".into(),
+    }
+}
+
+#[actix_web::test]
+async fn use_jwt_with_authority_data() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(
+        App::new()
+            .use_jwt_with_authority_data(authority, web::scope("/private"))
+            .route("/explicit", web::get().to(explicitly_verified_id)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/explicit")
+        .cookie(
+            token_signer
+                .create_access_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"1");
+
+    let req = TestRequest::get().uri("/explicit").to_request();
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"unauthenticated");
+}
+
+#[cfg(feature = "reqwest")]
+#[actix_web::test]
+async fn introspection_authority() {
+    let introspection_calls = Arc::new(AtomicUsize::new(0));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let introspection_url = format!("http://{}/introspect", listener.local_addr().unwrap());
+    let server = HttpServer::new({
+        let introspection_calls = Arc::clone(&introspection_calls);
+        move || {
+            let introspection_calls = Arc::clone(&introspection_calls);
+            App::new().route(
+                "/introspect",
+                web::post().to(move |form: web::Form<HashMap<String, String>>| {
+                    introspection_calls.fetch_add(1, Ordering::SeqCst);
+                    let response = if form["token"] == "active-token" {
+                        serde_json::json!({
+                            "active": true,
+                            "exp": (Utc::now() + TimeDelta::try_minutes(1).unwrap()).timestamp(),
+                            "sub": "alice",
+                        })
+                    } else {
+                        serde_json::json!({ "active": false })
+                    };
+                    async move { HttpResponse::Ok().json(response) }
+                }),
+            )
+        }
+    })
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_web::rt::spawn(server);
+
+    let introspection_authority = DynAuthority::from(
+        IntrospectionAuthority::<SubjectClaims>::new()
+            .introspection_url(introspection_url.clone())
+            .build()
+            .unwrap(),
+    );
+    assert!(matches!(
+        introspection_authority.public_config(),
+        DynAuthorityConfig::Introspection(config)
+            if config.introspection_url == introspection_url && config.client_id.is_none()
+    ));
+    assert!(matches!(
+        introspection_authority.verify_token_ignoring_expiry("active-token"),
+        Err(AuthError::Unsupported(_))
+    ));
+
+    let app = init_service(
+        App::new().service(web::scope("").wrap(introspection_authority).route(
+            "/sub",
+            web::get().to(|req: HttpRequest| async move {
+                extract_claims::<SubjectClaims>(&req).unwrap().sub
+            }),
+        )),
+    )
+    .await;
+
+    for _ in 0..2 {
+        let req = TestRequest::get()
+            .uri("/sub")
+            .insert_header(("Authorization", "Bearer active-token"))
+            .to_request();
+        assert_eq!(&call_and_read_body(&app, req).await[..], b"alice");
+    }
+    assert_eq!(introspection_calls.load(Ordering::SeqCst), 1);
+
+    let req = TestRequest::get()
+        .uri("/sub")
+        .insert_header(("Authorization", "Bearer revoked-token"))
+        .to_request();
+    let err = try_call_service(&app, req)
+        .await
+        .expect_err("Inactive tokens are rejected");
+    assert_eq!(err.error_response().status(), 401);
+
+    let req = TestRequest::get()
+        .uri("/sub")
+        .insert_header(("Authorization", "Beareractive-token"))
+        .to_request();
+    let err = try_call_service(&app, req)
+        .await
+        .expect_err("Bearer tokens without a space are rejected");
+    assert_eq!(err.error_response().status(), 401);
+    assert_eq!(introspection_calls.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "reqwest")]
+#[actix_web::test]
+async fn introspection_request_timeout() {
+    // the connection is accepted by the backlog of the listener, but never answered
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let introspection_authority = IntrospectionAuthority::<SubjectClaims>::new()
+        .introspection_url(format!(
+            "http://{}/introspect",
+            listener.local_addr().unwrap()
+        ))
+        .request_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+
+    assert!(matches!(
+        introspection_authority.introspect("active-token").await,
+        Err(AuthError::Introspection(_))
+    ));
+}
+
+#[actix_web::test]
+async fn refresh_authorizer_redirect() {
+    let token_signer = TOKEN_SIGNER.clone();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async {
+            Err(InternalError::from_response(
+                "please log in again",
+                HttpResponse::Found()
+                    .insert_header((LOCATION, "/login"))
+                    .finish(),
+            )
+            .into())
+        })
+        .refresh_denied_status(StatusCode::FORBIDDEN)
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/hello")
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&TestClaims { id: 1 })
+                .unwrap(),
+        )
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing denied refresh case")
+        .error_response();
+
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(res.headers().get(LOCATION).unwrap(), "/login");
+}
+
+#[actix_web::test]
+async fn fingerprint_bound_tokens() {
+    fn user_agent(req: &HttpRequest) -> String {
+        req.headers()
+            .get(USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .fingerprint_extractor(user_agent)
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .renew_access_token_automatically(false)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let cookie = token_signer
+        .create_access_cookie_for_request(
+            &TestClaims { id: 1 },
+            &TestRequest::default()
+                .insert_header((USER_AGENT, "device-a"))
+                .to_http_request(),
+        )
+        .unwrap();
+
+    let req = TestRequest::get()
+        .uri("/hello")
+        .insert_header((USER_AGENT, "device-a"))
+        .cookie(cookie.clone())
+        .to_request();
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"hello");
+
+    let req = TestRequest::get()
+        .uri("/hello")
+        .insert_header((USER_AGENT, "device-b"))
+        .cookie(cookie)
+        .to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing fingerprint mismatch case")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn no_token_lists_checked_sources() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(TimeOptions::default())
+        .enable_cookie_tokens(false)
+        .enable_header_tokens(true)
+        .enable_authorization_header(true)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let err = try_call_service(&app, TestRequest::get().uri("/hello").to_request())
+        .await
+        .expect_err("Testing no token case");
+
+    assert!(matches!(
+        err.as_error::<AuthError>(),
+        Some(AuthError::NoToken(checked_sources))
+            if checked_sources == &[TokenSource::Header, TokenSource::AuthorizationHeader]
+    ));
+    #[cfg(debug_assertions)]
+    assert!(err
+        .to_string()
+        .ends_with("Checked: header(absent), authorization header(absent)"));
+}
+
+#[cfg(feature = "problem-details")]
+#[actix_web::test]
+async fn problem_details_responses() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(
+            TokenSigner::new()
+                .algorithm(Ed25519)
+                .signing_key(KEY_PAIR.sk.clone())
+                .build()
+                .unwrap(),
+        ))
+        .refresh_authorizer(|| async { Ok(()) })
+        .problem_details_responses(true)
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route("/hello", web::get().to(|| async { "hello" })),
+    ))
+    .await;
+
+    let req = TestRequest::get().uri("/hello").to_request();
+    let res = try_call_service(&app, req)
+        .await
+        .expect_err("Testing unauthenticated request")
+        .error_response();
+
+    assert_eq!(
+        res.headers().get(CONTENT_TYPE).unwrap(),
+        "application/problem+json"
+    );
+    let problem_details: serde_json::Value =
+        serde_json::from_slice(&to_bytes(res.into_body()).await.unwrap()).unwrap();
+    assert_eq!(problem_details["status"], 401);
+    assert_eq!(problem_details["title"], "Unauthorized");
+}
+
+#[cfg(feature = "jwe")]
+#[actix_web::test]
+async fn encrypted_access_cookie() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .encryption_key(TokenEncryptionKey::new([7; 32]))
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let app = init_service(App::new().use_jwt(
+        authority,
+        web::scope("").route(
+            "/id",
+            web::get().to(|claims: web::ReqData<TestClaims>| async move { claims.id.to_string() }),
+        ),
+    ))
+    .await;
+
+    let cookie = token_signer
+        .create_access_cookie(&TestClaims { id: 1 })
+        .unwrap();
+    assert!(UntrustedToken::new(cookie.value()).is_err());
+
+    let req = TestRequest::get().uri("/id").cookie(cookie).to_request();
+
+    assert_eq!(&call_and_read_body(&app, req).await[..], b"1");
+}
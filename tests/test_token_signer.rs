@@ -0,0 +1,487 @@
+use actix_jwt_auth_middleware::{AuthError, CookiePriority, TokenSigner};
+use actix_web::cookie::Cookie;
+use ed25519_compact::KeyPair;
+use jwt_compact::alg::Ed25519;
+use jwt_compact::UntrustedToken;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct TestClaims {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct RoleClaims {
+    roles: Vec<String>,
+}
+
+lazy_static! {
+    static ref KEY_PAIR: KeyPair = KeyPair::generate();
+}
+
+#[test]
+fn secure_cookies_by_default() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+
+    let cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    assert_eq!(cookie.name(), "access_token");
+    assert_eq!(cookie.secure(), Some(true));
+}
+
+#[test]
+fn insecure_cookies_from_cookie_builder() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_builder(Cookie::build("", "").secure(false))
+        .build()
+        .unwrap();
+
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    let refresh_cookie = token_signer.create_refresh_cookie(&TestClaims {}).unwrap();
+
+    assert_eq!(access_cookie.secure(), Some(false));
+    assert_eq!(refresh_cookie.secure(), Some(false));
+    assert_eq!(refresh_cookie.name(), "refresh_token");
+}
+
+#[test]
+fn debug_redacts_signing_key() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+
+    let debug_output = format!("{token_signer:?}");
+
+    assert!(debug_output.contains("signing_key: <redacted>"));
+}
+
+#[actix_web::test]
+async fn enriched_access_cookie() {
+    let token_signer: TokenSigner<RoleClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .claims_enricher(|mut claims: RoleClaims| async move {
+            claims.roles.push("admin".to_string());
+            Ok(claims)
+        })
+        .build()
+        .unwrap();
+
+    let cookie = token_signer
+        .create_access_cookie_async(RoleClaims { roles: Vec::new() })
+        .await
+        .unwrap();
+    let claims = UntrustedToken::new(cookie.value())
+        .unwrap()
+        .deserialize_claims_unchecked::<RoleClaims>()
+        .unwrap()
+        .custom;
+
+    assert_eq!(claims.roles, vec!["admin".to_string()]);
+}
+
+#[test]
+fn cookie_domain_overrides_cookie_builder() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_builder(Cookie::build("", "").domain("other.com"))
+        .cookie_domain("example.com")
+        .build()
+        .unwrap();
+
+    let cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    assert_eq!(cookie.domain(), Some("example.com"));
+}
+
+#[test]
+fn invalid_cookie_domain() {
+    assert!(TokenSigner::<TestClaims, _>::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_domain("https://example.com")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn zero_cookie_chunk_size() {
+    assert!(TokenSigner::<TestClaims, _>::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_chunk_size(0)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn token_type_header() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .token_type("at+jwt")
+        .build()
+        .unwrap();
+
+    let cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    let token = UntrustedToken::new(cookie.value()).unwrap();
+
+    assert_eq!(token.header().token_type.as_deref(), Some("at+jwt"));
+}
+
+#[test]
+fn cookie_priority_and_partitioned() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_priority(CookiePriority::High)
+        .cookie_partitioned(true)
+        .build()
+        .unwrap();
+
+    let cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    let header_value = token_signer.set_cookie_header_value(&cookie);
+
+    assert!(header_value
+        .to_str()
+        .unwrap()
+        .ends_with("; Priority=High; Partitioned"));
+    assert_eq!(
+        Cookie::parse(header_value.to_str().unwrap())
+            .unwrap()
+            .value(),
+        cookie.value()
+    );
+}
+
+#[test]
+fn refresh_cookie_with_lifetime() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+
+    let lifetime = |cookie: Cookie| {
+        let claims = UntrustedToken::new(cookie.value())
+            .unwrap()
+            .deserialize_claims_unchecked::<TestClaims>()
+            .unwrap();
+        (claims.expiration.unwrap() - claims.issued_at.unwrap()).num_seconds()
+    };
+
+    assert_eq!(
+        lifetime(token_signer.create_refresh_cookie(&TestClaims {}).unwrap()),
+        30 * 60
+    );
+    assert_eq!(
+        lifetime(
+            token_signer
+                .create_refresh_cookie_with_lifetime(
+                    &TestClaims {},
+                    Duration::from_secs(30 * 24 * 60 * 60)
+                )
+                .unwrap()
+        ),
+        30 * 24 * 60 * 60
+    );
+    assert!(matches!(
+        token_signer.create_refresh_cookie_with_lifetime(&TestClaims {}, Duration::MAX),
+        Err(AuthError::TokenCreation(_))
+    ));
+}
+
+#[test]
+fn cookie_max_age_matches_token_lifetime() {
+    use actix_web::cookie::time::Duration as CookieDuration;
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .access_token_lifetime(Duration::from_secs(60))
+        .build()
+        .unwrap();
+
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    let refresh_cookie = token_signer.create_refresh_cookie(&TestClaims {}).unwrap();
+
+    assert_eq!(access_cookie.max_age(), Some(CookieDuration::seconds(60)));
+    assert_eq!(
+        refresh_cookie.max_age(),
+        Some(CookieDuration::seconds(30 * 60))
+    );
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_builder(Cookie::build("", "").max_age(CookieDuration::days(1)))
+        .build()
+        .unwrap();
+
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    assert_eq!(access_cookie.max_age(), Some(CookieDuration::days(1)));
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_max_age_from_token_lifetime(false)
+        .build()
+        .unwrap();
+
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    assert_eq!(access_cookie.max_age(), None);
+}
+
+#[test]
+fn signed_token_from_json_value() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+
+    let token = token_signer
+        .create_signed_token_value(
+            &serde_json::json!({ "roles": ["admin"] }),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+    let claims = UntrustedToken::new(&token)
+        .unwrap()
+        .deserialize_claims_unchecked::<RoleClaims>()
+        .unwrap();
+
+    assert_eq!(claims.custom.roles, vec!["admin".to_string()]);
+    assert!(claims.expiration.is_some());
+}
+
+#[actix_web::test]
+async fn login_response() {
+    use actix_web::body::{to_bytes, BodySize, MessageBody};
+    use actix_web::http::header::{CACHE_CONTROL, SET_COOKIE};
+
+    let token_signer: TokenSigner<RoleClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let claims = RoleClaims {
+        roles: vec!["admin".to_string()],
+    };
+
+    let response = token_signer.login_response(&claims).unwrap();
+    let cookie_names: Vec<_> = response
+        .cookies()
+        .map(|cookie| cookie.name().to_string())
+        .collect();
+
+    assert_eq!(response.headers().get_all(SET_COOKIE).count(), 2);
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+    assert_eq!(cookie_names, vec!["access_token", "refresh_token"]);
+    assert_eq!(
+        to_bytes(response.into_body()).await.unwrap(),
+        r#"{"roles":["admin"]}"#
+    );
+
+    let response = token_signer.login_response_without_body(&claims).unwrap();
+
+    assert_eq!(response.headers().get_all(SET_COOKIE).count(), 2);
+    assert_eq!(response.into_body().size(), BodySize::Sized(0));
+}
+
+#[test]
+fn signed_token_with_claims() {
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use jwt_compact::Claims;
+
+    let token_signer: TokenSigner<RoleClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let issued_at = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+
+    let mut claims = Claims::new(RoleClaims {
+        roles: vec!["admin".to_string()],
+    });
+    claims.issued_at = Some(issued_at);
+    claims.not_before = Some(issued_at + TimeDelta::try_minutes(1).unwrap());
+    claims.expiration = Some(issued_at + TimeDelta::try_hours(2).unwrap());
+
+    let token = token_signer
+        .create_signed_token_with_claims(claims.clone())
+        .unwrap();
+    let signed_claims = UntrustedToken::new(&token)
+        .unwrap()
+        .deserialize_claims_unchecked::<RoleClaims>()
+        .unwrap();
+    assert_eq!(signed_claims.issued_at, claims.issued_at);
+    assert_eq!(signed_claims.not_before, claims.not_before);
+    assert_eq!(signed_claims.expiration, claims.expiration);
+    assert_eq!(signed_claims.custom, claims.custom);
+
+    let mut claims = Claims::new(RoleClaims { roles: vec![] });
+    claims.issued_at = Some(issued_at);
+
+    let token = token_signer
+        .create_signed_token_with_claims(claims)
+        .unwrap();
+    let signed_claims = UntrustedToken::new(&token)
+        .unwrap()
+        .deserialize_claims_unchecked::<RoleClaims>()
+        .unwrap();
+    assert_eq!(
+        signed_claims.expiration,
+        Some(issued_at + TimeDelta::try_seconds(60).unwrap())
+    );
+
+    let mut claims = Claims::new(RoleClaims { roles: vec![] });
+    claims.issued_at = Some(chrono::DateTime::<Utc>::MAX_UTC);
+    assert!(matches!(
+        token_signer.create_signed_token_with_claims(claims),
+        Err(AuthError::TokenCreation(_))
+    ));
+}
+
+#[test]
+fn access_cookie_builder() {
+    use actix_web::cookie::SameSite;
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_domain("example.com")
+        .build()
+        .unwrap();
+
+    let cookie = token_signer
+        .access_cookie_builder_for(&TestClaims {})
+        .unwrap()
+        .same_site(SameSite::Lax)
+        .finish();
+    let default_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    assert_eq!(cookie.name(), "access_token");
+    assert!(UntrustedToken::new(cookie.value()).is_ok());
+    assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    assert_eq!(cookie.domain(), Some("example.com"));
+    assert_eq!(cookie.secure(), default_cookie.secure());
+    assert_eq!(cookie.http_only(), default_cookie.http_only());
+    assert_eq!(cookie.path(), default_cookie.path());
+    assert_eq!(cookie.max_age(), default_cookie.max_age());
+}
+
+#[test]
+fn random_source() {
+    use actix_jwt_auth_middleware::{AccessTokenProfile, RandomSource};
+
+    #[derive(Debug)]
+    struct ZeroRandom;
+
+    impl RandomSource for ZeroRandom {
+        fn fill_bytes(&self, dest: &mut [u8]) {
+            dest.fill(0);
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct JtiClaims {
+        jti: String,
+    }
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .access_token_profile(AccessTokenProfile::new(
+            "https://issuer.example.com",
+            "https://api.example.com",
+        ))
+        .csrf_cookie_name("csrf_token")
+        .random_source(ZeroRandom)
+        .build()
+        .unwrap();
+
+    let cookies = token_signer.create_login_cookies(&TestClaims {}).unwrap();
+    let jti = UntrustedToken::new(cookies[0].value())
+        .unwrap()
+        .deserialize_claims_unchecked::<JtiClaims>()
+        .unwrap()
+        .custom
+        .jti;
+    let csrf_cookie = cookies
+        .iter()
+        .find(|cookie| cookie.name() == "csrf_token")
+        .unwrap();
+
+    assert_eq!(jti, "A".repeat(22));
+    assert_eq!(csrf_cookie.value(), "A".repeat(43));
+}
+
+#[test]
+fn access_token_lifetime_exceeding_refresh_token_lifetime() {
+    let result = TokenSigner::<TestClaims, _>::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .access_token_lifetime(Duration::from_secs(60 * 60))
+        .refresh_token_lifetime(Duration::from_secs(30 * 60))
+        .build();
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("has to be shorter than the refresh_token_lifetime"));
+
+    let result = TokenSigner::<TestClaims, _>::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .access_token_lifetime(Duration::from_secs(30 * 60))
+        .build();
+    assert!(result.is_err());
+
+    let result = TokenSigner::<TestClaims, _>::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .refresh_token_lifetime(Duration::MAX)
+        .build();
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("exceeds the range of durations"));
+}
+
+#[test]
+fn refresh_cookie_path() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_builder(Cookie::build("", "").path("/"))
+        .refresh_cookie_path("/api/refresh")
+        .build()
+        .unwrap();
+
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    let refresh_cookie = token_signer.create_refresh_cookie(&TestClaims {}).unwrap();
+    let login_cookies = token_signer.create_login_cookies(&TestClaims {}).unwrap();
+
+    assert_eq!(access_cookie.path(), Some("/"));
+    assert_eq!(refresh_cookie.path(), Some("/api/refresh"));
+    assert_eq!(login_cookies[0].path(), Some("/"));
+    assert_eq!(login_cookies[1].path(), Some("/api/refresh"));
+
+    let result = TokenSigner::<TestClaims, _>::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .refresh_cookie_path("api/refresh")
+        .build();
+    assert!(result.is_err());
+}
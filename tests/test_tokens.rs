@@ -1,20 +1,28 @@
-use actix_jwt_auth_middleware::{AuthError, Authority, TokenSigner};
+use actix_jwt_auth_middleware::{
+    time, AuthError, Authority, JwtConfig, TokenSigner, TokenSource, TokenTiming, TokenVersion,
+    WasExpired, WasRefreshed,
+};
 use actix_web::cookie::Cookie;
 use actix_web::test::TestRequest;
-use chrono::{Duration, TimeDelta, Utc};
+use actix_web::HttpMessage;
+use chrono::{DateTime, Duration, TimeDelta, Utc};
 use ed25519_compact::KeyPair;
 use jwt_compact::alg::Ed25519;
 use jwt_compact::ValidationError::Expired as TokenExpired;
-use jwt_compact::{Claims, Header, ParseError, TimeOptions};
+use jwt_compact::ValidationError::InvalidSignature;
+use jwt_compact::ValidationError::MalformedClaims;
+use jwt_compact::ValidationError::NoClaim;
+use jwt_compact::{AlgorithmExt, Claim, Claims, Header, ParseError, TimeOptions, UntrustedToken};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 struct TestClaims {}
 
 lazy_static! {
     static ref KEY_PAIR: KeyPair = KeyPair::generate();
-    static ref TIME_OPTIONS: TimeOptions = TimeOptions::from_leeway(Duration::min_value());
+    static ref TIME_OPTIONS: TimeOptions = TimeOptions::from_leeway(Duration::MIN);
     static ref HEADER: Header = Header::default();
     static ref CLAIMS: Claims<TestClaims> = Claims::new(TestClaims {});
     static ref TOKEN_SIGNER: TokenSigner<TestClaims, Ed25519> = TokenSigner::new()
@@ -62,7 +70,7 @@ async fn deactivated_access_token_header() {
             .verify_service_request(&mut req)
             .await
             .expect_err("Testing no token case"),
-        AuthError::NoToken
+        AuthError::NoToken(Vec::new())
     )
 }
 
@@ -99,7 +107,7 @@ async fn no_token() {
             .verify_service_request(&mut req)
             .await
             .expect_err("Testing no token case"),
-        AuthError::NoToken
+        AuthError::NoToken(Vec::new())
     )
 }
 
@@ -125,7 +133,7 @@ async fn expired_token() {
             .verify_service_request(&mut req)
             .await
             .expect_err("Testing expired token case"),
-        AuthError::TokenValidation(TokenExpired)
+        AuthError::TokenValidation(TokenExpired, None)
     )
 }
 
@@ -154,3 +162,1947 @@ async fn nonce_token() {
         AuthError::TokenParse(ParseError::InvalidTokenStructure)
     )
 }
+
+#[actix_web::test]
+async fn query_token_with_custom_name() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .enable_query_tokens(true)
+        .query_access_token_name("token")
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let token = TOKEN_SIGNER
+        .create_signed_token(&TestClaims {}, std::time::Duration::from_secs(60))
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .uri(&format!("/?token={token}"))
+        .insert_header(("x-forwarded-proto", "https"))
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok())
+}
+
+#[actix_web::test]
+async fn redacted_query_token() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .enable_query_tokens(true)
+        .redact_query_tokens(true)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let token = TOKEN_SIGNER
+        .create_signed_token(&TestClaims {}, std::time::Duration::from_secs(60))
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .uri(&format!(
+            "/path?page=2&access_token={token}&q=a+b%2Fc&tilde=%7E"
+        ))
+        .insert_header(("x-forwarded-proto", "https"))
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+    assert_eq!(req.path(), "/path");
+    assert_eq!(req.query_string(), "page=2&q=a+b%2Fc&tilde=%7E");
+}
+
+#[actix_web::test]
+async fn query_token_over_insecure_transport() {
+    let authority_builder = || {
+        Authority::<TestClaims, _, _, _>::new()
+            .algorithm(Ed25519)
+            .verifying_key(KEY_PAIR.pk)
+            .time_options(*TIME_OPTIONS)
+            .enable_query_tokens(true)
+            .refresh_authorizer(|| async { Ok(()) })
+    };
+    let token = TOKEN_SIGNER
+        .create_signed_token(&TestClaims {}, std::time::Duration::from_secs(60))
+        .unwrap();
+    let request = || TestRequest::default().uri(&format!("/?access_token={token}"));
+
+    let authority = authority_builder().build().unwrap();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut request().to_srv_request())
+            .await
+            .expect_err("Testing query token over http case"),
+        AuthError::InsecureTransport
+    );
+    assert!(authority
+        .verify_service_request(
+            &mut request()
+                .insert_header(("forwarded", "proto=https"))
+                .to_srv_request()
+        )
+        .await
+        .is_ok());
+
+    let authority = authority_builder()
+        .require_tls_for_query_tokens(false)
+        .build()
+        .unwrap();
+    assert!(authority
+        .verify_service_request(&mut request().to_srv_request())
+        .await
+        .is_ok());
+}
+
+#[actix_web::test]
+async fn refresh_token_within_refresh_leeway() {
+    let authority_builder = || {
+        Authority::<TestClaims, _, _, _>::new()
+            .token_signer(Some(TOKEN_SIGNER.clone()))
+            .time_options(TimeOptions::new(TimeDelta::zero(), || {
+                Utc::now() + TimeDelta::try_minutes(31).unwrap()
+            }))
+            .verifying_key(KEY_PAIR.pk)
+            .refresh_authorizer(|| async { Ok(()) })
+    };
+    let refresh_cookie = TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap();
+
+    let authority = authority_builder().build().unwrap();
+    let mut req = TestRequest::default()
+        .cookie(refresh_cookie.clone())
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing expired refresh token case"),
+        AuthError::RefreshTokenExpired
+    );
+
+    let authority = authority_builder()
+        .refresh_leeway(std::time::Duration::from_secs(5 * 60))
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(refresh_cookie.clone())
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let authority = authority_builder()
+        .refresh_leeway(std::time::Duration::MAX)
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(refresh_cookie)
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok())
+}
+
+#[actix_web::test]
+async fn token_update_cookies() {
+    let authority = Authority::<TestClaims, _, _, _>::new()
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .verifying_key(KEY_PAIR.pk)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    assert!(authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .is_none());
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    let token_update = authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .expect("the access token should be renewed");
+    let access_cookie = token_update
+        .access_cookie()
+        .expect("the renewed access token should be set as cookie");
+    assert_eq!(access_cookie.name(), "access_token");
+    assert!(UntrustedToken::new(access_cookie.value()).is_ok());
+    assert!(token_update.refresh_cookie().is_none());
+    assert!(token_update.navigation_cookie().is_none());
+}
+
+#[actix_web::test]
+async fn csrf_cookie_renewed_with_refresh_token() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .csrf_cookie_name("csrf_token")
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .csrf_header_name("x-csrf-token")
+        .renew_refresh_token_automatically(true)
+        .refresh_renewal_threshold(1.0)
+        .build()
+        .unwrap();
+
+    let cookies = token_signer.create_login_cookies(&TestClaims {}).unwrap();
+    let find_cookie = |name: &str| {
+        cookies
+            .iter()
+            .find(|cookie| cookie.name() == name)
+            .cloned()
+            .unwrap()
+    };
+
+    let mut req = TestRequest::default()
+        .cookie(find_cookie("refresh_token"))
+        .to_srv_request();
+    let token_update = authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .expect("the tokens should be renewed");
+
+    assert!(token_update.refresh_cookie().is_some());
+    let csrf_cookie = token_update
+        .csrf_cookie()
+        .expect("the CSRF cookie should be renewed alongside the refresh token");
+    assert_eq!(csrf_cookie.value(), find_cookie("csrf_token").value());
+    assert_eq!(csrf_cookie.max_age(), find_cookie("csrf_token").max_age());
+}
+
+#[actix_web::test]
+async fn access_token_within_renewal_threshold() {
+    let authority_builder = || {
+        Authority::<TestClaims, _, _, _>::new()
+            .token_signer(Some(TOKEN_SIGNER.clone()))
+            .verifying_key(KEY_PAIR.pk)
+            .refresh_authorizer(|| async { Ok(()) })
+    };
+    let access_cookie = TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap();
+    let refresh_cookie = TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap();
+
+    let authority = authority_builder()
+        .access_renewal_threshold(std::time::Duration::from_secs(10))
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(access_cookie.clone())
+        .cookie(refresh_cookie.clone())
+        .to_srv_request();
+    assert!(authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .is_none());
+
+    let authority = authority_builder()
+        .access_renewal_threshold(std::time::Duration::from_secs(2 * 60))
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(access_cookie.clone())
+        .cookie(refresh_cookie)
+        .to_srv_request();
+    assert!(authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .is_some());
+
+    let mut req = TestRequest::default()
+        .cookie(access_cookie)
+        .to_srv_request();
+    assert!(authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[actix_web::test]
+async fn refreshed_request_is_marked() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+    assert_eq!(
+        req.extensions().get::<WasRefreshed>(),
+        Some(&WasRefreshed(true))
+    );
+}
+
+#[actix_web::test]
+async fn refresh_denied_status() {
+    use actix_web::error::ErrorUnauthorized;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .refresh_authorizer(|| async { Err(ErrorUnauthorized("not allowed")) })
+        .refresh_denied_status(StatusCode::FORBIDDEN)
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    let err = authority
+        .verify_service_request(&mut req)
+        .await
+        .expect_err("Testing denied refresh case");
+    assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn compressed_access_token() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .compress_claims(true)
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok())
+}
+
+#[actix_web::test]
+async fn compressed_claims_inflating_beyond_the_limit() {
+    #[derive(Serialize, Deserialize, Clone)]
+    struct PaddedClaims {
+        padding: String,
+    }
+
+    let token_signer: TokenSigner<PaddedClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .compress_claims(true)
+        .build()
+        .unwrap();
+    let authority: Authority<PaddedClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let claims = PaddedClaims {
+        padding: "a".repeat(1024 * 1024),
+    };
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&claims).unwrap())
+        .to_srv_request();
+
+    assert!(matches!(
+        authority.verify_service_request(&mut req).await,
+        Err(AuthError::TokenValidation(MalformedClaims(_), _))
+    ));
+}
+
+#[actix_web::test]
+async fn chunked_access_token() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .cookie_chunk_size(50)
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let cookies = token_signer
+        .create_access_cookies_chunked(&TestClaims {})
+        .unwrap();
+    assert!(cookies.len() > 1);
+    assert_eq!(cookies[0].name(), "access_token.0");
+
+    let mut req = cookies
+        .into_iter()
+        .fold(TestRequest::default(), |req, cookie| req.cookie(cookie))
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok())
+}
+
+#[actix_web::test]
+async fn token_type_mismatch() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .expected_token_type("at+jwt")
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing token type mismatch case"),
+        AuthError::TokenTypeMismatch
+    )
+}
+
+#[actix_web::test]
+async fn access_token_profile() {
+    use actix_jwt_auth_middleware::AccessTokenProfile;
+
+    #[derive(Serialize, Deserialize, Clone)]
+    struct ProfileClaims {
+        sub: String,
+        client_id: String,
+    }
+
+    let token_signer: TokenSigner<ProfileClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .access_token_profile(AccessTokenProfile::new(
+            "https://auth.example.com",
+            "https://api.example.com",
+        ))
+        .build()
+        .unwrap();
+    let authority: Authority<ProfileClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .enforce_access_token_profile(true)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+    let claims = ProfileClaims {
+        sub: "1".to_string(),
+        client_id: "client".to_string(),
+    };
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&claims).unwrap())
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_cookie(&claims, "access_token", std::time::Duration::from_secs(60))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing token without profile case"),
+        AuthError::TokenTypeMismatch
+    );
+}
+
+#[actix_web::test]
+async fn verify_expired_token_ignoring_expiry() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() + TimeDelta::try_minutes(5).unwrap()
+        }))
+        .verifying_key(KEY_PAIR.pk)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let token = TOKEN_SIGNER
+        .create_signed_token(&TestClaims {}, std::time::Duration::from_secs(60))
+        .unwrap();
+    assert!(authority.verify_token_ignoring_expiry(&token).is_ok());
+
+    let (message, signature) = token.rsplit_once('.').unwrap();
+    let tampered_signature: String = signature
+        .chars()
+        .enumerate()
+        .map(|(index, c)| match (index, c) {
+            (0, 'A') => 'B',
+            (0, _) => 'A',
+            _ => c,
+        })
+        .collect();
+    assert!(authority
+        .verify_token_ignoring_expiry(&format!("{message}.{tampered_signature}"))
+        .is_err());
+}
+
+#[test]
+fn mismatched_verifying_key() {
+    let result: Result<Authority<TestClaims, _, _, _>, _> = Authority::new()
+        .verifying_key(KeyPair::generate().pk)
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build();
+
+    assert!(result.is_err());
+}
+
+#[actix_web::test]
+async fn separate_refresh_key() {
+    let refresh_key_pair = KeyPair::generate();
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .refresh_signing_key(refresh_key_pair.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .refresh_verifying_key(refresh_key_pair.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_refresh_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing refresh token signed with the access key"),
+        AuthError::TokenValidation(jwt_compact::ValidationError::InvalidSignature, None)
+    );
+
+    let result: Result<Authority<TestClaims, _, _, _>, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build();
+    assert!(result.is_err());
+}
+
+#[actix_web::test]
+async fn max_access_token_age() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .access_token_lifetime(std::time::Duration::from_secs(2 * 60 * 60))
+        .refresh_token_lifetime(std::time::Duration::from_secs(4 * 60 * 60))
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() - TimeDelta::try_hours(1).unwrap()
+        }))
+        .build()
+        .unwrap();
+    let authority_builder = |max_access_token_age| {
+        Authority::<TestClaims, _, _, _>::new()
+            .algorithm(Ed25519)
+            .verifying_key(KEY_PAIR.pk)
+            .time_options(*TIME_OPTIONS)
+            .renew_access_token_automatically(false)
+            .max_access_token_age(max_access_token_age)
+            .refresh_authorizer(|| async { Ok(()) })
+    };
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    let authority = authority_builder(std::time::Duration::from_secs(30 * 60))
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(access_cookie.clone())
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing token older than the maximum age"),
+        AuthError::SessionExpired
+    );
+
+    let authority = authority_builder(std::time::Duration::from_secs(2 * 60 * 60))
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(access_cookie)
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+}
+
+#[actix_web::test]
+async fn max_refresh_token_age() {
+    let expired_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() - TimeDelta::try_hours(1).unwrap()
+        }))
+        .build()
+        .unwrap();
+    let authority_builder = |max_refresh_token_age| {
+        Authority::<TestClaims, _, _, _>::new()
+            .verifying_key(KEY_PAIR.pk)
+            .token_signer(Some(TOKEN_SIGNER.clone()))
+            .renew_refresh_token_automatically(true)
+            .max_refresh_token_age(max_refresh_token_age)
+            .refresh_authorizer(|| async { Ok(()) })
+    };
+    let refresh_cookie = expired_token_signer
+        .create_refresh_cookie(&TestClaims {})
+        .unwrap();
+    let issued_at = |cookie: &Cookie| {
+        UntrustedToken::new(cookie.value())
+            .unwrap()
+            .deserialize_claims_unchecked::<TestClaims>()
+            .unwrap()
+            .issued_at
+    };
+
+    let authority = authority_builder(std::time::Duration::from_secs(45 * 60))
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(refresh_cookie.clone())
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing expired refresh token older than the maximum age"),
+        AuthError::SessionExpired
+    );
+
+    let authority = authority_builder(std::time::Duration::from_secs(2 * 60 * 60))
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(refresh_cookie.clone())
+        .to_srv_request();
+    let token_update = authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .expect("the tokens should be renewed");
+    assert_eq!(
+        issued_at(token_update.refresh_cookie().unwrap()),
+        issued_at(&refresh_cookie)
+    );
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct AuthTimeClaims {
+    auth_time: i64,
+}
+
+#[actix_web::test]
+async fn max_auth_age() {
+    let token_signer: TokenSigner<AuthTimeClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<AuthTimeClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .max_auth_age(std::time::Duration::from_secs(60 * 60))
+        .renew_refresh_token_automatically(true)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+    let expired_token_signer: TokenSigner<AuthTimeClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() - TimeDelta::try_hours(1).unwrap()
+        }))
+        .build()
+        .unwrap();
+    let authenticated_ago = |hours| AuthTimeClaims {
+        auth_time: (Utc::now() - TimeDelta::try_hours(hours).unwrap()).timestamp(),
+    };
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&authenticated_ago(0))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&authenticated_ago(2))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing subject which authenticated too long ago"),
+        AuthError::ReauthRequired
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&authenticated_ago(2))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing renewal for subject which authenticated too long ago"),
+        AuthError::ReauthRequired
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(
+            expired_token_signer
+                .create_refresh_cookie(&authenticated_ago(2))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing renewal through an expired refresh token for subject which authenticated too long ago"),
+        AuthError::ReauthRequired
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .max_auth_age(std::time::Duration::from_secs(60 * 60))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing token without auth_time claim"),
+        AuthError::MissingClaim("auth_time")
+    );
+}
+
+#[actix_web::test]
+async fn verify_http_request() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap())
+        .to_http_request();
+    assert_eq!(authority.verify_http_request(&req), Ok(TestClaims {}));
+
+    let req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap())
+        .to_http_request();
+    assert_eq!(
+        authority.verify_http_request(&req),
+        Err(AuthError::NoToken(Vec::new()))
+    );
+}
+
+#[actix_web::test]
+async fn allowed_client_ids() {
+    #[derive(Serialize, Deserialize, Clone)]
+    struct ClientClaims {
+        client_id: String,
+    }
+
+    let token_signer: TokenSigner<ClientClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<ClientClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .renew_access_token_automatically(false)
+        .allowed_client_ids(["trusted-client".to_string()])
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let access_cookie = |client_id: &str| {
+        token_signer
+            .create_access_cookie(&ClientClaims {
+                client_id: client_id.into(),
+            })
+            .unwrap()
+    };
+
+    let mut req = TestRequest::default()
+        .cookie(access_cookie("trusted-client"))
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let mut req = TestRequest::default()
+        .cookie(access_cookie("other-client"))
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing token of a client which is not allowed"),
+        AuthError::ClientNotAllowed
+    );
+}
+
+#[actix_web::test]
+async fn async_token_signer() {
+    use actix_jwt_auth_middleware::{AsyncTokenSigner, SignFuture};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountingSigner(Arc<AtomicUsize>);
+
+    impl AsyncTokenSigner<TestClaims> for CountingSigner {
+        fn sign<'a>(&'a self, claims: &'a TestClaims) -> SignFuture<'a> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            TOKEN_SIGNER.sign(claims)
+        }
+    }
+
+    let sign_count = Arc::new(AtomicUsize::new(0));
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .async_token_signer(CountingSigner(Arc::clone(&sign_count)))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_refresh_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+
+    assert!(matches!(
+        authority.verify_service_request(&mut req).await,
+        Ok(Some(_))
+    ));
+    assert_eq!(sign_count.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn async_token_signer_binds_fingerprint_and_seals_cookie() {
+    fn device(req: &actix_web::HttpRequest) -> String {
+        req.headers()
+            .get("device")
+            .and_then(|device| device.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .encode_cookie_value(true)
+        .fingerprint_extractor(device)
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .async_token_signer(token_signer.clone())
+        .fingerprint_extractor(device)
+        .decode_cookie_value(true)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let refresh_cookie = token_signer
+        .create_refresh_cookie_for_request(
+            &TestClaims {},
+            &TestRequest::default()
+                .insert_header(("device", "device-a"))
+                .to_http_request(),
+        )
+        .unwrap();
+    let mut req = TestRequest::default()
+        .insert_header(("device", "device-a"))
+        .cookie(refresh_cookie)
+        .to_srv_request();
+    let token_update = authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .unwrap();
+    let access_cookie = token_update.access_cookie().unwrap().clone();
+    assert_ne!(Some(access_cookie.value()), token_update.access_token());
+
+    let mut req = TestRequest::default()
+        .insert_header(("device", "device-a"))
+        .cookie(access_cookie.clone())
+        .to_srv_request();
+    assert!(matches!(
+        authority.verify_service_request(&mut req).await,
+        Ok(None)
+    ));
+
+    let mut req = TestRequest::default()
+        .insert_header(("device", "device-b"))
+        .cookie(access_cookie)
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing token of another device"),
+        AuthError::FingerprintMismatch
+    );
+}
+
+#[actix_web::test]
+async fn allow_expired_access_token() {
+    let expired_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() - TimeDelta::try_hours(1).unwrap()
+        }))
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(TimeOptions::default())
+        .allow_expired(true)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let expired_cookie = expired_token_signer
+        .create_access_cookie(&TestClaims {})
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(expired_cookie.clone())
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+    assert_eq!(
+        req.extensions().get::<WasExpired>(),
+        Some(&WasExpired(true))
+    );
+
+    let req = TestRequest::default()
+        .cookie(expired_cookie)
+        .to_http_request();
+    assert_eq!(
+        authority
+            .verify_http_request(&req)
+            .expect_err("Testing expired token outside of the middleware case"),
+        AuthError::TokenValidation(TokenExpired, None)
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+    assert_eq!(req.extensions().get::<WasExpired>(), None);
+}
+
+#[actix_web::test]
+async fn require_expiration() {
+    let token_without_expiration = Ed25519.token(&HEADER, &CLAIMS, &KEY_PAIR.sk).unwrap();
+    let authority = |require_expiration| -> Authority<TestClaims, _, _, _> {
+        Authority::new()
+            .algorithm(Ed25519)
+            .time_options(*TIME_OPTIONS)
+            .verifying_key(KEY_PAIR.pk)
+            .require_expiration(require_expiration)
+            .refresh_authorizer(|| async { Ok(()) })
+            .build()
+            .unwrap()
+    };
+
+    let mut req = TestRequest::default()
+        .cookie(Cookie::new("access_token", &token_without_expiration))
+        .to_srv_request();
+    assert_eq!(
+        authority(true)
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing missing expiration case"),
+        AuthError::TokenValidation(NoClaim(Claim::Expiration), None)
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(Cookie::new("access_token", &token_without_expiration))
+        .to_srv_request();
+    assert!(authority(false)
+        .verify_service_request(&mut req)
+        .await
+        .is_ok());
+}
+
+#[actix_web::test]
+async fn time_helpers() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(time::fixed_clock(
+            Utc::now() - TimeDelta::try_minutes(5).unwrap(),
+        ))
+        .build()
+        .unwrap();
+    let authority = |leeway| -> Authority<TestClaims, _, _, _> {
+        Authority::new()
+            .algorithm(Ed25519)
+            .time_options(time::leeway(leeway))
+            .verifying_key(KEY_PAIR.pk)
+            .renew_access_token_automatically(false)
+            .refresh_authorizer(|| async { Ok(()) })
+            .build()
+            .unwrap()
+    };
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(access_cookie.clone())
+        .to_srv_request();
+    assert_eq!(
+        authority(0)
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing expired token case"),
+        AuthError::TokenValidation(TokenExpired, None)
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(access_cookie)
+        .to_srv_request();
+    assert!(authority(10 * 60)
+        .verify_service_request(&mut req)
+        .await
+        .is_ok());
+}
+
+#[actix_web::test]
+async fn expired_token_timing() {
+    let issued_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let now = issued_at + TimeDelta::try_minutes(5).unwrap();
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(time::fixed_clock(issued_at))
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .time_options(time::fixed_clock(now))
+        .verifying_key(KEY_PAIR.pk)
+        .renew_access_token_automatically(false)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(token_signer.create_access_cookie(&TestClaims {}).unwrap())
+        .to_srv_request();
+    let err = authority
+        .verify_service_request(&mut req)
+        .await
+        .expect_err("Testing expired token case");
+    let expiration = issued_at + TimeDelta::try_minutes(1).unwrap();
+    assert!(matches!(
+        err,
+        AuthError::TokenValidation(
+            TokenExpired,
+            Some(TokenTiming {
+                expiration: Some(timing_expiration),
+                not_before: None,
+                now: timing_now,
+            }),
+        ) if timing_expiration == expiration && timing_now == now
+    ));
+    #[cfg(debug_assertions)]
+    assert!(err.to_string().contains(&format!(
+        "Timing: exp {}, nbf unset, server time {}",
+        expiration.to_rfc3339(),
+        now.to_rfc3339()
+    )));
+}
+
+#[test]
+fn fixed_clocks_are_independent() {
+    let now = Utc::now();
+    let earlier = now - TimeDelta::try_minutes(5).unwrap();
+    let current: time::SharedTimeOptions = time::fixed_clock(now).into();
+    let previous: time::SharedTimeOptions = time::fixed_clock(earlier).into();
+
+    assert_eq!(current.now(), now);
+    assert_eq!(previous.now(), earlier);
+}
+
+#[actix_web::test]
+async fn verifying_keys_selected_by_kid() {
+    let key_pairs = [KeyPair::generate(), KeyPair::generate()];
+    let token_signer = |key_id: &str, key_pair: &KeyPair| -> TokenSigner<TestClaims, _> {
+        TokenSigner::new()
+            .algorithm(Ed25519)
+            .signing_key(key_pair.sk.clone())
+            .header(Header::empty().with_key_id(key_id))
+            .build()
+            .unwrap()
+    };
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .time_options(*TIME_OPTIONS)
+        .verifying_key(KEY_PAIR.pk)
+        .verifying_keys(HashMap::from([
+            ("first".to_string(), key_pairs[0].pk),
+            ("second".to_string(), key_pairs[1].pk),
+        ]))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    for (key_id, key_pair) in [("first", &key_pairs[0]), ("second", &key_pairs[1])] {
+        let mut req = TestRequest::default()
+            .cookie(
+                token_signer(key_id, key_pair)
+                    .create_access_cookie(&TestClaims {})
+                    .unwrap(),
+            )
+            .to_srv_request();
+        assert!(authority.verify_service_request(&mut req).await.is_ok());
+    }
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer("second", &key_pairs[0])
+                .create_access_cookie(&TestClaims {})
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing mismatching kid case"),
+        AuthError::TokenValidation(InvalidSignature, None)
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer("third", &key_pairs[0])
+                .create_access_cookie(&TestClaims {})
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing unknown kid case"),
+        AuthError::UnknownKeyId
+    );
+}
+
+#[actix_web::test]
+async fn public_config() {
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .verifying_key(KEY_PAIR.pk)
+        .enable_query_tokens(true)
+        .query_access_token_name("token")
+        .verifying_keys(HashMap::from([("first".to_string(), KEY_PAIR.pk)]))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let config = authority.public_config();
+    assert_eq!(config.algorithm, "EdDSA");
+    assert_eq!(config.verifying_key_ids, ["first"]);
+    assert_eq!(config.access_token_name, "access_token");
+    assert_eq!(config.query_access_token_name, "token");
+    assert_eq!(config.query_refresh_token_name, "refresh_token");
+    assert!(config.enable_query_tokens);
+    assert!(config.require_tls_for_query_tokens);
+    assert!(config.enable_cookie_tokens);
+    assert!(config.has_token_signer);
+    assert!(!config.has_async_token_signer);
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct VersionedClaims {
+    sub: String,
+    token_version: u64,
+}
+
+impl TokenVersion for VersionedClaims {
+    fn subject(&self) -> String {
+        self.sub.clone()
+    }
+
+    fn token_version(&self) -> u64 {
+        self.token_version
+    }
+}
+
+#[actix_web::test]
+async fn superseded_token_version() {
+    let token_signer: TokenSigner<VersionedClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<VersionedClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .min_token_version(|subject: String| async move {
+            if subject == "logged-out-everywhere" {
+                2
+            } else {
+                0
+            }
+        })
+        .build()
+        .unwrap();
+
+    let claims = |sub: &str, token_version| VersionedClaims {
+        sub: sub.to_string(),
+        token_version,
+    };
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&claims("logged-out-everywhere", 2))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&claims("logged-out-everywhere", 1))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing superseded access token case"),
+        AuthError::TokenSuperseded
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_refresh_cookie(&claims("logged-out-everywhere", 1))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing superseded refresh token case"),
+        AuthError::TokenSuperseded
+    );
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&claims("someone-else", 1))
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct NumericIdClaims {
+    id: u32,
+}
+
+#[actix_web::test]
+async fn claims_not_matching_the_schema() {
+    let token_signer: TokenSigner<serde_json::Value, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<NumericIdClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .renew_access_token_automatically(false)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&serde_json::json!({ "id": "not a number" }))
+                .unwrap(),
+        )
+        .to_srv_request();
+
+    assert!(matches!(
+        authority.verify_service_request(&mut req).await,
+        Err(AuthError::ClaimsDeserialization(_))
+    ));
+
+    let forged_signer: TokenSigner<serde_json::Value, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KeyPair::generate().sk)
+        .build()
+        .unwrap();
+    let mut req = TestRequest::default()
+        .cookie(
+            forged_signer
+                .create_access_cookie(&serde_json::json!({ "id": "not a number" }))
+                .unwrap(),
+        )
+        .to_srv_request();
+
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing forged token with mismatching claims case"),
+        AuthError::TokenValidation(InvalidSignature, None)
+    );
+}
+
+#[actix_web::test]
+async fn jwt_config() {
+    let (token_signer, authority_builder) = JwtConfig::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .verifying_key(KEY_PAIR.pk)
+        .access_token_name("session")
+        .build()
+        .unwrap()
+        .into_parts::<TestClaims, _, _>();
+    let authority = authority_builder
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let access_cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    assert_eq!(access_cookie.name(), "session");
+    assert_eq!(authority.public_config().access_token_name, "session");
+
+    let mut req = TestRequest::default()
+        .cookie(access_cookie)
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    assert!(JwtConfig::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .verifying_key(KeyPair::generate().pk)
+        .build()
+        .is_err());
+}
+
+#[actix_web::test]
+async fn unsigned_token_with_alg_none() {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(TOKEN_SIGNER.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let signed_token = TOKEN_SIGNER
+        .create_signed_token(&TestClaims {}, std::time::Duration::from_secs(60))
+        .unwrap();
+    let encoded_claims = signed_token.split('.').nth(1).unwrap();
+    let unsigned_header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#);
+
+    for unsigned_token in [
+        format!("{unsigned_header}.{encoded_claims}."),
+        format!("{unsigned_header}.{encoded_claims}"),
+    ] {
+        let req = TestRequest::default()
+            .cookie(Cookie::new("access_token", unsigned_token))
+            .to_http_request();
+
+        let result = authority.verify_http_request(&req);
+        assert!(
+            matches!(
+                result,
+                Err(AuthError::AlgorithmMismatch | AuthError::TokenParse(_))
+            ),
+            "unsigned token was not rejected: {result:?}"
+        );
+    }
+}
+
+#[test]
+fn auth_error_snapshot() {
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    let err = AuthError::TokenValidation(TokenExpired, None);
+    let snapshot = err.to_static();
+    let cloned_snapshot = snapshot.clone();
+
+    assert_eq!(snapshot, cloned_snapshot);
+    assert_eq!(cloned_snapshot.status_code(), StatusCode::UNAUTHORIZED);
+    assert_eq!(cloned_snapshot.message(), err.to_string());
+    assert_eq!(cloned_snapshot.to_string(), err.to_string());
+}
+
+#[test]
+fn peek_unverified_claims() {
+    use actix_jwt_auth_middleware::peek_unverified_claims;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Subject {
+        sub: String,
+    }
+
+    let forging_token_signer: TokenSigner<Subject, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KeyPair::generate().sk)
+        .build()
+        .unwrap();
+    let forged_token = forging_token_signer
+        .create_access_token(&Subject {
+            sub: "ferris".to_string(),
+        })
+        .unwrap();
+
+    assert_eq!(
+        peek_unverified_claims::<Subject>(&forged_token).unwrap(),
+        Subject {
+            sub: "ferris".to_string()
+        }
+    );
+    assert!(peek_unverified_claims::<Subject>("not a token").is_err());
+    assert!(peek_unverified_claims::<Subject>(
+        &TOKEN_SIGNER.create_access_token(&TestClaims {}).unwrap()
+    )
+    .is_err());
+
+    let compressing_token_signer: TokenSigner<Subject, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KeyPair::generate().sk)
+        .compress_claims(true)
+        .build()
+        .unwrap();
+    let oversized_token = compressing_token_signer
+        .create_access_token(&Subject {
+            sub: "a".repeat(1024 * 1024),
+        })
+        .unwrap();
+    assert!(matches!(
+        peek_unverified_claims::<Subject>(&oversized_token),
+        Err(AuthError::TokenValidation(MalformedClaims(_), _))
+    ));
+}
+
+#[cfg(feature = "jsonschema")]
+#[actix_web::test]
+async fn claims_schema_violation() {
+    use actix_jwt_auth_middleware::ClaimsSchema;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize, Clone)]
+    struct RoleClaims {
+        role: String,
+    }
+
+    let token_signer: TokenSigner<RoleClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let authority: Authority<RoleClaims, _, _, _> = Authority::new()
+        .token_signer(Some(token_signer.clone()))
+        .verifying_key(KEY_PAIR.pk)
+        .claims_schema(
+            ClaimsSchema::new(&json!({
+                "type": "object",
+                "properties": {
+                    "role": { "enum": ["admin", "user"] }
+                },
+                "required": ["role", "exp"]
+            }))
+            .unwrap(),
+        )
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&RoleClaims {
+                    role: "admin".into(),
+                })
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert!(authority.verify_service_request(&mut req).await.is_ok());
+
+    let mut req = TestRequest::default()
+        .cookie(
+            token_signer
+                .create_access_cookie(&RoleClaims {
+                    role: "root".into(),
+                })
+                .unwrap(),
+        )
+        .to_srv_request();
+    assert!(matches!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing claims schema violation case"),
+        AuthError::ClaimsSchemaViolation(_)
+    ));
+}
+
+#[cfg(feature = "detached-payload")]
+#[actix_web::test]
+async fn detached_payload() {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jwt_compact::{Algorithm, AlgorithmSignature};
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct OrderClaims {
+        order_id: u32,
+        client_id: String,
+    }
+
+    let authority: Authority<OrderClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .expected_token_type("JWT")
+        .allowed_client_ids(["trusted-client".to_string()])
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let payload = serde_json::to_vec(
+        &Claims::new(OrderClaims {
+            order_id: 42,
+            client_id: "trusted-client".into(),
+        })
+        .set_duration(&TimeOptions::default(), TimeDelta::try_minutes(5).unwrap()),
+    )
+    .unwrap();
+    let detached_token = |header: serde_json::Value, signed_payload: &[u8]| {
+        let encoded_header = URL_SAFE_NO_PAD.encode(header.to_string());
+        let mut signing_input = format!("{encoded_header}.").into_bytes();
+        signing_input.extend_from_slice(signed_payload);
+        let signature = Ed25519.sign(&KEY_PAIR.sk, &signing_input);
+        format!(
+            "{encoded_header}..{}",
+            URL_SAFE_NO_PAD.encode(signature.as_bytes())
+        )
+    };
+
+    let unencoded_token = detached_token(
+        serde_json::json!({ "alg": Ed25519.name(), "typ": "JWT", "b64": false, "crit": ["b64"] }),
+        &payload,
+    );
+    let claims = authority
+        .verify_detached_token(&unencoded_token, &payload)
+        .await
+        .unwrap();
+    assert_eq!(
+        claims.custom,
+        OrderClaims {
+            order_id: 42,
+            client_id: "trusted-client".into(),
+        }
+    );
+
+    let encoded_token = detached_token(
+        serde_json::json!({ "alg": Ed25519.name(), "typ": "JWT" }),
+        URL_SAFE_NO_PAD.encode(&payload).as_bytes(),
+    );
+    assert!(authority
+        .verify_detached_token(&encoded_token, &payload)
+        .await
+        .is_ok());
+
+    let tampered_payload = serde_json::to_vec(
+        &Claims::new(OrderClaims {
+            order_id: 43,
+            client_id: "trusted-client".into(),
+        })
+        .set_duration(&TimeOptions::default(), TimeDelta::try_minutes(5).unwrap()),
+    )
+    .unwrap();
+    assert_eq!(
+        authority
+            .verify_detached_token(&unencoded_token, &tampered_payload)
+            .await
+            .unwrap_err(),
+        AuthError::TokenValidation(InvalidSignature, None)
+    );
+
+    let refresh_token = detached_token(
+        serde_json::json!({ "alg": Ed25519.name(), "typ": "refresh+jwt" }),
+        URL_SAFE_NO_PAD.encode(&payload).as_bytes(),
+    );
+    assert_eq!(
+        authority
+            .verify_detached_token(&refresh_token, &payload)
+            .await
+            .unwrap_err(),
+        AuthError::TokenTypeMismatch
+    );
+
+    let unknown_critical_token = detached_token(
+        serde_json::json!({ "alg": Ed25519.name(), "typ": "JWT", "b64": false, "crit": ["b64", "exp"] }),
+        &payload,
+    );
+    assert!(matches!(
+        authority
+            .verify_detached_token(&unknown_critical_token, &payload)
+            .await
+            .unwrap_err(),
+        AuthError::TokenParse(_)
+    ));
+
+    let untrusted_payload = serde_json::to_vec(
+        &Claims::new(OrderClaims {
+            order_id: 42,
+            client_id: "untrusted-client".into(),
+        })
+        .set_duration(&TimeOptions::default(), TimeDelta::try_minutes(5).unwrap()),
+    )
+    .unwrap();
+    let untrusted_token = detached_token(
+        serde_json::json!({ "alg": Ed25519.name(), "typ": "JWT", "b64": false, "crit": ["b64"] }),
+        &untrusted_payload,
+    );
+    assert_eq!(
+        authority
+            .verify_detached_token(&untrusted_token, &untrusted_payload)
+            .await
+            .unwrap_err(),
+        AuthError::ClientNotAllowed
+    );
+}
+
+#[actix_web::test]
+async fn multiple_cookies_with_the_same_name() {
+    use actix_web::http::header::COOKIE;
+
+    let stale_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() - TimeDelta::try_hours(1).unwrap()
+        }))
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .renew_access_token_automatically(false)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let stale_cookie = stale_token_signer
+        .create_access_cookie(&TestClaims {})
+        .unwrap();
+    let fresh_cookie = TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap();
+
+    let mut req = TestRequest::default()
+        .insert_header((
+            COOKIE,
+            format!("{}; {}", stale_cookie.stripped(), fresh_cookie.stripped()),
+        ))
+        .to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok())
+}
+
+#[actix_web::test]
+async fn expired_cookie_preferred_over_garbage_cookie_with_the_same_name() {
+    use actix_web::http::header::COOKIE;
+
+    let expired_token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(TimeOptions::new(TimeDelta::zero(), || {
+            Utc::now() - TimeDelta::try_hours(1).unwrap()
+        }))
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(TimeOptions::default())
+        .renew_access_token_automatically(false)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let expired_cookie = expired_token_signer
+        .create_access_cookie(&TestClaims {})
+        .unwrap();
+    let garbage_cookie = Cookie::new(expired_cookie.name().to_string(), "garbage");
+
+    let mut req = TestRequest::default()
+        .insert_header((
+            COOKIE,
+            format!(
+                "{}; {}",
+                expired_cookie.stripped(),
+                garbage_cookie.stripped()
+            ),
+        ))
+        .to_srv_request();
+
+    assert!(matches!(
+        authority.verify_service_request(&mut req).await,
+        Err(AuthError::TokenValidation(TokenExpired, _))
+    ))
+}
+
+#[actix_web::test]
+async fn expired_refresh_token_renewed_from_verified_cookie() {
+    use actix_web::http::header::COOKIE;
+
+    let expired_time_options: TimeOptions = TimeOptions::new(TimeDelta::zero(), || {
+        Utc::now() - TimeDelta::try_hours(1).unwrap()
+    });
+    let token_signer: TokenSigner<NumericIdClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .build()
+        .unwrap();
+    let expired_token_signer = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .time_options(expired_time_options)
+        .build()
+        .unwrap();
+    let forged_token_signer = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KeyPair::generate().sk)
+        .time_options(expired_time_options)
+        .build()
+        .unwrap();
+    let authority: Authority<NumericIdClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer))
+        .renew_refresh_token_automatically(true)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let forged_cookie = forged_token_signer
+        .create_refresh_cookie(&NumericIdClaims { id: 2 })
+        .unwrap();
+    let expired_cookie = expired_token_signer
+        .create_refresh_cookie(&NumericIdClaims { id: 1 })
+        .unwrap();
+
+    let mut req = TestRequest::default()
+        .insert_header((
+            COOKIE,
+            format!(
+                "refresh_token=not-a-token; {}; {}",
+                forged_cookie.stripped(),
+                expired_cookie.stripped()
+            ),
+        ))
+        .to_srv_request();
+
+    let token_update = authority
+        .verify_service_request(&mut req)
+        .await
+        .unwrap()
+        .expect("the tokens should be renewed");
+    assert!(token_update.refresh_cookie().is_some());
+    assert_eq!(
+        req.extensions().get::<NumericIdClaims>(),
+        Some(&NumericIdClaims { id: 1 })
+    );
+
+    let mut req = TestRequest::default()
+        .insert_header((
+            COOKIE,
+            format!("refresh_token=not-a-token; {}", forged_cookie.stripped()),
+        ))
+        .to_srv_request();
+
+    assert_eq!(
+        authority
+            .verify_service_request(&mut req)
+            .await
+            .expect_err("Testing forged expired refresh token case"),
+        AuthError::TokenValidation(InvalidSignature, None)
+    );
+}
+
+#[actix_web::test]
+async fn encoded_cookie_value() {
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .encode_cookie_value(true)
+        .build()
+        .unwrap();
+    let authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+    assert!(!cookie.value().contains('.'));
+
+    let mut req = TestRequest::default().cookie(cookie).to_srv_request();
+
+    assert!(authority.verify_service_request(&mut req).await.is_ok())
+}
+
+#[actix_web::test]
+async fn token_source_order() {
+    let authority_builder = || {
+        Authority::<TestClaims, _, _, _>::new()
+            .algorithm(Ed25519)
+            .verifying_key(KEY_PAIR.pk)
+            .time_options(*TIME_OPTIONS)
+            .enable_header_tokens(true)
+            .renew_access_token_automatically(false)
+            .refresh_authorizer(|| async { Ok(()) })
+    };
+    let header_first = authority_builder().build().unwrap();
+    let cookie_first = authority_builder()
+        .token_source_order(vec![TokenSource::Cookie, TokenSource::Header])
+        .build()
+        .unwrap();
+
+    let cookie = TOKEN_SIGNER.create_access_cookie(&TestClaims {}).unwrap();
+    let test_request = || {
+        TestRequest::default()
+            .cookie(cookie.clone())
+            .insert_header((cookie.name(), "not-a-token"))
+            .to_srv_request()
+    };
+
+    assert!(matches!(
+        header_first
+            .verify_service_request(&mut test_request())
+            .await,
+        Err(AuthError::TokenParse(_))
+    ));
+    assert!(cookie_first
+        .verify_service_request(&mut test_request())
+        .await
+        .is_ok());
+}
+
+#[cfg(feature = "cbor")]
+#[actix_web::test]
+async fn cbor_claims() {
+    use actix_jwt_auth_middleware::ClaimsFormat;
+
+    let token_signer: TokenSigner<TestClaims, _> = TokenSigner::new()
+        .algorithm(Ed25519)
+        .signing_key(KEY_PAIR.sk.clone())
+        .claims_format(ClaimsFormat::Cbor)
+        .build()
+        .unwrap();
+    let cbor_authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .verifying_key(KEY_PAIR.pk)
+        .token_signer(Some(token_signer.clone()))
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+    let json_authority: Authority<TestClaims, _, _, _> = Authority::new()
+        .algorithm(Ed25519)
+        .verifying_key(KEY_PAIR.pk)
+        .time_options(*TIME_OPTIONS)
+        .refresh_authorizer(|| async { Ok(()) })
+        .build()
+        .unwrap();
+
+    let cookie = token_signer.create_access_cookie(&TestClaims {}).unwrap();
+
+    let mut req = TestRequest::default()
+        .cookie(cookie.clone())
+        .to_srv_request();
+    assert!(cbor_authority
+        .verify_service_request(&mut req)
+        .await
+        .is_ok());
+
+    let mut req = TestRequest::default().cookie(cookie).to_srv_request();
+    assert!(matches!(
+        json_authority.verify_service_request(&mut req).await,
+        Err(AuthError::TokenParse(_))
+    ));
+}
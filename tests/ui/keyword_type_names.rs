@@ -0,0 +1,12 @@
+use actix_jwt_auth_middleware::FromRequest;
+
+// the lowercased names of these types are keywords
+#[derive(Clone, FromRequest)]
+struct Fn;
+
+#[derive(Clone, FromRequest)]
+struct Struct {
+    id: u32,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use actix_jwt_auth_middleware::FromRequest;
+
+struct FullClaims {
+    user: User,
+    scopes: Vec<String>,
+}
+
+#[derive(Clone, FromRequest)]
+#[from_request(map = "|claims: &FullClaims| claims.user.clone()")]
+struct User {
+    id: u32,
+}
+
+#[derive(FromRequest)]
+#[from_request(map = "|claims: &FullClaims| Scopes(claims.scopes.clone())")]
+struct Scopes(Vec<String>);
+
+fn main() {}
@@ -0,0 +1,11 @@
+// shadows the `actix_web` crate, just like renaming the dependency in the Cargo.toml would
+mod actix_web {}
+
+use actix_jwt_auth_middleware::FromRequest;
+
+#[derive(Clone, FromRequest)]
+struct User {
+    id: u32,
+}
+
+fn main() {}